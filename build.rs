@@ -1,6 +1,31 @@
+fn serialize_enum(name: &str) -> String {
+    format!(
+        "#[serde(serialize_with = \"crate::proto::serialize_enum::<crate::proto::{}, _>\")]",
+        name
+    )
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     tonic_build::configure()
         .out_dir("src/proto/") // you can change the generated code's location
+        .type_attribute(".", "#[derive(serde::Serialize)]")
+        // Enum fields are plain numbers, print them by name.
+        .field_attribute(
+            "swandns.RecordsQueryRequest.health",
+            serialize_enum("HealthFilter"),
+        )
+        .field_attribute(
+            "swandns.RecordsQueryRequest.order_by",
+            serialize_enum("RecordOrder"),
+        )
+        .field_attribute(
+            "swandns.RecordChange.action",
+            serialize_enum("ChangeAction"),
+        )
+        .field_attribute(
+            "swandns.HistoryEntry.action",
+            serialize_enum("HistoryAction"),
+        )
         .compile(&["proto/swandns.proto"], &["proto/"])
         .unwrap();
     Ok(())
@@ -69,6 +69,7 @@
             Env = [
               "SWANDNS_CONFIG=/data/client.yaml"
               "SWANDNS_SCHEDULE=*/5 * * * *"
+              "SWANDNS_STATE_FILE=/data/update-state.json"
             ];
             Cmd = [
               "${pkgs.bash}/bin/bash"
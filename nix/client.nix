@@ -21,6 +21,13 @@ in {
       description = "Group under which swandns-update runs";
     };
 
+    watch = mkOption {
+      type = bool;
+      default = false;
+      description =
+        "Keep running and update as soon as interface addresses change, instead of using a timer";
+    };
+
     settings = mkOption {
       type = submodule { freeformType = settingsFormat.type; };
       default = { };
@@ -40,15 +47,18 @@ in {
       wantedBy = [ "multi-user.target" ];
       startLimitIntervalSec = 30;
       startLimitBurst = 5;
+      environment.SWANDNS_STATE_FILE = "/var/lib/swandns-update/state.json";
       serviceConfig = {
-        Type = "oneshot";
-        ExecStart = "${cfg.package}/bin/swandns-update --config ${configFile}";
+        Type = if cfg.watch then "simple" else "oneshot";
+        ExecStart = "${cfg.package}/bin/swandns-update --config ${configFile}"
+          + optionalString cfg.watch " --watch";
         Restart = "on-failure";
         RestartSec = 5;
+        StateDirectory = "swandns-update";
       };
     };
 
-    systemd.timers.swandns-update = {
+    systemd.timers.swandns-update = mkIf (!cfg.watch) {
       wantedBy = [ "timers.target" ];
       timerConfig = {
         OnUnitActiveSec = "5m";
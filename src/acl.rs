@@ -0,0 +1,38 @@
+use crate::util::{canonical_ip, parse_cidr};
+use crate::AclConfig;
+use anyhow::Result;
+use ipnet::IpNet;
+use std::net::IpAddr;
+
+/// Allow and deny lists of client networks. Denies take precedence, and an empty allow list
+/// allows everyone that isn't denied.
+#[derive(Debug, Clone, Default)]
+pub struct Acl {
+    pub allow: Vec<IpNet>,
+    pub deny: Vec<IpNet>,
+}
+
+impl Acl {
+    pub fn from_config(cfg: &AclConfig) -> Result<Acl> {
+        Ok(Acl {
+            allow: cfg
+                .allow
+                .iter()
+                .map(|value| parse_cidr(value))
+                .collect::<Result<Vec<IpNet>>>()?,
+            deny: cfg
+                .deny
+                .iter()
+                .map(|value| parse_cidr(value))
+                .collect::<Result<Vec<IpNet>>>()?,
+        })
+    }
+
+    pub fn permits(&self, ip_addr: IpAddr) -> bool {
+        let ip_addr = canonical_ip(ip_addr);
+        if self.deny.iter().any(|net| net.contains(&ip_addr)) {
+            return false;
+        }
+        self.allow.is_empty() || self.allow.iter().any(|net| net.contains(&ip_addr))
+    }
+}
@@ -0,0 +1,87 @@
+use crate::util::parse_cidr;
+use anyhow::Result;
+use ipnet::IpNet;
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv6Addr};
+use tracing::debug;
+
+/// `IFA_F_TEMPORARY` from `linux/if_addr.h`.
+static IFA_F_TEMPORARY: u32 = 0x01;
+
+/// Addresses to leave out when publishing an interface's addresses.
+///
+/// Built from a list of `link-local`, `ula`, `temporary` and CIDRs.
+#[derive(Debug, Clone, Default)]
+pub struct AddressFilter {
+    link_local: bool,
+    unique_local: bool,
+    temporary: bool,
+    networks: Vec<IpNet>,
+}
+
+impl AddressFilter {
+    pub fn from_config(exclude: &[String]) -> Result<Self> {
+        let mut filter = Self::default();
+        for entry in exclude.iter() {
+            match entry.to_ascii_lowercase().as_str() {
+                "link-local" => filter.link_local = true,
+                "ula" => filter.unique_local = true,
+                "temporary" => filter.temporary = true,
+                _ => filter.networks.push(parse_cidr(entry)?),
+            }
+        }
+        Ok(filter)
+    }
+
+    /// Whether an address should be published, `temporary` being the interface's temporary
+    /// privacy addresses.
+    pub fn permits(&self, ip_addr: IpAddr, temporary: &HashSet<Ipv6Addr>) -> bool {
+        let excluded = match ip_addr {
+            IpAddr::V4(v4) => self.link_local && v4.is_link_local(),
+            IpAddr::V6(v6) => {
+                (self.link_local && (v6.segments()[0] & 0xffc0) == 0xfe80)
+                    || (self.unique_local && (v6.segments()[0] & 0xfe00) == 0xfc00)
+                    || (self.temporary && temporary.contains(&v6))
+            }
+        } || self.networks.iter().any(|net| net.contains(&ip_addr));
+        if excluded {
+            debug!("Excluding address {:?}", ip_addr);
+        }
+        !excluded
+    }
+
+    pub fn excludes_temporary(&self) -> bool {
+        self.temporary
+    }
+}
+
+/// Lists the temporary IPv6 privacy addresses (RFC 8981) of the host.
+///
+/// Only Linux exposes these, through `/proc/net/if_inet6`. Elsewhere no addresses are temporary.
+pub fn temporary_addresses() -> HashSet<Ipv6Addr> {
+    let Ok(contents) = std::fs::read_to_string("/proc/net/if_inet6") else {
+        return HashSet::new();
+    };
+    parse_if_inet6(&contents)
+}
+
+/// Parses the temporary addresses out of `/proc/net/if_inet6`.
+///
+/// Each line is `<address> <index> <prefix length> <scope> <flags> <interface>` in hex.
+pub fn parse_if_inet6(contents: &str) -> HashSet<Ipv6Addr> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 6 || fields[0].len() != 32 {
+                return None;
+            }
+            let flags = u32::from_str_radix(fields[4], 16).ok()?;
+            if flags & IFA_F_TEMPORARY == 0 {
+                return None;
+            }
+            let address = u128::from_str_radix(fields[0], 16).ok()?;
+            Some(Ipv6Addr::from(address))
+        })
+        .collect()
+}
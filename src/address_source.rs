@@ -0,0 +1,212 @@
+use crate::stun::query_stun_server;
+use crate::util::{get_iface_addrs, lookup_server};
+use crate::{AddressSourceConfig, SourceStrategy};
+use anyhow::{anyhow, Result};
+use hickory_server::proto::rr::{RData, RecordType};
+use hickory_server::resolver::config::{NameServerConfigGroup, ResolverConfig, ResolverOpts};
+use hickory_server::resolver::{Name, TokioAsyncResolver};
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::process::Command;
+use tokio::task::JoinSet;
+use tracing::{debug, warn};
+
+static SOURCE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Finds the addresses to publish for a record from its configured sources.
+///
+/// With [`SourceStrategy::Fallback`] the sources are tried in order until one returns an address.
+/// With [`SourceStrategy::Consensus`] every source is asked and only the addresses reported by a
+/// majority of them are kept, see [`consensus`].
+pub async fn resolve_sources(
+    sources: &[AddressSourceConfig],
+    strategy: SourceStrategy,
+    bind: Option<String>,
+    protocol: Option<String>,
+) -> Result<Vec<IpAddr>> {
+    // Fail on a bad protocol before asking anyone.
+    protocol_filter(protocol.as_deref())?;
+    match strategy {
+        SourceStrategy::Fallback => {
+            for source in sources {
+                match resolve_source(source.clone(), bind.clone(), protocol.clone()).await {
+                    Ok(ip_addrs) if !ip_addrs.is_empty() => return Ok(ip_addrs),
+                    Ok(_) => debug!("No addresses from {:?}", source),
+                    Err(err) => warn!("Address source {:?} failed: {}", source, err),
+                }
+            }
+            Err(anyhow!("No address source returned an address"))
+        }
+        SourceStrategy::Consensus => {
+            let mut tasks = JoinSet::new();
+            for source in sources {
+                tasks.spawn(resolve_source(
+                    source.clone(),
+                    bind.clone(),
+                    protocol.clone(),
+                ));
+            }
+            let mut results = vec![];
+            while let Some(result) = tasks.join_next().await {
+                match result? {
+                    Ok(ip_addrs) if !ip_addrs.is_empty() => results.push(ip_addrs),
+                    Ok(_) => {}
+                    Err(err) => warn!("Address source failed: {}", err),
+                }
+            }
+            if results.is_empty() {
+                return Err(anyhow!("No address source returned an address"));
+            }
+            let ip_addrs = consensus(&results);
+            if ip_addrs.is_empty() {
+                return Err(anyhow!("Address sources disagree: {:?}", results));
+            }
+            Ok(ip_addrs)
+        }
+    }
+}
+
+/// Keeps the addresses reported by more than half of the sources that answered for their family.
+///
+/// Families are counted separately, so IPv4-only sources don't outvote an IPv6 source.
+pub fn consensus(results: &[Vec<IpAddr>]) -> Vec<IpAddr> {
+    let mut ip_addrs = vec![];
+    for is_family in [IpAddr::is_ipv4 as fn(&IpAddr) -> bool, IpAddr::is_ipv6] {
+        let answers: Vec<&Vec<IpAddr>> = results
+            .iter()
+            .filter(|result| result.iter().any(is_family))
+            .collect();
+        for ip_addr in answers.iter().flat_map(|result| result.iter()) {
+            if !is_family(ip_addr) || ip_addrs.contains(ip_addr) {
+                continue;
+            }
+            let votes = answers
+                .iter()
+                .filter(|result| result.contains(ip_addr))
+                .count();
+            if votes * 2 > answers.len() {
+                ip_addrs.push(*ip_addr);
+            }
+        }
+    }
+    ip_addrs
+}
+
+/// Parses whitespace separated addresses, like the body of a "what is my IP" endpoint.
+pub fn parse_addresses(text: &str) -> Result<Vec<IpAddr>> {
+    text.split_whitespace()
+        .map(|value| {
+            IpAddr::from_str(value).map_err(|_| anyhow!("{:?} is not an IP address", value))
+        })
+        .collect()
+}
+
+async fn resolve_source(
+    source: AddressSourceConfig,
+    bind: Option<String>,
+    protocol: Option<String>,
+) -> Result<Vec<IpAddr>> {
+    let ip_addrs = match tokio::time::timeout(
+        SOURCE_TIMEOUT,
+        query_source(&source, bind, protocol.clone()),
+    )
+    .await
+    {
+        Ok(ip_addrs) => ip_addrs?,
+        Err(_) => return Err(anyhow!("Timed out")),
+    };
+    let permits = protocol_filter(protocol.as_deref())?;
+    let ip_addrs: Vec<IpAddr> = ip_addrs.into_iter().filter(permits).collect();
+    debug!("Found ips {:?} from {:?}", ip_addrs, source);
+    Ok(ip_addrs)
+}
+
+async fn query_source(
+    source: &AddressSourceConfig,
+    bind: Option<String>,
+    protocol: Option<String>,
+) -> Result<Vec<IpAddr>> {
+    match source {
+        AddressSourceConfig::Interface { bind: source_bind } => {
+            get_iface_addrs(source_bind.clone().or(bind), protocol)
+        }
+        AddressSourceConfig::Http { url } => {
+            let body = reqwest::Client::builder()
+                .timeout(SOURCE_TIMEOUT)
+                .build()?
+                .get(url)
+                .send()
+                .await?
+                .error_for_status()?
+                .text()
+                .await?;
+            parse_addresses(&body)
+        }
+        AddressSourceConfig::Stun { server } => Ok(vec![query_stun_server(server).await?]),
+        AddressSourceConfig::Dns {
+            server,
+            name,
+            record_type,
+        } => query_dns(server, name, record_type.as_deref().unwrap_or("A")).await,
+        AddressSourceConfig::Static { value } => parse_addresses(value),
+        AddressSourceConfig::Command { command } => {
+            let (program, args) = command
+                .split_first()
+                .ok_or_else(|| anyhow!("Empty command"))?;
+            let output = Command::new(program)
+                .args(args)
+                .kill_on_drop(true)
+                .output()
+                .await?;
+            if !output.status.success() {
+                return Err(anyhow!("{:?} exited with {}", program, output.status));
+            }
+            parse_addresses(&String::from_utf8_lossy(&output.stdout))
+        }
+    }
+}
+
+/// Asks a nameserver for our address, e.g. `myip.opendns.com` A or `o-o.myaddr.l.google.com` TXT.
+async fn query_dns(server: &str, name: &str, record_type: &str) -> Result<Vec<IpAddr>> {
+    let server_addr = lookup_server(server, 53).await?;
+    let record_type = RecordType::from_str(&record_type.to_ascii_uppercase())?;
+    let mut name = Name::from_str(name)?;
+    name.set_fqdn(true);
+
+    let name_servers =
+        NameServerConfigGroup::from_ips_clear(&[server_addr.ip()], server_addr.port(), true);
+    let mut opts = ResolverOpts::default();
+    opts.cache_size = 0;
+    let resolver =
+        TokioAsyncResolver::tokio(ResolverConfig::from_parts(None, vec![], name_servers), opts);
+
+    let mut ip_addrs = vec![];
+    for rdata in resolver.lookup(name, record_type).await?.iter() {
+        match rdata {
+            RData::A(a) => ip_addrs.push(IpAddr::V4(a.0)),
+            RData::AAAA(aaaa) => ip_addrs.push(IpAddr::V6(aaaa.0)),
+            // Some TXT answers carry extra strings, like the EDNS Client Subnet seen by the server.
+            RData::TXT(txt) => ip_addrs.extend(
+                txt.iter()
+                    .filter_map(|data| std::str::from_utf8(data).ok())
+                    .filter_map(|data| IpAddr::from_str(data.trim()).ok()),
+            ),
+            _ => {}
+        }
+    }
+    Ok(ip_addrs)
+}
+
+fn protocol_filter(protocol: Option<&str>) -> Result<fn(&IpAddr) -> bool> {
+    let protocol = protocol.unwrap_or("ipv4");
+    if protocol.eq_ignore_ascii_case("both") {
+        Ok(|_| true)
+    } else if protocol.eq_ignore_ascii_case("ipv4") {
+        Ok(IpAddr::is_ipv4)
+    } else if protocol.eq_ignore_ascii_case("ipv6") {
+        Ok(IpAddr::is_ipv6)
+    } else {
+        Err(anyhow!("Unknown protocol {:?}", protocol))
+    }
+}
@@ -0,0 +1,158 @@
+use crate::record_repository::integrity_problems;
+use crate::record_store::RecordStore;
+use crate::util::{migrate_database, schema_status};
+use crate::{ServerConfig, StorageBackend};
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use cron_parser::parse;
+use rusqlite::backup::Progress;
+use rusqlite::DatabaseName;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::fs;
+use tokio::sync::watch;
+use tokio_rusqlite::Connection;
+use tracing::{error, info};
+
+/// Backups are named after the time they were taken, so they sort oldest first.
+static BACKUP_PREFIX: &str = "swandns-";
+static BACKUP_SUFFIX: &str = ".db";
+
+/// Where backups are written, `backup.dir` or `backups` in the data directory.
+pub fn backup_dir(cfg: &ServerConfig) -> PathBuf {
+    cfg.backup
+        .dir
+        .clone()
+        .unwrap_or_else(|| cfg.data_dir.join("backups"))
+}
+
+/// The backups in `dir`, oldest first.
+pub async fn list_backups(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut backups = vec![];
+    let mut entries = fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with(BACKUP_PREFIX) && name.ends_with(BACKUP_SUFFIX) {
+            backups.push(entry.path());
+        }
+    }
+    backups.sort();
+    Ok(backups)
+}
+
+/// Deletes all but the `keep` most recent backups in `dir`, `0` keeps them all. Returns the
+/// deleted backups.
+pub async fn prune_backups(dir: &Path, keep: usize) -> Result<Vec<PathBuf>> {
+    if keep == 0 {
+        return Ok(vec![]);
+    }
+    let mut backups = list_backups(dir).await?;
+    let excess = backups.len().saturating_sub(keep);
+    let pruned: Vec<PathBuf> = backups.drain(..excess).collect();
+    for path in pruned.iter() {
+        fs::remove_file(path).await?;
+    }
+    Ok(pruned)
+}
+
+/// Writes a snapshot of the store to the backup directory, then prunes the backups beyond
+/// `backup.keep`.
+///
+/// The snapshot only gets its final name once it's complete, so an interrupted backup is never
+/// mistaken for one.
+pub async fn create_backup(store: &dyn RecordStore, cfg: &ServerConfig) -> Result<PathBuf> {
+    let dir = backup_dir(cfg);
+    fs::create_dir_all(&dir).await?;
+    let name = format!(
+        "{}{}{}",
+        BACKUP_PREFIX,
+        Utc::now().format("%Y%m%dT%H%M%S%3fZ"),
+        BACKUP_SUFFIX
+    );
+    let path = dir.join(&name);
+    let partial = dir.join(format!("{}.partial", name));
+    store.backup(partial.clone()).await?;
+    fs::rename(&partial, &path).await?;
+    for pruned in prune_backups(&dir, cfg.backup.keep).await? {
+        info!("Deleted old backup {:?}", pruned);
+    }
+    Ok(path)
+}
+
+/// Takes a backup on `backup.schedule`, a cron expression, whenever it's set.
+pub async fn run_scheduled_backups(
+    store: Arc<dyn RecordStore>,
+    mut configs: watch::Receiver<Arc<ServerConfig>>,
+) -> Result<()> {
+    loop {
+        let cfg = configs.borrow_and_update().clone();
+        let Some(schedule) = cfg.backup.schedule.clone() else {
+            configs.changed().await?;
+            continue;
+        };
+        let now = Utc::now();
+        let upcoming = match parse(&schedule, &now) {
+            Ok(upcoming) => upcoming,
+            Err(err) => {
+                error!("Invalid backup schedule {:?}: {}", schedule, err);
+                configs.changed().await?;
+                continue;
+            }
+        };
+        // A reloaded config may change the schedule.
+        tokio::select! {
+            _ = tokio::time::sleep((upcoming - now).to_std().unwrap_or_default()) => {}
+            changed = configs.changed() => {
+                changed?;
+                continue;
+            }
+        }
+        match create_backup(store.as_ref(), &cfg).await {
+            Ok(path) => info!("Backed up the database to {:?}", path),
+            Err(err) => error!("Backup failed: {}", err),
+        }
+    }
+}
+
+/// Replaces the configured database with a backup.
+///
+/// The backup is checked for corruption and migrated to the latest schema in memory first, so a
+/// backup that can't be used leaves the database untouched. The server mustn't be running.
+pub async fn restore_backup(cfg: &ServerConfig, backup: &Path) -> Result<()> {
+    if cfg.storage != StorageBackend::Sqlite {
+        bail!("Backups can only be restored to SQLite storage");
+    }
+    // Opening a missing file would create an empty database.
+    if !backup.is_file() {
+        bail!("{:?} isn't a file", backup);
+    }
+    let source = backup.to_path_buf();
+    let conn = Connection::open_in_memory().await?;
+    let problems = conn
+        .call(move |conn| {
+            conn.restore(DatabaseName::Main, &source, None::<fn(Progress)>)?;
+            integrity_problems(conn)
+        })
+        .await?;
+    if !problems.is_empty() {
+        bail!("{:?} is corrupt: {}", backup, problems.join(", "));
+    }
+
+    let conn = Arc::new(conn);
+    if schema_status(&conn).await?.current == 0 {
+        bail!("{:?} isn't a Swan DNS database", backup);
+    }
+    migrate_database(conn.clone())
+        .await
+        .with_context(|| format!("Couldn't restore {:?}", backup))?;
+
+    fs::create_dir_all(&cfg.data_dir).await?;
+    let target = cfg.data_dir.join(&cfg.db_file);
+    info!("Restoring {:?} to {:?}", backup, target);
+    conn.call(move |conn| {
+        conn.backup(DatabaseName::Main, &target, None)?;
+        Ok(())
+    })
+    .await?;
+    Ok(())
+}
@@ -1,17 +1,34 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use chrono::Utc;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use cron_parser::parse;
+#[cfg(target_os = "linux")]
+use std::collections::HashSet;
+use std::net::IpAddr;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
-use swandns::client::update_records;
-use swandns::util::configure_tracing;
-use swandns::{load_config, ClientConfig};
+use swandns::client::{
+    delete_record, get_record, list_records, owner, publish_record, remove_record, remove_records,
+    render_reply, server_url, update_records,
+};
+#[cfg(target_os = "linux")]
+use swandns::client::update_interface_records;
+#[cfg(target_os = "linux")]
+use swandns::netlink::{interface_name, AddressWatcher};
+use swandns::dns_server::STATIC_RECORD_TTL;
+use swandns::proto::{FindUniqueRecordRequest, UpsertRecordRequest};
+use swandns::util::{configure_tracing, get_ip_addr_record_type};
+use swandns::{load_config, ClientConfig, ClientRecordConfig};
 use tokio_graceful_shutdown::{FutureExt, SubsystemHandle, Toplevel, SubsystemBuilder};
 use tracing::{debug, error, info};
 
 static CONF_NAME: &str = "client";
+/// Heartbeat for `--watch` without a `--schedule`, so records don't go unhealthy.
+static DEFAULT_HEARTBEAT: &str = "*/5 * * * *";
+/// How long to wait for more address changes before updating.
+#[cfg(target_os = "linux")]
+static SETTLE_TIME: Duration = Duration::from_secs(1);
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -20,6 +37,70 @@ struct Args {
     config: Option<PathBuf>,
     #[arg(short, long)]
     schedule: Option<String>,
+    /// Update as soon as an interface's addresses change, besides the schedule. Linux only.
+    #[arg(short, long)]
+    watch: bool,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Publish the configured records, on `--schedule` and/or `--watch`. This is the default.
+    Update,
+    /// Publish a record with the given values, replacing its previous values.
+    Publish {
+        name: String,
+        #[arg(required = true)]
+        values: Vec<String>,
+        /// Record type, defaults to A or AAAA depending on the first value.
+        #[arg(short, long = "type")]
+        r#type: Option<String>,
+        #[arg(long, default_value_t = STATIC_RECORD_TTL)]
+        ttl: u32,
+        /// Seconds the server keeps serving the record without it being published again, defaults
+        /// to the server's default lease.
+        #[arg(long)]
+        lease: Option<u32>,
+        /// View to publish the record to, defaults to all views.
+        #[arg(long)]
+        view: Option<String>,
+        /// Swan DNS API, defaults to `default_server_url`.
+        #[arg(long)]
+        server: Option<String>,
+        /// Take the record over even if another host still owns it.
+        #[arg(long)]
+        force: bool,
+    },
+    /// Delete a record from the server. Without a name every configured record is removed.
+    Remove {
+        name: Option<String>,
+        /// Record type, defaults to both A and AAAA.
+        #[arg(short, long = "type")]
+        r#type: Option<String>,
+        #[arg(long)]
+        view: Option<String>,
+        #[arg(long)]
+        server: Option<String>,
+        /// Delete the record even if another host still owns it.
+        #[arg(long)]
+        force: bool,
+    },
+    /// List the records on the server.
+    List {
+        #[arg(long)]
+        server: Option<String>,
+    },
+    /// Show a record from the server.
+    Get {
+        name: String,
+        #[arg(short, long = "type", default_value = "A")]
+        r#type: String,
+        #[arg(long)]
+        view: Option<String>,
+        #[arg(long)]
+        server: Option<String>,
+    },
 }
 
 async fn update_in_loop(
@@ -65,6 +146,82 @@ async fn update_in_loop(
     }
 }
 
+#[cfg(target_os = "linux")]
+async fn update_on_change(subsys: SubsystemHandle, cfg: Arc<ClientConfig>) -> Result<()> {
+    let mut watcher = AddressWatcher::new()?;
+    info!("Watching for interface address changes");
+
+    loop {
+        let mut indexes = match watcher.changed().cancel_on_shutdown(&subsys).await {
+            Ok(indexes) => indexes?,
+            Err(_) => return Ok(()),
+        };
+        // Changes come in bursts, e.g. a new DHCP lease removes one address and adds another.
+        while let Ok(more) = tokio::time::timeout(SETTLE_TIME, watcher.changed()).await {
+            indexes.extend(more?);
+        }
+
+        // Interfaces that can't be looked up anymore could have been any record's interface.
+        let interfaces: Option<HashSet<String>> = indexes.into_iter().map(interface_name).collect();
+        let result = match interfaces {
+            Some(interfaces) => {
+                info!("Addresses changed on {:?}", interfaces);
+                update_interface_records(cfg.clone(), &interfaces)
+                    .cancel_on_shutdown(&subsys)
+                    .await
+            }
+            None => {
+                info!("Addresses changed on a removed interface");
+                update_records(cfg.clone())
+                    .cancel_on_shutdown(&subsys)
+                    .await
+            }
+        };
+        match result {
+            Ok(Ok(_)) => info!("Records updated successfully"),
+            Ok(Err(err)) => error!("{:?}", err),
+            Err(_) => {
+                debug!("Cancelled by shutdown");
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+async fn update_on_change(_subsys: SubsystemHandle, _cfg: Arc<ClientConfig>) -> Result<()> {
+    Err(anyhow!("Watching interfaces is only supported on Linux"))
+}
+
+async fn update(cfg: Arc<ClientConfig>, schedule: Option<String>, watch: bool) -> Result<()> {
+    if schedule.is_none() && !watch {
+        update_records(cfg).await?;
+        return Ok(());
+    }
+
+    let schedule = schedule.unwrap_or(DEFAULT_HEARTBEAT.to_string());
+    let loop_cfg = cfg.clone();
+    let watch_cfg = cfg.clone();
+    let result = Toplevel::new(move |s| async move {
+        s.start(SubsystemBuilder::new("Schedule", |s| update_in_loop(s, loop_cfg, schedule)));
+        if watch {
+            s.start(SubsystemBuilder::new("Watch", |s| update_on_change(s, watch_cfg)));
+        }
+    })
+        .catch_signals()
+        .handle_shutdown_requests(Duration::from_millis(1000))
+        .await;
+
+    // A subsystem failing isn't the host going away, so the records are kept.
+    if cfg.remove_on_shutdown && result.is_ok() {
+        info!("Removing records before shutdown");
+        if let Err(err) = remove_records(cfg).await {
+            error!("Couldn't remove records: {:?}", err);
+        }
+    }
+    result.map_err(Into::into)
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     configure_tracing();
@@ -72,16 +229,92 @@ async fn main() -> Result<()> {
     let args: Args = Args::parse();
     let cfg: Arc<ClientConfig> = Arc::new(load_config(CONF_NAME, args.config).await?);
 
-    if let Some(schedule) = args.schedule {
-        return Toplevel::new(|s| async move {
-            s.start(SubsystemBuilder::new("Schedule", |s| update_in_loop(s, cfg, schedule)));
-        })
-            .catch_signals()
-            .handle_shutdown_requests(Duration::from_millis(1000))
-            .await
-            .map_err(Into::into);
-    } else {
-        update_records(cfg.clone()).await?;
+    match args.command.unwrap_or(Command::Update) {
+        Command::Update => update(cfg, args.schedule, args.watch).await,
+        Command::Publish {
+            name,
+            values,
+            r#type,
+            ttl,
+            lease,
+            view,
+            server,
+            force,
+        } => {
+            let r#type = match r#type {
+                Some(r#type) => r#type.to_uppercase(),
+                None => {
+                    let ip_addr: IpAddr = values[0]
+                        .parse()
+                        .map_err(|_| anyhow!("--type is required for {:?}", values[0]))?;
+                    get_ip_addr_record_type(&ip_addr)?.to_string()
+                }
+            };
+            let message = UpsertRecordRequest {
+                name,
+                r#type,
+                value: values[0].clone(),
+                ttl,
+                view: view.unwrap_or_default(),
+                r#static: false,
+                values,
+                lease: lease.unwrap_or_default(),
+                owner: owner(&cfg),
+                force,
+            };
+            let record = publish_record(server_url(&cfg, server), message).await?;
+            println!("{}", render_reply(&record));
+            Ok(())
+        }
+        Command::Remove {
+            name,
+            r#type,
+            view,
+            server,
+            force,
+        } => match (name, r#type) {
+            (None, _) => remove_records(cfg).await,
+            (Some(name), Some(r#type)) => {
+                let message = FindUniqueRecordRequest {
+                    name,
+                    r#type: r#type.to_uppercase(),
+                    view: view.unwrap_or_default(),
+                    owner: owner(&cfg),
+                    force,
+                };
+                delete_record(server_url(&cfg, server), message).await
+            }
+            (Some(name), None) => {
+                let record_config = ClientRecordConfig {
+                    server_url: server,
+                    name,
+                    view,
+                    ..Default::default()
+                };
+                remove_record(cfg, record_config, force).await
+            }
+        },
+        Command::List { server } => {
+            for record in list_records(server_url(&cfg, server)).await? {
+                println!("{}", render_reply(&record));
+            }
+            Ok(())
+        }
+        Command::Get {
+            name,
+            r#type,
+            view,
+            server,
+        } => {
+            let message = FindUniqueRecordRequest {
+                name,
+                r#type: r#type.to_uppercase(),
+                view: view.unwrap_or_default(),
+                ..Default::default()
+            };
+            let record = get_record(server_url(&cfg, server), message).await?;
+            println!("{}", render_reply(&record));
+            Ok(())
+        }
     }
-    Ok(())
 }
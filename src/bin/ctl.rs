@@ -0,0 +1,582 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use clap::{Parser, Subcommand};
+use serde::Serialize;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use swandns::client::{query_records, rpc_error};
+use swandns::dns_server::STATIC_RECORD_TTL;
+use swandns::output::{render, Table};
+use swandns::proto::admin_client::AdminClient;
+use swandns::proto::ping_client::PingClient;
+use swandns::proto::records_client::RecordsClient;
+use swandns::proto::{
+    BackupReply, BackupRequest, ConflictsReply, ConflictsRequest, FindUniqueRecordRequest,
+    HealthFilter, HistoryAction, HistoryReply, HistoryRequest, ImportReply, ImportRequest,
+    InfoReply, InfoRequest, IntegrityCheckReply, IntegrityCheckRequest, PingRequest, RecordReply,
+    RecordsQueryRequest, UpsertRecordRequest, ZonesReply, ZonesRequest,
+};
+use swandns::util::get_ip_addr_record_type;
+use swandns::zone_file::render_change;
+use swandns::{load_config, CtlConfig, OutputFormat};
+use tokio::fs;
+use tokio::io::AsyncReadExt;
+
+static CONF_NAME: &str = "ctl";
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+    /// Swan DNS API, defaults to `server_url` from the config.
+    #[arg(short, long)]
+    server: Option<String>,
+    /// Output format, defaults to `output` from the config.
+    #[arg(short, long, value_enum)]
+    output: Option<OutputFormat>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Inspect and manage records.
+    #[command(subcommand)]
+    Records(RecordsCommand),
+    /// Show the zones served and the health of their records.
+    Zones,
+    /// Check that the server is up and list unhealthy records. Fails when any record is unhealthy.
+    Health,
+    /// Show the server's version and settings.
+    Info,
+    /// List upserts rejected because another publisher owned the record, most recent first.
+    Conflicts,
+    /// List the changes made to records, most recent first.
+    History {
+        /// Only list changes within this zone.
+        #[arg(long)]
+        zone: Option<String>,
+        /// Only list changes to records with this name.
+        #[arg(long)]
+        name: Option<String>,
+        #[arg(short, long = "type")]
+        r#type: Option<String>,
+        #[arg(long)]
+        view: Option<String>,
+        /// Only list changes made by this actor, e.g. a client's address.
+        #[arg(long)]
+        actor: Option<String>,
+        /// List at most this many changes.
+        #[arg(short = 'n', long, default_value_t = 100)]
+        limit: u32,
+    },
+    /// Write a snapshot of the database to the server's backup directory.
+    Backup,
+    /// Check the database for corruption. Fails when any problem is found.
+    IntegrityCheck,
+}
+
+#[derive(Subcommand, Debug)]
+enum RecordsCommand {
+    /// List records from the database.
+    List {
+        /// Only list records within this zone.
+        #[arg(long)]
+        zone: Option<String>,
+        #[arg(short, long = "type")]
+        r#type: Option<String>,
+        /// Only list records published to this view, `*` for the ones published to all views.
+        #[arg(long)]
+        view: Option<String>,
+        /// Only list records with names matching this glob, e.g. `web-*`.
+        #[arg(long)]
+        name: Option<String>,
+        /// Only list records published by this owner.
+        #[arg(long)]
+        owner: Option<String>,
+        /// Only list unhealthy records.
+        #[arg(long)]
+        unhealthy: bool,
+    },
+    /// Show a record.
+    Get {
+        name: String,
+        #[arg(short, long = "type", default_value = "A")]
+        r#type: String,
+        #[arg(long)]
+        view: Option<String>,
+    },
+    /// Create or replace a record.
+    Set {
+        name: String,
+        #[arg(required = true)]
+        values: Vec<String>,
+        /// Record type, defaults to A or AAAA depending on the first value.
+        #[arg(short, long = "type")]
+        r#type: Option<String>,
+        #[arg(long, default_value_t = STATIC_RECORD_TTL)]
+        ttl: u32,
+        #[arg(long)]
+        view: Option<String>,
+        /// Always report the record healthy, instead of expecting it to be refreshed.
+        #[arg(long = "static")]
+        r#static: bool,
+        /// Seconds the record is served without being refreshed, defaults to the server's default
+        /// lease. Ignored for static records.
+        #[arg(long)]
+        lease: Option<u32>,
+        /// Publish the record under this owner, only it can replace the record afterwards.
+        #[arg(long)]
+        owner: Option<String>,
+        /// Replace the record even if someone else owns it.
+        #[arg(long)]
+        force: bool,
+    },
+    /// Delete a record.
+    Delete {
+        name: String,
+        #[arg(short, long = "type")]
+        r#type: String,
+        #[arg(long)]
+        view: Option<String>,
+        /// Delete the record as this owner, records owned by someone else are kept.
+        #[arg(long)]
+        owner: Option<String>,
+        /// Delete the record even if someone else owns it.
+        #[arg(long)]
+        force: bool,
+    },
+    /// Import records from an RFC 1035 master file.
+    Import {
+        /// Master file to import, `-` reads from stdin.
+        file: PathBuf,
+        /// Origin for relative names, unless the file sets $ORIGIN.
+        #[arg(long)]
+        origin: Option<String>,
+        #[arg(long)]
+        view: Option<String>,
+        /// Import as static records, which are always healthy.
+        #[arg(long = "static")]
+        r#static: bool,
+        /// Show the changes without applying them.
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Debug, Serialize)]
+struct HealthReport {
+    version: String,
+    records: usize,
+    unhealthy: Vec<RecordReply>,
+}
+
+fn record_status(record: &RecordReply) -> &'static str {
+    if record.r#static {
+        "static"
+    } else if record.healthy {
+        "healthy"
+    } else {
+        "unhealthy"
+    }
+}
+
+fn view_name(view: &str) -> &str {
+    if view.is_empty() {
+        "*"
+    } else {
+        view
+    }
+}
+
+fn records_table(records: &[RecordReply]) -> Table {
+    let mut table = Table::new(&["NAME", "TYPE", "TTL", "VIEW", "STATUS", "OWNER", "VALUES"]);
+    for record in records {
+        table.row(vec![
+            record.name.clone(),
+            record.r#type.clone(),
+            record.ttl.to_string(),
+            view_name(&record.view).to_string(),
+            record_status(record).to_string(),
+            owner_name(&record.owner).to_string(),
+            record.values.join(" "),
+        ]);
+    }
+    table
+}
+
+fn owner_name(owner: &str) -> &str {
+    if owner.is_empty() {
+        "-"
+    } else {
+        owner
+    }
+}
+
+fn conflicts_table(reply: &ConflictsReply) -> Table {
+    let mut table = Table::new(&["TIME", "NAME", "TYPE", "VIEW", "OWNER", "REJECTED"]);
+    for conflict in reply.conflicts.iter() {
+        table.row(vec![
+            timestamp(conflict.created_at),
+            conflict.name.clone(),
+            conflict.r#type.clone(),
+            view_name(&conflict.view).to_string(),
+            owner_name(&conflict.owner).to_string(),
+            owner_name(&conflict.challenger).to_string(),
+        ]);
+    }
+    table
+}
+
+fn history_table(reply: &HistoryReply) -> Table {
+    let mut table = Table::new(&[
+        "TIME", "ACTION", "NAME", "TYPE", "VIEW", "OLD", "NEW", "ACTOR",
+    ]);
+    for entry in reply.entries.iter() {
+        let action = match entry.action() {
+            HistoryAction::Upsert => "upsert",
+            HistoryAction::Delete => "delete",
+            HistoryAction::Expire => "expire",
+        };
+        table.row(vec![
+            timestamp(entry.created_at),
+            action.to_string(),
+            entry.name.clone(),
+            entry.r#type.clone(),
+            view_name(&entry.view).to_string(),
+            entry.old_values.join(" "),
+            entry.new_values.join(" "),
+            owner_name(&entry.actor).to_string(),
+        ]);
+    }
+    table
+}
+
+fn timestamp(timestamp: i64) -> String {
+    DateTime::<Utc>::from_timestamp(timestamp, 0)
+        .map_or(timestamp.to_string(), |timestamp| timestamp.to_rfc3339())
+}
+
+fn zones_table(reply: &ZonesReply) -> Table {
+    let mut table = Table::new(&["ZONE", "VIEW", "STATIC", "RECORDS", "HEALTHY", "DNSSEC"]);
+    for zone in reply.zones.iter() {
+        table.row(vec![
+            zone.name.clone(),
+            view_name(&zone.view).to_string(),
+            zone.static_records.to_string(),
+            zone.records.to_string(),
+            zone.healthy_records.to_string(),
+            if zone.dnssec_validate {
+                "validate"
+            } else {
+                "-"
+            }
+            .to_string(),
+        ]);
+    }
+    table
+}
+
+fn info_table(info: &InfoReply) -> Table {
+    let started_at = timestamp(info.started_at);
+    let mut table = Table::new(&["SETTING", "VALUE"]);
+    table.row(vec!["version".to_string(), info.version.clone()]);
+    table.row(vec!["started_at".to_string(), started_at]);
+    table.row(vec!["dns_port".to_string(), info.dns_port.to_string()]);
+    table.row(vec!["api_port".to_string(), info.api_port.to_string()]);
+    table.row(vec!["nameservers".to_string(), info.nameservers.join(" ")]);
+    table.row(vec!["views".to_string(), info.views.join(" ")]);
+    table.row(vec!["records".to_string(), info.records.to_string()]);
+    table.row(vec![
+        "healthy_records".to_string(),
+        info.healthy_records.to_string(),
+    ]);
+    table
+}
+
+async fn records(server_url: String, output: OutputFormat, command: RecordsCommand) -> Result<()> {
+    match command {
+        RecordsCommand::List {
+            zone,
+            r#type,
+            view,
+            name,
+            owner,
+            unhealthy,
+        } => {
+            let mut query = RecordsQueryRequest {
+                zone: zone.unwrap_or_default(),
+                name: name.unwrap_or_default(),
+                r#type: r#type.unwrap_or_default(),
+                view: view.unwrap_or_default(),
+                owner: owner.unwrap_or_default(),
+                ..Default::default()
+            };
+            if unhealthy {
+                query.set_health(HealthFilter::Unhealthy);
+            }
+            let records = query_records(server_url, query).await?;
+            print!("{}", render(output, records.as_slice(), records_table)?);
+        }
+        RecordsCommand::Get { name, r#type, view } => {
+            let mut client = RecordsClient::connect(server_url).await?;
+            let record = client
+                .find_unique(FindUniqueRecordRequest {
+                    name,
+                    r#type: r#type.to_uppercase(),
+                    view: view.unwrap_or_default(),
+                    ..Default::default()
+                })
+                .await
+                .map_err(rpc_error)?
+                .into_inner();
+            print!("{}", render(output, std::slice::from_ref(&record), records_table)?);
+        }
+        RecordsCommand::Set {
+            name,
+            values,
+            r#type,
+            ttl,
+            view,
+            r#static,
+            lease,
+            owner,
+            force,
+        } => {
+            let r#type = match r#type {
+                Some(r#type) => r#type.to_uppercase(),
+                None => {
+                    let ip_addr: IpAddr = values[0]
+                        .parse()
+                        .map_err(|_| anyhow!("--type is required for {:?}", values[0]))?;
+                    get_ip_addr_record_type(&ip_addr)?.to_string()
+                }
+            };
+            let mut client = RecordsClient::connect(server_url).await?;
+            let record = client
+                .upsert(UpsertRecordRequest {
+                    name,
+                    r#type,
+                    value: values[0].clone(),
+                    ttl,
+                    view: view.unwrap_or_default(),
+                    r#static,
+                    values,
+                    lease: lease.unwrap_or_default(),
+                    owner: owner.unwrap_or_default(),
+                    force,
+                })
+                .await
+                .map_err(rpc_error)?
+                .into_inner();
+            print!("{}", render(output, std::slice::from_ref(&record), records_table)?);
+        }
+        RecordsCommand::Delete {
+            name,
+            r#type,
+            view,
+            owner,
+            force,
+        } => {
+            let mut client = RecordsClient::connect(server_url).await?;
+            client
+                .delete(FindUniqueRecordRequest {
+                    name,
+                    r#type: r#type.to_uppercase(),
+                    view: view.unwrap_or_default(),
+                    owner: owner.unwrap_or_default(),
+                    force,
+                })
+                .await
+                .map_err(rpc_error)?;
+        }
+        RecordsCommand::Import {
+            file,
+            origin,
+            view,
+            r#static,
+            dry_run,
+        } => {
+            let zone_file = if file.as_os_str() == "-" {
+                let mut zone_file = String::new();
+                tokio::io::stdin().read_to_string(&mut zone_file).await?;
+                zone_file
+            } else {
+                fs::read_to_string(&file).await?
+            };
+            let mut client = RecordsClient::connect(server_url).await?;
+            let reply = client
+                .import(ImportRequest {
+                    zone_file,
+                    origin: origin.unwrap_or_default(),
+                    view: view.unwrap_or_default(),
+                    r#static,
+                    dry_run,
+                })
+                .await?
+                .into_inner();
+            let summary = |reply: &ImportReply| {
+                reply
+                    .changes
+                    .iter()
+                    .map(|change| render_change(change) + "\n")
+                    .collect::<String>()
+            };
+            print!("{}", render(output, &reply, summary)?);
+        }
+    }
+    Ok(())
+}
+
+async fn health(server_url: String, output: OutputFormat) -> Result<()> {
+    PingClient::connect(server_url.clone())
+        .await?
+        .ping(PingRequest {
+            message: "ping".to_string(),
+        })
+        .await?;
+    let info = AdminClient::connect(server_url.clone())
+        .await?
+        .info(InfoRequest {})
+        .await?
+        .into_inner();
+    let mut query = RecordsQueryRequest::default();
+    query.set_health(HealthFilter::Unhealthy);
+    let unhealthy = query_records(server_url, query).await?;
+    let report = HealthReport {
+        version: info.version,
+        records: info.records as usize,
+        unhealthy,
+    };
+    print!(
+        "{}",
+        render(output, &report, |report| {
+            format!(
+                "Server {} is up, {} of {} records unhealthy\n{}",
+                report.version,
+                report.unhealthy.len(),
+                report.records,
+                if report.unhealthy.is_empty() {
+                    String::new()
+                } else {
+                    records_table(&report.unhealthy).to_string()
+                }
+            )
+        })?
+    );
+    if !report.unhealthy.is_empty() {
+        return Err(anyhow!("{} records are unhealthy", report.unhealthy.len()));
+    }
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args: Args = Args::parse();
+    let cfg: CtlConfig = load_config(CONF_NAME, args.config).await?;
+    let server_url = args
+        .server
+        .or(cfg.server_url)
+        .unwrap_or("http://127.0.0.1:8080".to_string());
+    let output = args.output.or(cfg.output).unwrap_or_default();
+
+    match args.command {
+        Command::Records(command) => records(server_url, output, command).await,
+        Command::Zones => {
+            let reply = AdminClient::connect(server_url)
+                .await?
+                .zones(ZonesRequest {})
+                .await?
+                .into_inner();
+            print!("{}", render(output, &reply, zones_table)?);
+            Ok(())
+        }
+        Command::Health => health(server_url, output).await,
+        Command::Info => {
+            let info = AdminClient::connect(server_url)
+                .await?
+                .info(InfoRequest {})
+                .await?
+                .into_inner();
+            print!("{}", render(output, &info, info_table)?);
+            Ok(())
+        }
+        Command::Conflicts => {
+            let reply = AdminClient::connect(server_url)
+                .await?
+                .conflicts(ConflictsRequest {})
+                .await?
+                .into_inner();
+            print!("{}", render(output, &reply, conflicts_table)?);
+            Ok(())
+        }
+        Command::History {
+            zone,
+            name,
+            r#type,
+            view,
+            actor,
+            limit,
+        } => {
+            let reply = AdminClient::connect(server_url)
+                .await?
+                .history(HistoryRequest {
+                    zone: zone.unwrap_or_default(),
+                    name: name.unwrap_or_default(),
+                    r#type: r#type.unwrap_or_default(),
+                    view: view.unwrap_or_default(),
+                    actor: actor.unwrap_or_default(),
+                    limit,
+                    ..Default::default()
+                })
+                .await
+                .map_err(rpc_error)?
+                .into_inner();
+            print!("{}", render(output, &reply, history_table)?);
+            Ok(())
+        }
+        Command::Backup => {
+            let reply = AdminClient::connect(server_url)
+                .await?
+                .backup(BackupRequest {})
+                .await
+                .map_err(rpc_error)?
+                .into_inner();
+            print!(
+                "{}",
+                render(output, &reply, |reply: &BackupReply| format!(
+                    "Backed up to {}\n",
+                    reply.path
+                ))?
+            );
+            Ok(())
+        }
+        Command::IntegrityCheck => {
+            let reply = AdminClient::connect(server_url)
+                .await?
+                .integrity_check(IntegrityCheckRequest {})
+                .await
+                .map_err(rpc_error)?
+                .into_inner();
+            print!(
+                "{}",
+                render(output, &reply, |reply: &IntegrityCheckReply| {
+                    if reply.ok {
+                        "ok\n".to_string()
+                    } else {
+                        reply
+                            .problems
+                            .iter()
+                            .map(|problem| format!("{}\n", problem))
+                            .collect()
+                    }
+                })?
+            );
+            if !reply.ok {
+                return Err(anyhow!("{} integrity problems", reply.problems.len()));
+            }
+            Ok(())
+        }
+    }
+}
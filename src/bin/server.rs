@@ -1,14 +1,23 @@
-use anyhow::Result;
-use clap::Parser;
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand};
+use std::io::Read;
 use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
+use swandns::backup::{restore_backup, run_scheduled_backups};
+use swandns::config_watcher::watch_config;
 use swandns::dns_server::DnsServer;
-use swandns::record_repository::RecordRepository;
+use swandns::history::maintain_history;
+use swandns::postgres_store;
+use swandns::proto::{ChangeAction, ExportRequest, ImportRequest};
+use swandns::record_store::{acting_as, open_store, RecordStore};
 use swandns::rpc_server::RpcServer;
-use swandns::util::{configure_tracing, get_socket_addr, migrate_database, open_database};
-use swandns::{load_config, ServerConfig};
+use swandns::util::{configure_tracing, get_socket_addr, open_database, schema_status};
+use swandns::zone_file::{export_zone_file, import_zone_file, render_change};
+use swandns::zone_includes::load_server_config;
+use swandns::{config_path, ServerConfig, StorageBackend};
+use tokio::sync::watch;
 use tokio_graceful_shutdown::{FutureExt, SubsystemHandle, Toplevel, SubsystemBuilder};
 use tracing::debug;
 
@@ -19,32 +28,137 @@ static CONF_NAME: &str = "server";
 struct Args {
     #[arg(short, long)]
     config: Option<PathBuf>,
+    /// Migrate the database to the latest schema and exit.
+    #[arg(long, conflicts_with = "check_schema")]
+    migrate_only: bool,
+    /// Check the database's schema without migrating it and exit. Fails unless it's the latest.
+    #[arg(long)]
+    check_schema: bool,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run the DNS and API servers. This is the default.
+    Serve,
+    /// Import records from an RFC 1035 master file into the database.
+    Import {
+        /// Master file to import, `-` reads from stdin.
+        file: PathBuf,
+        /// Origin for relative names, unless the file sets $ORIGIN.
+        #[arg(long)]
+        origin: Option<String>,
+        /// View to import the records into.
+        #[arg(long)]
+        view: Option<String>,
+        /// Import as static records, which are always healthy.
+        #[arg(long = "static")]
+        r#static: bool,
+        /// Show the changes without applying them.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Export the served records as an RFC 1035 master file.
+    Export {
+        /// Only export records within this zone.
+        #[arg(long)]
+        zone: Option<String>,
+        /// View to export the records of.
+        #[arg(long)]
+        view: Option<String>,
+        /// Leave out the static records from the config.
+        #[arg(long)]
+        no_static: bool,
+        /// File to write to, defaults to stdout.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Replace the database with a backup. The server must be stopped.
+    Restore {
+        /// Backup to restore, e.g. from the backup directory.
+        file: PathBuf,
+    },
 }
 
 async fn start_dns_server(
     subsys: SubsystemHandle,
-    cfg: Arc<ServerConfig>,
-    repo: Arc<RecordRepository>,
+    configs: watch::Receiver<Arc<ServerConfig>>,
+    repo: Arc<dyn RecordStore>,
 ) -> Result<()> {
+    let cfg = configs.borrow().clone();
     let dns_server = DnsServer { repo, cfg };
-    if let Err(_) = dns_server.run().cancel_on_shutdown(&subsys).await {
+    if let Err(_) = dns_server
+        .run_with_reloads(configs)
+        .cancel_on_shutdown(&subsys)
+        .await
+    {
         debug!("DNS server shutdown");
     }
     Ok(())
 }
 
+async fn start_config_watcher(
+    subsys: SubsystemHandle,
+    path: PathBuf,
+    repo: Arc<dyn RecordStore>,
+    sender: watch::Sender<Arc<ServerConfig>>,
+) -> Result<()> {
+    if let Err(_) = watch_config(CONF_NAME, path, repo, sender)
+        .cancel_on_shutdown(&subsys)
+        .await
+    {
+        debug!("Config watcher shutdown");
+    }
+    Ok(())
+}
+
+async fn start_history_maintenance(
+    subsys: SubsystemHandle,
+    configs: watch::Receiver<Arc<ServerConfig>>,
+    repo: Arc<dyn RecordStore>,
+) -> Result<()> {
+    if let Err(_) = maintain_history(repo, configs)
+        .cancel_on_shutdown(&subsys)
+        .await
+    {
+        debug!("History maintenance shutdown");
+    }
+    Ok(())
+}
+
+async fn start_scheduled_backups(
+    subsys: SubsystemHandle,
+    configs: watch::Receiver<Arc<ServerConfig>>,
+    repo: Arc<dyn RecordStore>,
+) -> Result<()> {
+    if let Err(_) = run_scheduled_backups(repo, configs)
+        .cancel_on_shutdown(&subsys)
+        .await
+    {
+        debug!("Scheduled backups shutdown");
+    }
+    Ok(())
+}
+
 async fn start_rpc_server(
     subsys: SubsystemHandle,
-    cfg: Arc<ServerConfig>,
-    repo: Arc<RecordRepository>,
+    configs: watch::Receiver<Arc<ServerConfig>>,
+    repo: Arc<dyn RecordStore>,
 ) -> Result<()> {
+    let cfg = configs.borrow().clone();
     let listen_addr: SocketAddr =
         get_socket_addr(cfg.bind.clone(), cfg.api_port, Some("ipv4".to_string()))?;
     let rpc_server = RpcServer {
         addr: listen_addr,
         repo,
+        cfg,
     };
-    if let Err(_) = rpc_server.run().cancel_on_shutdown(&subsys).await {
+    if let Err(_) = rpc_server
+        .run_with_reloads(configs)
+        .cancel_on_shutdown(&subsys)
+        .await
+    {
         debug!("DNS server shutdown");
     }
     Ok(())
@@ -56,20 +170,149 @@ async fn main() -> Result<()> {
 
     let args: Args = Args::parse();
 
-    let cfg: Arc<ServerConfig> = Arc::new(load_config(CONF_NAME, args.config).await?);
-    let dns_cfg = cfg.clone();
-    let rpc_cfg = cfg.clone();
+    let config_path = config_path(CONF_NAME, args.config)?;
+    let cfg: Arc<ServerConfig> = Arc::new(load_server_config(CONF_NAME, &config_path).await?);
+
+    let command = args.command.unwrap_or(Command::Serve);
+    // Restored before the database is opened, so nothing is using it.
+    if let Command::Restore { file } = &command {
+        restore_backup(&cfg, file).await?;
+        println!("Restored {}", file.display());
+        return Ok(());
+    }
+
+    if args.check_schema {
+        return check_schema(&cfg).await;
+    }
+    let record_repo = open_store(&cfg).await?;
+    if args.migrate_only {
+        println!("Migrated the database");
+        return Ok(());
+    }
+
+    match command {
+        Command::Serve => serve(cfg, config_path, record_repo).await,
+        Command::Import {
+            file,
+            origin,
+            view,
+            r#static,
+            dry_run,
+        } => {
+            let zone_file = if file.as_os_str() == "-" {
+                let mut zone_file = String::new();
+                std::io::stdin().read_to_string(&mut zone_file)?;
+                zone_file
+            } else {
+                tokio::fs::read_to_string(&file).await?
+            };
+            let reply = acting_as(
+                "import".to_string(),
+                import_zone_file(
+                    &cfg,
+                    record_repo.as_ref(),
+                    ImportRequest {
+                        zone_file,
+                        origin: origin.unwrap_or_default(),
+                        view: view.unwrap_or_default(),
+                        r#static,
+                        dry_run,
+                    },
+                ),
+            )
+            .await?;
+            for change in reply.changes.iter() {
+                println!("{}", render_change(change));
+            }
+            let count = |action| {
+                reply
+                    .changes
+                    .iter()
+                    .filter(|change| change.action() == action)
+                    .count()
+            };
+            println!(
+                "{} {} added, {} updated, {} unchanged",
+                if dry_run { "Would have" } else { "Records" },
+                count(ChangeAction::Add),
+                count(ChangeAction::Update),
+                count(ChangeAction::Unchanged)
+            );
+            Ok(())
+        }
+        Command::Export {
+            zone,
+            view,
+            no_static,
+            output,
+        } => {
+            let reply = export_zone_file(
+                &cfg,
+                record_repo.as_ref(),
+                ExportRequest {
+                    zone: zone.unwrap_or_default(),
+                    view: view.unwrap_or_default(),
+                    include_static: !no_static,
+                },
+            )
+            .await?;
+            match output {
+                Some(output) => tokio::fs::write(output, reply.zone_file).await?,
+                None => print!("{}", reply.zone_file),
+            }
+            Ok(())
+        }
+        Command::Restore { .. } => unreachable!(),
+    }
+}
 
-    let conn = Arc::new(open_database(&cfg.data_dir, &cfg.db_file).await?);
-    let record_repo = Arc::new(RecordRepository { conn: conn.clone() });
+async fn check_schema(cfg: &ServerConfig) -> Result<()> {
+    let status = match cfg.storage {
+        StorageBackend::Sqlite => {
+            let conn = open_database(&cfg.data_dir, &cfg.db_file).await?;
+            schema_status(&conn).await?
+        }
+        StorageBackend::Postgres => {
+            let url = cfg
+                .postgres_url
+                .as_deref()
+                .context("storage: postgres needs a postgres_url")?;
+            let client = postgres_store::connect(url).await?;
+            postgres_store::schema_status(&client).await?
+        }
+        StorageBackend::Memory => bail!("Memory storage has no schema"),
+    };
+    println!("Database is at {}", status);
+    if status.is_newer() {
+        bail!("The database is from a newer version of Swan DNS");
+    }
+    if !status.is_current() {
+        bail!("The database has to be migrated");
+    }
+    Ok(())
+}
+
+async fn serve(
+    cfg: Arc<ServerConfig>,
+    config_path: PathBuf,
+    record_repo: Arc<dyn RecordStore>,
+) -> Result<()> {
+    let (config_sender, dns_configs) = watch::channel(cfg);
+    let rpc_configs = dns_configs.clone();
+    let history_configs = dns_configs.clone();
+    let watcher_repo = record_repo.clone();
     let dns_repo = record_repo.clone();
     let rpc_repo = record_repo.clone();
-
-    migrate_database(conn.clone()).await?;
+    let history_repo = record_repo.clone();
+    let backup_configs = dns_configs.clone();
+    let backup_repo = record_repo.clone();
 
     Toplevel::new(|s| async move {
-        s.start(SubsystemBuilder::new("DnsServer", |h| start_dns_server(h, dns_cfg, dns_repo)));
-        s.start(SubsystemBuilder::new("RpcServer", |h| start_rpc_server(h, rpc_cfg, rpc_repo)));
+        s.start(SubsystemBuilder::new("ConfigWatcher", |h| start_config_watcher(h, config_path, watcher_repo, config_sender)));
+        s.start(SubsystemBuilder::new("DnsServer", |h| start_dns_server(h, dns_configs, dns_repo)));
+        s.start(SubsystemBuilder::new("RpcServer", |h| start_rpc_server(h, rpc_configs, rpc_repo)));
+        s.start(SubsystemBuilder::new("History", |h| start_history_maintenance(h, history_configs, history_repo)));
+        s.start(SubsystemBuilder::new("Backup", |h| start_scheduled_backups(h, backup_configs, backup_repo)));
     })
         .catch_signals()
         .handle_shutdown_requests(Duration::from_millis(1000))
@@ -1,68 +1,339 @@
+use crate::address_filter::{temporary_addresses, AddressFilter};
+use crate::address_source::resolve_sources;
+use crate::proto::error_details::field_violations;
+use crate::proto::record_operation::Operation;
 use crate::proto::records_client::RecordsClient;
-use crate::proto::{RecordReply, UpsertRecordRequest};
-use crate::util::get_iface_addr;
+use crate::proto::{
+    BatchReply, BatchRequest, FindUniqueRecordRequest, KeepAliveReply, KeepAliveRequest,
+    LeaseRenewal, RecordOperation, RecordReply, RecordsQueryRequest, RenewalResult,
+    UpsertRecordRequest,
+};
+use crate::update_state::{default_state_file, PublishedRecord, UpdateState};
+use crate::util::get_iface_addrs;
+use crate::zone_file::render_record;
 use crate::{ClientConfig, ClientRecordConfig};
 use anyhow::{anyhow, Result};
+use std::collections::HashSet;
 use std::iter::Iterator;
+use std::net::IpAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
+use time::OffsetDateTime;
 use tokio_retry::strategy::{jitter, FibonacciBackoff};
-use tokio_retry::Retry;
-use tonic::{Request, Response};
+use tokio_retry::RetryIf;
+use tokio_stream::StreamExt;
+use tonic::{Code, Request, Response, Status};
 use tracing::{debug, info, warn};
 
+/// DNS TTL of published records, unless the record config sets one.
+static DEFAULT_TTL: u32 = 30;
+/// Seconds an unchanged record is only kept alive before it's upserted in full again.
+static DEFAULT_HEARTBEAT_INTERVAL: u64 = 60 * 60;
+
+/// Turns a status from the API into an error that shows the field violations sent with it.
+///
+/// The status stays downcastable, e.g. to tell whether retrying could help.
+pub fn rpc_error(status: Status) -> anyhow::Error {
+    let violations = field_violations(&status);
+    let message = if violations.is_empty() {
+        format!("{:?}: {}", status.code(), status.message())
+    } else {
+        let violations = violations
+            .iter()
+            .map(|violation| format!("\n  {}: {}", violation.field, violation.description))
+            .collect::<String>();
+        format!("{:?}: {}{}", status.code(), status.message(), violations)
+    };
+    anyhow::Error::from(status).context(message)
+}
+
 async fn client_upsert(
     server_url: String,
     message: &UpsertRecordRequest,
 ) -> Result<Response<RecordReply>> {
     let mut client = RecordsClient::connect(server_url).await?;
-    let res = client.upsert(Request::new(message.clone())).await?;
+    let res = client
+        .upsert(Request::new(message.clone()))
+        .await
+        .map_err(rpc_error)?;
     Ok(res)
 }
 
+async fn client_batch(server_url: String, message: &BatchRequest) -> Result<BatchReply> {
+    let mut client = RecordsClient::connect(server_url).await?;
+    let res = client
+        .batch(Request::new(message.clone()))
+        .await
+        .map_err(rpc_error)?;
+    Ok(res.into_inner())
+}
+
+async fn client_keep_alive(
+    server_url: String,
+    message: KeepAliveRequest,
+) -> Result<KeepAliveReply> {
+    let mut client = RecordsClient::connect(server_url).await?;
+    let res = client
+        .keep_alive(Request::new(message))
+        .await
+        .map_err(rpc_error)?;
+    Ok(res.into_inner())
+}
+
+fn state_file(cfg: &ClientConfig) -> PathBuf {
+    cfg.state_file.clone().unwrap_or_else(default_state_file)
+}
+
+/// Identity records are published under, so hosts configured with the same name don't take turns
+/// overwriting it. Defaults to the systemd machine id, or no owner if there isn't one.
+pub fn owner(cfg: &ClientConfig) -> String {
+    cfg.machine_id.clone().unwrap_or_else(|| {
+        std::fs::read_to_string("/etc/machine-id")
+            .map(|machine_id| machine_id.trim().to_string())
+            .unwrap_or_default()
+    })
+}
+
+/// The API to use for a record, falling back to `default_server_url` and then localhost.
+pub fn server_url(cfg: &ClientConfig, server_url: Option<String>) -> String {
+    server_url
+        .or(cfg.default_server_url.clone())
+        .unwrap_or("http://127.0.0.1:8080".to_string())
+}
+
+/// Publishes the addresses for a record, one RRset for each of A and AAAA.
 pub async fn update_record(
     cfg: Arc<ClientConfig>,
     record_config: ClientRecordConfig,
-) -> Result<RecordReply> {
+) -> Result<Vec<RecordReply>> {
     let name = record_config.name;
     let bind = record_config.bind.or(cfg.default_bind.clone());
     let protocol = record_config.protocol.or(cfg.default_protocol.clone());
-    let ip_addr = get_iface_addr(bind, protocol)?;
-    let server_url = record_config
-        .server_url
-        .clone()
-        .or(cfg.default_server_url.clone())
-        .unwrap_or("http://127.0.0.1:8080".to_string());
+    let filter = AddressFilter::from_config(&record_config.exclude)?;
+    let temporary = if filter.excludes_temporary() {
+        temporary_addresses()
+    } else {
+        HashSet::new()
+    };
+    let ip_addrs = if record_config.sources.is_empty() {
+        get_iface_addrs(bind, protocol)?
+    } else {
+        let strategy = record_config.source_strategy.unwrap_or_default();
+        resolve_sources(&record_config.sources, strategy, bind, protocol).await?
+    };
+    let ip_addrs: Vec<IpAddr> = ip_addrs
+        .into_iter()
+        .filter(|ip_addr| filter.permits(*ip_addr, &temporary))
+        .collect();
+    if ip_addrs.is_empty() {
+        return Err(anyhow!("No addresses to publish for {:?}", name));
+    }
+    let server_url = server_url(&cfg, record_config.server_url.clone());
 
-    debug!("Sending {:?}={:?} to {:?}", name, ip_addr, server_url);
+    let view = record_config.view.clone().unwrap_or_default();
+    let ttl = record_config.ttl.unwrap_or(DEFAULT_TTL);
+    let lease = record_config.lease.unwrap_or_default();
+    let state_file = state_file(&cfg);
+    let state = UpdateState::load(&state_file).await;
+    let heartbeat_interval = cfg.heartbeat_interval.unwrap_or(DEFAULT_HEARTBEAT_INTERVAL) as i64;
+    let now = OffsetDateTime::now_utc().unix_timestamp();
 
-    let r#type = if ip_addr.is_ipv6() { "AAAA" } else { "A" };
-    let message = UpsertRecordRequest {
-        name: name.clone(),
-        r#type: r#type.to_string(),
-        value: ip_addr.to_string(),
-        ttl: 30,
-    };
-    let retry_policy = FibonacciBackoff::from_millis(1000).map(jitter).take(5);
-    let res = Retry::spawn(retry_policy, || {
-        client_upsert(server_url.to_string(), &message)
-    })
-    .await?;
-    debug!("Response: {:?}", res);
-    let reply = res.into_inner();
+    let mut rrsets = vec![];
+    for (r#type, is_type) in [
+        ("A", IpAddr::is_ipv4 as fn(&IpAddr) -> bool),
+        ("AAAA", IpAddr::is_ipv6),
+    ] {
+        let values: Vec<String> = ip_addrs
+            .iter()
+            .filter(|ip_addr| is_type(ip_addr))
+            .map(|ip_addr| ip_addr.to_string())
+            .collect();
+        if !values.is_empty() {
+            rrsets.push((r#type, values));
+        }
+    }
+
+    // Unchanged records only need their lease renewed, unless the server has a newer revision.
+    let renewals: Vec<LeaseRenewal> = rrsets
+        .iter()
+        .filter_map(|(r#type, values)| {
+            state
+                .get(&server_url, &name, r#type, &view)
+                .filter(|published| {
+                    published.values == *values
+                        && published.ttl == ttl
+                        && published.lease == lease
+                        && now - published.published_at < heartbeat_interval
+                })
+        })
+        .map(|published| LeaseRenewal {
+            name: name.clone(),
+            r#type: published.r#type.clone(),
+            view: view.clone(),
+            revision: published.revision,
+            owner: owner(&cfg),
+        })
+        .collect();
+    let mut replies = vec![];
+    if !renewals.is_empty() {
+        debug!("Renewing {:?} on {:?}", name, server_url);
+        let message = KeepAliveRequest { renewals };
+        match client_keep_alive(server_url.clone(), message).await {
+            Ok(reply) => {
+                for result in reply.results {
+                    match result {
+                        RenewalResult {
+                            current: true,
+                            record: Some(record),
+                        } => replies.push(record),
+                        _ => debug!("{:?} changed on the server, sending it again", name),
+                    }
+                }
+            }
+            Err(err) => warn!("Couldn't renew {:?}: {}", name, err),
+        }
+    }
+
+    // Everything else is replaced in one batch, so the record's A and AAAA RRsets change together.
+    let mut operations = vec![];
+    let mut upserted = vec![];
+    for (r#type, values) in rrsets.iter() {
+        if replies.iter().any(|record| record.r#type == *r#type) {
+            continue;
+        }
+        debug!("Sending {:?}={:?} to {:?}", name, values, server_url);
+        operations.push(RecordOperation {
+            operation: Some(Operation::Upsert(UpsertRecordRequest {
+                name: name.clone(),
+                r#type: r#type.to_string(),
+                value: values[0].clone(),
+                ttl,
+                view: view.clone(),
+                r#static: false,
+                values: values.clone(),
+                lease,
+                owner: owner(&cfg),
+                force: false,
+            })),
+            ..Default::default()
+        });
+        upserted.push((*r#type, values.clone()));
+    }
+    // RRsets published before that have no addresses anymore, e.g. after losing IPv6.
+    let mut removed = vec![];
+    for r#type in ["A", "AAAA"] {
+        if rrsets.iter().any(|(published, _)| *published == r#type)
+            || state.get(&server_url, &name, r#type, &view).is_none()
+        {
+            continue;
+        }
+        debug!("Removing stale {:?} {} from {:?}", name, r#type, server_url);
+        operations.push(RecordOperation {
+            operation: Some(Operation::Delete(FindUniqueRecordRequest {
+                name: name.clone(),
+                r#type: r#type.to_string(),
+                view: view.clone(),
+                owner: owner(&cfg),
+                force: false,
+            })),
+            ..Default::default()
+        });
+        removed.push(r#type);
+    }
 
-    info!("Updated {:?} to {:?}", name, ip_addr);
-    Ok(reply)
+    let mut published = vec![];
+    if !operations.is_empty() {
+        let message = BatchRequest { operations };
+        let retry_policy = FibonacciBackoff::from_millis(1000).map(jitter).take(5);
+        // Retrying won't help when another host owns the record, or the server rejected it.
+        let reply = RetryIf::spawn(
+            retry_policy,
+            || client_batch(server_url.to_string(), &message),
+            |err: &anyhow::Error| {
+                err.downcast_ref::<Status>().map_or(true, |status| {
+                    !matches!(
+                        status.code(),
+                        Code::PermissionDenied | Code::InvalidArgument
+                    )
+                })
+            },
+        )
+        .await?;
+        debug!("Response: {:?}", reply);
+        for ((r#type, values), record) in upserted.into_iter().zip(reply.records) {
+            published.push(PublishedRecord {
+                server_url: server_url.clone(),
+                name: name.clone(),
+                r#type: r#type.to_string(),
+                view: view.clone(),
+                values: values.clone(),
+                ttl,
+                lease,
+                revision: record.revision,
+                published_at: now,
+            });
+            replies.push(record);
+
+            info!("Updated {:?} to {:?}", name, values);
+        }
+    }
+
+    replies.sort_by_key(|record| {
+        rrsets
+            .iter()
+            .position(|(r#type, _)| *r#type == record.r#type)
+    });
+
+    let saved = UpdateState::update(&state_file, |state| {
+        for r#type in removed {
+            state.remove(&server_url, &name, r#type, &view);
+        }
+        for record in published {
+            state.insert(record);
+        }
+    })
+    .await;
+    if let Err(err) = saved {
+        warn!("Couldn't save update state to {:?}: {}", state_file, err);
+    }
+    Ok(replies)
 }
 
 pub async fn update_records(cfg: Arc<ClientConfig>) -> Result<Vec<RecordReply>> {
-    let result: Vec<RecordReply> = vec![];
     if cfg.records.is_empty() {
         warn!("No update records configured");
-        return Ok(result);
+        return Ok(vec![]);
     }
 
+    update_matching_records(cfg, |_| true).await
+}
+
+/// Updates the records published from any of `interfaces`, after their addresses changed.
+///
+/// Records without an interface use the system's default interface, which could be any of them.
+pub async fn update_interface_records(
+    cfg: Arc<ClientConfig>,
+    interfaces: &HashSet<String>,
+) -> Result<Vec<RecordReply>> {
+    let default_bind = cfg.default_bind.clone();
+    update_matching_records(cfg, |record| {
+        match record.bind.as_ref().or(default_bind.as_ref()) {
+            Some(bind) => interfaces
+                .iter()
+                .any(|iface| iface.eq_ignore_ascii_case(bind)),
+            None => true,
+        }
+    })
+    .await
+}
+
+async fn update_matching_records(
+    cfg: Arc<ClientConfig>,
+    matches: impl Fn(&ClientRecordConfig) -> bool,
+) -> Result<Vec<RecordReply>> {
+    let result: Vec<RecordReply> = vec![];
     let mut errors = 0;
-    for record in cfg.records.clone().into_iter() {
+    for record in cfg.records.iter().filter(|record| matches(record)) {
         if let Err(err) = update_record(cfg.clone(), record.clone()).await {
             errors += 1;
             warn!("There was a problem updating {}: {}", record.name, err);
@@ -74,3 +345,134 @@ pub async fn update_records(cfg: Arc<ClientConfig>) -> Result<Vec<RecordReply>>
     }
     Ok(result)
 }
+
+/// Deletes the A and AAAA RRsets published for a record, even if another host owns them with
+/// `force`.
+pub async fn remove_record(
+    cfg: Arc<ClientConfig>,
+    record_config: ClientRecordConfig,
+    force: bool,
+) -> Result<()> {
+    let server_url = server_url(&cfg, record_config.server_url.clone());
+    let view = record_config.view.clone().unwrap_or_default();
+    let state_file = state_file(&cfg);
+    let mut removed = vec![];
+    for r#type in ["A", "AAAA"] {
+        let message = FindUniqueRecordRequest {
+            name: record_config.name.clone(),
+            r#type: r#type.to_string(),
+            view: view.clone(),
+            owner: owner(&cfg),
+            force,
+        };
+        delete_record(server_url.clone(), message).await?;
+        removed.push(r#type);
+    }
+    let saved = UpdateState::update(&state_file, |state| {
+        for r#type in removed {
+            state.remove(&server_url, &record_config.name, r#type, &view);
+        }
+    })
+    .await;
+    if let Err(err) = saved {
+        warn!("Couldn't save update state to {:?}: {}", state_file, err);
+    }
+    info!("Removed {:?}", record_config.name);
+    Ok(())
+}
+
+/// Deletes every configured record, e.g. when the host is shutting down for good.
+pub async fn remove_records(cfg: Arc<ClientConfig>) -> Result<()> {
+    let mut errors = 0;
+    for record in cfg.records.iter() {
+        if let Err(err) = remove_record(cfg.clone(), record.clone(), false).await {
+            errors += 1;
+            warn!("There was a problem removing {}: {}", record.name, err);
+        }
+    }
+
+    if errors > 0 {
+        return Err(anyhow!("{} records not removed", errors));
+    }
+    Ok(())
+}
+
+pub async fn publish_record(
+    server_url: String,
+    message: UpsertRecordRequest,
+) -> Result<RecordReply> {
+    Ok(client_upsert(server_url, &message).await?.into_inner())
+}
+
+pub async fn delete_record(server_url: String, message: FindUniqueRecordRequest) -> Result<()> {
+    let mut client = RecordsClient::connect(server_url).await?;
+    client
+        .delete(Request::new(message))
+        .await
+        .map_err(rpc_error)?;
+    Ok(())
+}
+
+pub async fn get_record(
+    server_url: String,
+    message: FindUniqueRecordRequest,
+) -> Result<RecordReply> {
+    let mut client = RecordsClient::connect(server_url).await?;
+    Ok(client
+        .find_unique(Request::new(message))
+        .await
+        .map_err(rpc_error)?
+        .into_inner())
+}
+
+pub async fn list_records(server_url: String) -> Result<Vec<RecordReply>> {
+    query_records(server_url, Default::default()).await
+}
+
+/// Lists the records matching the query, filtered on the server.
+pub async fn query_records(
+    server_url: String,
+    query: RecordsQueryRequest,
+) -> Result<Vec<RecordReply>> {
+    let mut client = RecordsClient::connect(server_url).await?;
+    let mut stream = client
+        .list(Request::new(query))
+        .await
+        .map_err(rpc_error)?
+        .into_inner();
+    let mut records = vec![];
+    while let Some(record) = stream.next().await {
+        records.push(record.map_err(rpc_error)?);
+    }
+    Ok(records)
+}
+
+/// Renders a record as master file lines, one for each value, noting its view and health.
+pub fn render_reply(record: &RecordReply) -> String {
+    let mut notes = vec![];
+    if !record.view.is_empty() {
+        notes.push(format!("view {}", record.view));
+    }
+    if record.r#static {
+        notes.push("static".to_string());
+    } else if !record.healthy {
+        notes.push("unhealthy".to_string());
+    }
+    let comment = if notes.is_empty() {
+        String::new()
+    } else {
+        format!(" ; {}", notes.join(", "))
+    };
+    record
+        .values
+        .iter()
+        .map(|value| {
+            format!(
+                "{}{}",
+                render_record(&record.name, record.ttl, &record.r#type, value),
+                comment
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
@@ -7,19 +7,25 @@ use figment::{
 use platform_dirs::AppDirs;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::{env, path::PathBuf};
 use tracing::info;
 
 static APP_NAME: &str = "swandns";
 
+/// The config file to read, `path` if given or the platform's default for `conf_name`.
+pub fn config_path(conf_name: &str, path: Option<PathBuf>) -> Result<PathBuf> {
+    Ok(match path {
+        Some(path) => path,
+        None => confy::get_configuration_file_path(APP_NAME, conf_name)?,
+    })
+}
+
 pub async fn load_config<T: Serialize + DeserializeOwned + Default>(
     conf_name: &str,
     path: Option<PathBuf>,
 ) -> Result<T> {
-    let path: PathBuf = match path {
-        Some(path) => path,
-        None => confy::get_configuration_file_path(APP_NAME, conf_name)?,
-    };
+    let path = config_path(conf_name, path)?;
     info!("Loading config from {:?}", path);
     let defaults: T = Default::default();
     let cfg: T = Figment::from(Serialized::defaults(defaults))
@@ -34,11 +40,21 @@ pub async fn load_config<T: Serialize + DeserializeOwned + Default>(
 pub struct ServerConfig {
     pub data_dir: PathBuf,
     pub db_file: PathBuf,
+    pub storage: StorageBackend,
+    pub postgres_url: Option<String>,
     pub bind: Option<String>,
     pub dns_port: u16,
     pub api_port: u16,
     pub nameservers: Vec<String>,
     pub zones: Vec<ZoneConfig>,
+    pub dnssec: DnssecConfig,
+    pub views: Vec<ViewConfig>,
+    pub recursion_acl: AclConfig,
+    pub rate_limit: Option<RateLimitConfig>,
+    pub client_subnet: Option<ClientSubnetConfig>,
+    pub sortlist: Option<SortlistConfig>,
+    pub history: HistoryConfig,
+    pub backup: BackupConfig,
 }
 
 impl Default for ServerConfig {
@@ -48,26 +64,196 @@ impl Default for ServerConfig {
         return Self {
             data_dir,
             db_file: PathBuf::from("swandns.db"),
+            storage: Default::default(),
+            postgres_url: None,
             bind: None,
             dns_port: 1053,
             api_port: 8080,
             nameservers: vec![],
             zones: vec![],
+            dnssec: Default::default(),
+            views: vec![],
+            recursion_acl: Default::default(),
+            rate_limit: None,
+            client_subnet: None,
+            sortlist: None,
+            history: Default::default(),
+            backup: Default::default(),
         };
     }
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    #[default]
+    Sqlite,
+    Memory,
+    Postgres,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DnssecConfig {
+    pub validate: bool,
+    pub trust_anchor_file: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AclConfig {
+    pub allow: Vec<String>,
+    pub deny: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    pub responses_per_second: u32,
+    pub window: u64,
+    pub slip: u32,
+    pub ipv4_prefix_length: u8,
+    pub ipv6_prefix_length: u8,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            responses_per_second: 20,
+            window: 15,
+            slip: 2,
+            ipv4_prefix_length: 24,
+            ipv6_prefix_length: 56,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistoryConfig {
+    pub retention_days: u32,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self { retention_days: 90 }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackupConfig {
+    pub dir: Option<PathBuf>,
+    pub schedule: Option<String>,
+    pub keep: usize,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            schedule: None,
+            keep: 7,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClientSubnetConfig {
+    pub ipv4_prefix_length: u8,
+    pub ipv6_prefix_length: u8,
+    pub overrides: Vec<ClientSubnetOverrideConfig>,
+}
+
+impl Default for ClientSubnetConfig {
+    fn default() -> Self {
+        Self {
+            ipv4_prefix_length: 24,
+            ipv6_prefix_length: 56,
+            overrides: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClientSubnetOverrideConfig {
+    pub clients: String,
+    pub subnet: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SortlistConfig {
+    pub ipv4_prefix_length: u8,
+    pub ipv6_prefix_length: u8,
+}
+
+impl Default for SortlistConfig {
+    fn default() -> Self {
+        Self {
+            ipv4_prefix_length: 24,
+            ipv6_prefix_length: 64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ViewConfig {
+    pub name: String,
+    pub match_clients: Vec<String>,
+    pub match_destinations: Vec<String>,
+    pub nameservers: Option<Vec<String>>,
+    pub zones: Vec<ZoneConfig>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ZoneConfig {
     pub name: String,
     #[serde(default)]
     pub records: Vec<RecordConfig>,
+    pub dnssec_validate: Option<bool>,
+    #[serde(default)]
+    pub query_acl: AclConfig,
+    #[serde(default)]
+    pub protected_names: Vec<String>,
+    pub default_ttl: Option<u32>,
+    pub zone_file: Option<PathBuf>,
+    pub records_dir: Option<PathBuf>,
+    pub lookup_order: Option<Vec<LookupTier>>,
+    pub forward: Option<bool>,
+    #[serde(default)]
+    pub allow_shadowing: bool,
+    #[serde(default)]
+    pub allow_apex: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Where the answers for a zone come from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LookupTier {
+    /// The records from the config.
+    Static,
+    /// The records published over the API.
+    Dynamic,
+    /// The upstream nameservers.
+    Forward,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct RecordConfig {
     pub key: String,
+    #[serde(default)]
     pub value: String,
+    #[serde(default)]
+    pub values: Vec<String>,
+    #[serde(rename = "type")]
+    pub r#type: Option<String>,
+    pub ttl: Option<u32>,
+    pub comment: Option<String>,
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -76,6 +262,11 @@ pub struct ClientConfig {
     pub default_bind: Option<String>,
     pub default_protocol: Option<String>,
     pub records: Vec<ClientRecordConfig>,
+    #[serde(default)]
+    pub remove_on_shutdown: bool,
+    pub state_file: Option<PathBuf>,
+    pub heartbeat_interval: Option<u64>,
+    pub machine_id: Option<String>,
 }
 
 impl Default for ClientConfig {
@@ -85,14 +276,74 @@ impl Default for ClientConfig {
             default_bind: None,
             default_protocol: None,
             records: vec![],
+            remove_on_shutdown: false,
+            state_file: None,
+            heartbeat_interval: None,
+            machine_id: None,
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CtlConfig {
+    pub server_url: Option<String>,
+    pub output: Option<OutputFormat>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Table,
+    Json,
+    Yaml,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ClientRecordConfig {
     pub server_url: Option<String>,
     pub name: String,
     pub bind: Option<String>,
     pub protocol: Option<String>,
+    pub view: Option<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    #[serde(default)]
+    pub sources: Vec<AddressSourceConfig>,
+    pub source_strategy: Option<SourceStrategy>,
+    pub ttl: Option<u32>,
+    pub lease: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum AddressSourceConfig {
+    Interface {
+        bind: Option<String>,
+    },
+    Http {
+        url: String,
+    },
+    Stun {
+        server: String,
+    },
+    Dns {
+        server: String,
+        name: String,
+        record_type: Option<String>,
+    },
+    Static {
+        value: String,
+    },
+    Command {
+        command: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceStrategy {
+    #[default]
+    Fallback,
+    Consensus,
 }
@@ -0,0 +1,128 @@
+use crate::dns_server::{matches_destinations, DnsServer};
+use crate::record_store::RecordStore;
+use crate::zone_includes::{included_paths, load_server_config};
+use crate::ServerConfig;
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::sync::watch;
+use tracing::{error, info, warn};
+
+/// How often the config file is checked for changes.
+static POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Modification times of the config file and everything it includes, including the files in
+/// included directories, so editing, adding or removing any of them changes it.
+fn fingerprint(path: &Path, cfg: &ServerConfig) -> Vec<(PathBuf, Option<SystemTime>)> {
+    let mut paths = vec![path.to_path_buf()];
+    for included in included_paths(cfg) {
+        if let Ok(entries) = std::fs::read_dir(&included) {
+            let mut entries: Vec<PathBuf> = entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .collect();
+            entries.sort();
+            paths.extend(entries);
+        }
+        paths.push(included);
+    }
+    paths
+        .into_iter()
+        .map(|path| {
+            let modified = modified(&path);
+            (path, modified)
+        })
+        .collect()
+}
+
+/// Settings that are only read on startup.
+fn restart_required(old: &ServerConfig, new: &ServerConfig) -> Vec<&'static str> {
+    let mut changed = vec![];
+    if old.bind != new.bind {
+        changed.push("bind");
+    }
+    if old.dns_port != new.dns_port {
+        changed.push("dns_port");
+    }
+    if old.api_port != new.api_port {
+        changed.push("api_port");
+    }
+    if old.data_dir != new.data_dir || old.db_file != new.db_file {
+        changed.push("db_file");
+    }
+    if old.storage != new.storage || old.postgres_url != new.postgres_url {
+        changed.push("storage");
+    }
+    if old.rate_limit != new.rate_limit {
+        changed.push("rate_limit");
+    }
+    if matches_destinations(old) != matches_destinations(new) {
+        changed.push("match_destinations");
+    }
+    changed
+}
+
+/// Loads the server config again whenever its file or the files it includes change, or the
+/// process receives SIGHUP, and sends each config whose zones build.
+///
+/// Configs that don't load, or whose catalogs fail to build, are logged and skipped, so every
+/// receiver keeps the last good config rather than only the DNS server.
+pub async fn watch_config(
+    conf_name: &str,
+    path: PathBuf,
+    repo: Arc<dyn RecordStore>,
+    sender: watch::Sender<Arc<ServerConfig>>,
+) -> Result<()> {
+    #[cfg(unix)]
+    let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?;
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    let mut last_modified = fingerprint(&path, &sender.borrow());
+    loop {
+        #[cfg(unix)]
+        let hung_up = tokio::select! {
+            _ = interval.tick() => false,
+            _ = hangup.recv() => true,
+        };
+        #[cfg(not(unix))]
+        let hung_up = {
+            interval.tick().await;
+            false
+        };
+        let modified = fingerprint(&path, &sender.borrow());
+        if !hung_up && modified == last_modified {
+            continue;
+        }
+        last_modified = modified;
+
+        info!("Reloading config from {:?}", path);
+        let cfg = match load_server_config(conf_name, &path).await {
+            Ok(cfg) => Arc::new(cfg),
+            Err(err) => {
+                error!("Rejected the config from {:?}: {}", path, err);
+                continue;
+            }
+        };
+        let dns_server = DnsServer {
+            repo: repo.clone(),
+            cfg: cfg.clone(),
+        };
+        if let Err(err) = dns_server.build_catalogs(&cfg).await {
+            error!("Rejected the config from {:?}: {}", path, err);
+            continue;
+        }
+        let changed = restart_required(&sender.borrow(), &cfg);
+        if !changed.is_empty() {
+            warn!(
+                "Changes to {} only apply after a restart",
+                changed.join(", ")
+            );
+        }
+        sender.send_replace(cfg);
+    }
+}
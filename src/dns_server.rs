@@ -1,29 +1,119 @@
-use crate::record_repository::RecordRepository;
-use crate::split_authority::SplitAuthority;
+use crate::acl::Acl;
+use crate::dnssec::load_trust_anchor;
+use crate::forwarder::{ClientSubnetPolicy, Forwarder};
+use crate::rate_limit::ResponseRateLimiter;
+use crate::record_store::RecordStore;
+use crate::request_handler::{Catalogs, SwanRequestHandler, View};
+use crate::sortlist::Sortlist;
+use crate::split_authority::{static_names, SplitAuthority};
 use crate::sqlite_authority::SqliteAuthority;
 use crate::util::{
-    create_record_data, get_ip_addr_record_type, get_socket_addr, parse_ip_optional_socket,
+    get_ip_addr_record_type, get_socket_addr, parse_cidr, parse_ip_optional_socket,
     render_record_name,
 };
-use crate::ServerConfig;
-use anyhow::Result;
+use crate::{LookupTier, RecordConfig, ServerConfig, ZoneConfig};
+use anyhow::{anyhow, bail, Result};
 use hickory_server::authority::{Catalog, ZoneType};
-use hickory_server::proto::rr::{DNSClass, LowerName, Record};
+use hickory_server::proto::rr::dnssec::TrustAnchor;
+use hickory_server::proto::rr::{DNSClass, LowerName, RData, Record, RecordType};
+use hickory_server::proto::serialize::txt::RDataParser;
 use hickory_server::recursor::NameServerConfig;
 use hickory_server::resolver::config::{NameServerConfigGroup, Protocol};
 use hickory_server::resolver::Name;
 use hickory_server::store::forwarder::{ForwardAuthority, ForwardConfig};
 use hickory_server::store::in_memory::InMemoryAuthority;
 use hickory_server::ServerFuture;
+use ipnet::IpNet;
+use local_ip_address::list_afinet_netifas;
 use std::net::{IpAddr, SocketAddr};
 use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use std::time::Duration;
 use tokio::net::{TcpListener, UdpSocket};
-use tracing::info;
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+use tracing::{error, info};
+
+/// TTL of the static records from the config that don't set one, unless their zone does.
+pub static STATIC_RECORD_TTL: u32 = 30;
+
+/// Tiers asked for answers in zones that don't set `lookup_order`.
+pub static DEFAULT_LOOKUP_ORDER: [LookupTier; 3] =
+    [LookupTier::Static, LookupTier::Dynamic, LookupTier::Forward];
+
+/// Builds the records of a static RRset from the config.
+///
+/// Records without a type are A or AAAA records, depending on each value. Errors name the zone
+/// and key of the record.
+pub fn static_rrset(zone: &ZoneConfig, record_config: &RecordConfig) -> Result<Vec<Record>> {
+    build_static_rrset(zone, record_config).map_err(|err| {
+        anyhow!(
+            "Invalid record {:?} in zone {:?}: {}",
+            record_config.key,
+            zone.name,
+            err
+        )
+    })
+}
+
+fn build_static_rrset(zone: &ZoneConfig, record_config: &RecordConfig) -> Result<Vec<Record>> {
+    let zone_name = Name::from_str(&zone.name)?;
+    let name = render_record_name(&record_config.key, &zone_name)?;
+    let values = if record_config.values.is_empty() {
+        vec![record_config.value.clone()]
+    } else {
+        record_config.values.clone()
+    };
+    if values.iter().any(|value| value.is_empty()) {
+        bail!("values must not be empty");
+    }
+    let record_type = record_config
+        .r#type
+        .as_ref()
+        .map(|r#type| {
+            RecordType::from_str(&r#type.to_uppercase())
+                .map_err(|_| anyhow!("{:?} is not a record type", r#type))
+        })
+        .transpose()?;
+    let ttl = record_config
+        .ttl
+        .or(zone.default_ttl)
+        .unwrap_or(STATIC_RECORD_TTL);
+
+    let mut records = vec![];
+    for value in values.iter() {
+        let record_type = match record_type {
+            Some(record_type) => record_type,
+            None => {
+                let ip_addr: IpAddr = value.parse().map_err(|_| {
+                    anyhow!("{:?} is not an address, the record needs a type", value)
+                })?;
+                get_ip_addr_record_type(&ip_addr)?
+            }
+        };
+        let rdata = RData::try_from_str(record_type, value)
+            .map_err(|err| anyhow!("{:?} is not valid {} data: {}", value, record_type, err))?;
+        let mut record = Record::new();
+        record
+            .set_name(name.clone())
+            .set_rr_type(record_type)
+            .set_dns_class(DNSClass::IN)
+            .set_ttl(ttl)
+            .set_data(Some(rdata));
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// Whether any view is selected by the address requests are sent to.
+pub fn matches_destinations(cfg: &ServerConfig) -> bool {
+    cfg.views
+        .iter()
+        .any(|view| !view.match_destinations.is_empty())
+}
 
 pub struct DnsServer {
-    pub repo: Arc<RecordRepository>,
+    pub repo: Arc<dyn RecordStore>,
     pub cfg: Arc<ServerConfig>,
 }
 
@@ -33,41 +123,74 @@ impl DnsServer {
         Ok(socket_addr)
     }
 
-    pub async fn run(&self) -> Result<()> {
+    /// Addresses to listen on.
+    ///
+    /// Views can only match the address a request was sent to when the listener is bound to it,
+    /// so if any view matches destinations and `bind` doesn't pick an address, every address of
+    /// the host is listened on separately.
+    fn listen_addrs(&self) -> Result<Vec<SocketAddr>> {
+        let socket_addr = self.get_socket_addr()?;
+        if !socket_addr.ip().is_unspecified() || !matches_destinations(&self.cfg) {
+            return Ok(vec![socket_addr]);
+        }
+        let mut ip_addrs: Vec<IpAddr> = list_afinet_netifas()?
+            .into_iter()
+            .map(|(_, ip_addr)| ip_addr)
+            .filter(|ip_addr| ip_addr.is_ipv4() == socket_addr.is_ipv4())
+            .collect();
+        ip_addrs.sort();
+        ip_addrs.dedup();
+        if ip_addrs.is_empty() {
+            bail!("No addresses found to listen on");
+        }
+        Ok(ip_addrs
+            .into_iter()
+            .map(|ip_addr| SocketAddr::new(ip_addr, socket_addr.port()))
+            .collect())
+    }
+
+    async fn build_catalog(
+        &self,
+        cfg: &ServerConfig,
+        view: &str,
+        zones: &[ZoneConfig],
+        upstreams: &[String],
+        trust_anchor: &TrustAnchor,
+    ) -> Result<Catalog> {
         let mut catalog = Catalog::new();
+        let recursion_acl = Acl::from_config(&cfg.recursion_acl)?;
+        let client_subnet = cfg
+            .client_subnet
+            .as_ref()
+            .map(ClientSubnetPolicy::from_config)
+            .transpose()?;
+        let sortlist = cfg.sortlist.clone().map(Sortlist::new);
 
         // Zones
-        for zone_config in self.cfg.zones.clone().into_iter() {
+        for zone_config in zones.iter().cloned() {
             let zone_name = Name::from_str(zone_config.name.as_str())?;
 
             // In-memory authority for static records.
             let in_memory_authority =
                 InMemoryAuthority::empty(zone_name.clone(), ZoneType::Primary, false);
             let mut i: u32 = 0;
-            for record_config in zone_config.records.into_iter() {
-                let name = render_record_name(&record_config.key, &zone_name)?;
-                let value = record_config.value;
-                let ip_addr: IpAddr = value.parse()?;
-                let rr_type = get_ip_addr_record_type(&ip_addr)?;
-                info!(
-                    "Registering record {:?}={:?} for zone {:?}",
-                    name, value, zone_name
-                );
-                let mut record = Record::new();
-                let rdata = create_record_data(value.as_str())?;
-                record
-                    .set_name(name)
-                    .set_rr_type(rr_type)
-                    .set_dns_class(DNSClass::IN)
-                    .set_ttl(30)
-                    .set_data(rdata);
-                in_memory_authority.upsert(record, i).await;
-                i += 1;
+            for record_config in zone_config.records.iter() {
+                for record in static_rrset(&zone_config, record_config)? {
+                    info!(
+                        "Registering record {} {} {} for zone {:?}",
+                        record.name(),
+                        record.record_type(),
+                        record.data().map(ToString::to_string).unwrap_or_default(),
+                        zone_name
+                    );
+                    in_memory_authority.upsert(record, i).await;
+                    i += 1;
+                }
             }
 
             // Forwarding authority
             let mut nameservers = NameServerConfigGroup::new();
-            for nameserver in self.cfg.nameservers.iter() {
+            for nameserver in upstreams.iter() {
                 let socket_addr = parse_ip_optional_socket(nameserver, 53)?;
                 info!(
                     "Registering upstream {:?} for zone {:?}",
@@ -76,6 +199,25 @@ impl DnsServer {
                 nameservers.push(NameServerConfig::new(socket_addr, Protocol::Udp));
                 nameservers.push(NameServerConfig::new(socket_addr, Protocol::Tcp));
             }
+            let validate = zone_config.dnssec_validate.unwrap_or(cfg.dnssec.validate);
+            if validate {
+                info!(
+                    "Validating forwarded answers with DNSSEC for zone {:?}",
+                    zone_name
+                );
+            }
+            if client_subnet.is_some() {
+                info!("Forwarding client subnets for zone {:?}", zone_name);
+            }
+            let forwarder = if validate || client_subnet.is_some() {
+                Some(Forwarder::new(
+                    nameservers.clone(),
+                    validate.then(|| trust_anchor.clone()),
+                    client_subnet.clone(),
+                ))
+            } else {
+                None
+            };
             let forward_config = ForwardConfig {
                 name_servers: nameservers,
                 options: None,
@@ -92,14 +234,30 @@ impl DnsServer {
                 origin: LowerName::from(zone_name.clone()),
                 zone_type: ZoneType::Primary,
                 repo: self.repo.clone(),
+                view: view.to_string(),
             };
 
             // Split authority
+            let mut lookup_order = zone_config
+                .lookup_order
+                .clone()
+                .unwrap_or_else(|| DEFAULT_LOOKUP_ORDER.to_vec());
+            if zone_config.forward == Some(false) {
+                info!("Not forwarding queries for zone {:?}", zone_name);
+                lookup_order.retain(|tier| *tier != LookupTier::Forward);
+            }
             let split_authority = SplitAuthority {
                 origin: LowerName::from(zone_name.clone()),
+                static_names: static_names(&in_memory_authority).await,
                 in_memory_authority,
                 sqlite_authority,
                 forward_authority,
+                forwarder,
+                query_acl: Acl::from_config(&zone_config.query_acl)?,
+                recursion_acl: recursion_acl.clone(),
+                sortlist: sortlist.clone(),
+                lookup_order,
+                allow_shadowing: zone_config.allow_shadowing,
             };
 
             catalog.upsert(
@@ -107,24 +265,138 @@ impl DnsServer {
                 Box::new(Arc::new(split_authority)),
             );
         }
+        Ok(catalog)
+    }
 
-        let mut server = ServerFuture::new(catalog);
+    /// Builds the catalogs for the default zones and every view from a config.
+    ///
+    /// Fails on anything invalid in the config, so a reloaded config is checked before it's used.
+    pub async fn build_catalogs(&self, cfg: &ServerConfig) -> Result<Catalogs> {
+        let trust_anchor = match &cfg.dnssec.trust_anchor_file {
+            Some(path) => load_trust_anchor(path)?,
+            None => TrustAnchor::default(),
+        };
+        let catalog = self
+            .build_catalog(cfg, "", &cfg.zones, &cfg.nameservers, &trust_anchor)
+            .await?;
+
+        // Views
+        let mut views = vec![];
+        for view_config in cfg.views.iter() {
+            let match_clients = view_config
+                .match_clients
+                .iter()
+                .map(|client| parse_cidr(client))
+                .collect::<Result<Vec<IpNet>>>()?;
+            let match_destinations = view_config
+                .match_destinations
+                .iter()
+                .map(|destination| parse_cidr(destination))
+                .collect::<Result<Vec<IpNet>>>()?;
+            info!(
+                "Registering view {:?} for clients {:?} and destinations {:?}",
+                view_config.name, match_clients, match_destinations
+            );
+            let upstreams = view_config.nameservers.as_ref().unwrap_or(&cfg.nameservers);
+            let catalog = self
+                .build_catalog(
+                    cfg,
+                    &view_config.name,
+                    &view_config.zones,
+                    upstreams,
+                    &trust_anchor,
+                )
+                .await?;
+            views.push(View {
+                name: view_config.name.clone(),
+                match_clients,
+                match_destinations,
+                catalog,
+            });
+        }
+        Ok(Catalogs { catalog, views })
+    }
 
-        // Configure UDP listener
-        let dns_listen_addr = self.get_socket_addr()?;
-        let dns_udp_socket = UdpSocket::bind(dns_listen_addr).await?;
-        let dns_upd_local_addr = dns_udp_socket.local_addr()?;
-        server.register_socket(dns_udp_socket);
-        info!("DNS server listening on {:?} (udp)", dns_upd_local_addr);
+    /// Swaps in the catalogs of every new config, until the sender is dropped.
+    ///
+    /// Configs that fail to build are logged and the previous catalogs are kept serving.
+    async fn reload(
+        &self,
+        catalogs: &RwLock<Arc<Catalogs>>,
+        mut configs: watch::Receiver<Arc<ServerConfig>>,
+    ) {
+        while configs.changed().await.is_ok() {
+            let cfg = configs.borrow_and_update().clone();
+            match self.build_catalogs(&cfg).await {
+                Ok(new_catalogs) => {
+                    *catalogs.write().expect("catalogs lock poisoned") = Arc::new(new_catalogs);
+                    info!("Reloaded zones from the new config");
+                }
+                Err(err) => error!(
+                    "Rejected the new config, keeping the previous zones: {}",
+                    err
+                ),
+            }
+        }
+    }
 
-        // Configure TCP listener
-        let dns_tcp_listener = TcpListener::bind(dns_listen_addr).await?;
-        let dns_tcp_request_timeout = Duration::from_secs(3);
-        let dns_tpc_local_addr = dns_tcp_listener.local_addr()?;
-        server.register_listener(dns_tcp_listener, dns_tcp_request_timeout);
-        info!("DNS server listening on {:?} (tcp)", dns_tpc_local_addr);
+    pub async fn run(&self) -> Result<()> {
+        self.serve(None).await
+    }
 
-        server.block_until_done().await?;
+    /// Runs the server, rebuilding its zones whenever a new config is sent.
+    ///
+    /// The listeners and rate limiter are kept, changes to them need a restart.
+    pub async fn run_with_reloads(
+        &self,
+        configs: watch::Receiver<Arc<ServerConfig>>,
+    ) -> Result<()> {
+        self.serve(Some(configs)).await
+    }
+
+    async fn serve(&self, configs: Option<watch::Receiver<Arc<ServerConfig>>>) -> Result<()> {
+        let catalogs = Arc::new(RwLock::new(Arc::new(self.build_catalogs(&self.cfg).await?)));
+
+        let rate_limiter = self.cfg.rate_limit.clone().map(|rate_limit| {
+            info!("Rate limiting responses with {:?}", rate_limit);
+            Arc::new(ResponseRateLimiter::new(rate_limit))
+        });
+
+        let mut servers = JoinSet::new();
+        for dns_listen_addr in self.listen_addrs()? {
+            let mut server = ServerFuture::new(SwanRequestHandler {
+                catalogs: catalogs.clone(),
+                rate_limiter: rate_limiter.clone(),
+                local_ip: dns_listen_addr.ip(),
+            });
+
+            // Configure UDP listener
+            let dns_udp_socket = UdpSocket::bind(dns_listen_addr).await?;
+            let dns_upd_local_addr = dns_udp_socket.local_addr()?;
+            server.register_socket(dns_udp_socket);
+            info!("DNS server listening on {:?} (udp)", dns_upd_local_addr);
+
+            // Configure TCP listener
+            let dns_tcp_listener = TcpListener::bind(dns_listen_addr).await?;
+            let dns_tcp_request_timeout = Duration::from_secs(3);
+            let dns_tpc_local_addr = dns_tcp_listener.local_addr()?;
+            server.register_listener(dns_tcp_listener, dns_tcp_request_timeout);
+            info!("DNS server listening on {:?} (tcp)", dns_tpc_local_addr);
+
+            servers.spawn(async move { server.block_until_done().await });
+        }
+
+        let reload = async {
+            if let Some(configs) = configs {
+                self.reload(&catalogs, configs).await;
+            }
+            // Keep serving the last config once nothing sends new ones.
+            std::future::pending::<()>().await
+        };
+        tokio::select! {
+            Some(res) = servers.join_next() => res??,
+            _ = reload => {}
+        }
         Ok(())
     }
 }
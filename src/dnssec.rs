@@ -0,0 +1,734 @@
+use crate::forwarder::Upstreams;
+use anyhow::{anyhow, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use hickory_server::proto::op::{Message, Query, ResponseCode};
+use hickory_server::proto::rr::dnssec::rdata::{DNSSECRData, DNSKEY, DS, NSEC, NSEC3, RRSIG};
+use hickory_server::proto::rr::dnssec::{Algorithm, PublicKeyBuf, TrustAnchor, Verifier};
+use hickory_server::proto::rr::{DNSClass, RData, Record, RecordType};
+use hickory_server::resolver::Name;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{debug, info};
+
+/// Loads DNSKEY records from a file in zone file presentation format to use as trust anchors.
+///
+/// Only the DNSKEY records are read, everything else in the file is ignored.
+pub fn load_trust_anchor(path: &Path) -> Result<TrustAnchor> {
+    info!("Loading DNSSEC trust anchors from {:?}", path);
+    let contents = std::fs::read_to_string(path)?;
+
+    let mut trust_anchor = TrustAnchor::new();
+    for line in contents.lines() {
+        let line = line.split(';').next().unwrap_or_default();
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let Some(i) = tokens
+            .iter()
+            .position(|token| token.eq_ignore_ascii_case("DNSKEY"))
+        else {
+            continue;
+        };
+        // <flags> <protocol> <algorithm> <public key...>
+        if tokens.len() < i + 5 {
+            return Err(anyhow!("Malformed DNSKEY record in {:?}: {}", path, line));
+        }
+        let public_key = STANDARD.decode(tokens[i + 4..].concat())?;
+        debug!("Adding trust anchor {:?}", tokens[0]);
+        trust_anchor.insert_trust_anchor(&PublicKeyBuf::new(public_key));
+    }
+    if trust_anchor.is_empty() {
+        return Err(anyhow!("No DNSKEY records found in {:?}", path));
+    }
+    Ok(trust_anchor)
+}
+
+/// NSEC3 proofs with more iterations than this aren't validated, their answers are treated as
+/// insecure as recommended by RFC 9276.
+static MAX_NSEC3_ITERATIONS: u16 = 150;
+
+/// CNAME records followed within an answer before giving up.
+static MAX_CNAME_CHAIN: usize = 16;
+
+/// Zones whose status is cached before expired entries are cleaned up.
+static MAX_CACHED_ZONES: usize = 10_000;
+
+/// How far a validated answer can be trusted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Security {
+    /// Every record is signed by a chain of keys from the trust anchor.
+    Secure,
+    /// Some records belong to a zone that its signed parent proves to be unsigned.
+    Insecure,
+}
+
+/// The keys of a signed zone, authenticated from the trust anchor down.
+#[derive(Debug)]
+struct ZoneKeys {
+    zone: Name,
+    keys: Vec<DNSKEY>,
+}
+
+#[derive(Debug, Clone)]
+enum ZoneStatus {
+    Secure(Arc<ZoneKeys>),
+    Insecure,
+}
+
+/// What a parent zone's DS answer says about a name below it.
+enum Cut {
+    /// A signed zone starts at the name.
+    Signed(ZoneKeys),
+    /// An unsigned zone starts at the name, or its keys use algorithms that aren't supported.
+    Unsigned,
+    /// The name is in the parent zone.
+    Within,
+    /// The name doesn't exist, and neither does anything below it.
+    Missing,
+}
+
+/// What the NSEC or NSEC3 records of a signed zone prove about a name.
+#[derive(Debug)]
+enum Denial {
+    /// The name doesn't exist.
+    NoName,
+    /// The name, or the wildcard that would match it, exists with these types only.
+    NoData(Vec<RecordType>),
+    /// Nothing is proven because of NSEC3 opt-out or NSEC3 parameters that aren't validated.
+    Insecure,
+}
+
+/// Validates forwarded answers with DNSSEC.
+///
+/// The zone of every name in an answer is found by walking the DS and DNSKEY records from the
+/// trust anchor down, so zones that a signed parent proves to be unsigned are insecure rather
+/// than bogus. Negative answers have to be proven with NSEC or NSEC3 records.
+pub struct Validator {
+    trust_anchor: TrustAnchor,
+    /// Zone status by name, until the records that proved it expire.
+    zones: Mutex<HashMap<Name, (Instant, ZoneStatus)>>,
+}
+
+impl Validator {
+    pub fn new(trust_anchor: TrustAnchor) -> Self {
+        Self {
+            trust_anchor,
+            zones: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Validates an upstream response to a query, returning an error if it's bogus.
+    pub async fn validate(
+        &self,
+        upstreams: &Upstreams,
+        query: &Query,
+        response: &Message,
+    ) -> Result<Security> {
+        let mut security = Security::Secure;
+        let answers = response.answers();
+        let rrsets = rrsets(answers);
+        for ((owner, rtype), records) in rrsets.iter() {
+            let keys = match self.zone_status(upstreams, &signer(owner, *rtype)).await? {
+                ZoneStatus::Secure(keys) => keys,
+                ZoneStatus::Insecure => {
+                    security = Security::Insecure;
+                    continue;
+                }
+            };
+            let (_, labels) = verify_rrset(&keys, records, answers)?;
+            if labels < owner.num_labels() {
+                // Expanded from a wildcard, so the name itself mustn't exist.
+                if prove_expansion(&keys, response.name_servers(), owner, labels)?
+                    == Security::Insecure
+                {
+                    security = Security::Insecure;
+                }
+            }
+        }
+
+        // Follow the CNAME chain to find the name the answer is about.
+        let qtype = query.query_type();
+        let mut name = query.name().to_lowercase();
+        let mut followed = 0;
+        loop {
+            if qtype == RecordType::ANY || rrsets.contains_key(&(name.clone(), qtype)) {
+                return Ok(security);
+            }
+            let target = rrsets
+                .get(&(name.clone(), RecordType::CNAME))
+                .and_then(|records| records[0].data())
+                .and_then(|data| data.as_cname())
+                .map(|target| target.0.to_lowercase());
+            match target {
+                Some(_) if followed == MAX_CNAME_CHAIN => {
+                    return Err(anyhow!("CNAME chain of {} is too long", query.name()))
+                }
+                Some(target) => {
+                    name = target;
+                    followed += 1;
+                }
+                None => break,
+            }
+        }
+
+        let keys = match self.zone_status(upstreams, &signer(&name, qtype)).await? {
+            ZoneStatus::Secure(keys) => keys,
+            ZoneStatus::Insecure => return Ok(Security::Insecure),
+        };
+        let (denial, _) = deny(&keys, response.name_servers(), &name)?;
+        match (denial, response.response_code()) {
+            (Denial::NoName, ResponseCode::NXDomain) => Ok(security),
+            (Denial::NoData(types), ResponseCode::NoError)
+                if !types.contains(&qtype) && !types.contains(&RecordType::CNAME) =>
+            {
+                Ok(security)
+            }
+            (Denial::Insecure, _) => Ok(Security::Insecure),
+            (denial, code) => Err(anyhow!(
+                "{:?} doesn't prove {} for {} {}",
+                denial,
+                code,
+                name,
+                qtype
+            )),
+        }
+    }
+
+    /// Finds the zone of a name, walking down from the root one label at a time.
+    async fn zone_status(&self, upstreams: &Upstreams, name: &Name) -> Result<ZoneStatus> {
+        let name = name.to_lowercase();
+        let mut status = match self.cached(&Name::root()) {
+            Some(status) => status,
+            None => {
+                let (keys, ttl) = self
+                    .zone_keys(upstreams, &Name::root(), |key| {
+                        self.trust_anchor.contains_dnskey_bytes(key.public_key())
+                    })
+                    .await?;
+                let status = ZoneStatus::Secure(Arc::new(keys));
+                self.remember(Name::root(), status.clone(), ttl);
+                status
+            }
+        };
+        for labels in 1..=name.num_labels() {
+            let child = name.trim_to(labels as usize);
+            if let Some(cached) = self.cached(&child) {
+                status = cached;
+                continue;
+            }
+            let ZoneStatus::Secure(parent) = &status else {
+                // Everything below an unsigned zone is unsigned too.
+                break;
+            };
+            let (cut, ttl) = self.find_cut(upstreams, parent, &child).await?;
+            let missing = matches!(cut, Cut::Missing);
+            status = match cut {
+                Cut::Signed(keys) => ZoneStatus::Secure(Arc::new(keys)),
+                Cut::Unsigned => {
+                    debug!("{} is an unsigned zone", child);
+                    ZoneStatus::Insecure
+                }
+                Cut::Within | Cut::Missing => status,
+            };
+            self.remember(child, status.clone(), ttl);
+            if missing {
+                break;
+            }
+        }
+        Ok(status)
+    }
+
+    /// Looks up the DS records of a name to find out whether a zone starts there.
+    async fn find_cut(
+        &self,
+        upstreams: &Upstreams,
+        parent: &ZoneKeys,
+        name: &Name,
+    ) -> Result<(Cut, u32)> {
+        let response = upstreams.query(name, RecordType::DS).await?;
+        let answers = response.answers();
+        let rrsets = rrsets(answers);
+
+        if let Some(records) = rrsets.get(&(name.clone(), RecordType::DS)) {
+            let (ttl, _) = verify_rrset(parent, records, answers)?;
+            let ds: Vec<&DS> = records
+                .iter()
+                .filter_map(|record| match record.data() {
+                    Some(RData::DNSSEC(DNSSECRData::DS(ds))) => Some(ds),
+                    _ => None,
+                })
+                .filter(|ds| is_supported(ds.algorithm()))
+                .collect();
+            if ds.is_empty() {
+                debug!("{} is signed with unsupported algorithms", name);
+                return Ok((Cut::Unsigned, ttl));
+            }
+            let (keys, keys_ttl) = self
+                .zone_keys(upstreams, name, |key| {
+                    ds.iter().any(|ds| {
+                        ds.algorithm() == key.algorithm() && ds.covers(name, key).unwrap_or(false)
+                    })
+                })
+                .await?;
+            return Ok((Cut::Signed(keys), ttl.min(keys_ttl)));
+        }
+        if let Some(records) = rrsets.get(&(name.clone(), RecordType::CNAME)) {
+            let (ttl, _) = verify_rrset(parent, records, answers)?;
+            return Ok((Cut::Within, ttl));
+        }
+
+        let (denial, ttl) = deny(parent, response.name_servers(), name)?;
+        let cut = match denial {
+            Denial::NoName => Cut::Missing,
+            Denial::NoData(types) if types.contains(&RecordType::DS) => {
+                return Err(anyhow!(
+                    "DS records of {} are missing from the answer",
+                    name
+                ))
+            }
+            Denial::NoData(types)
+                if types.contains(&RecordType::NS) && !types.contains(&RecordType::SOA) =>
+            {
+                Cut::Unsigned
+            }
+            Denial::NoData(_) => Cut::Within,
+            Denial::Insecure => Cut::Unsigned,
+        };
+        Ok((cut, ttl))
+    }
+
+    /// Looks up the DNSKEY records of a zone, which have to be signed by one of the trusted keys.
+    async fn zone_keys(
+        &self,
+        upstreams: &Upstreams,
+        zone: &Name,
+        trusted: impl Fn(&DNSKEY) -> bool,
+    ) -> Result<(ZoneKeys, u32)> {
+        let response = upstreams.query(zone, RecordType::DNSKEY).await?;
+        let answers = response.answers();
+        let records = rrsets(answers)
+            .remove(&(zone.clone(), RecordType::DNSKEY))
+            .ok_or_else(|| anyhow!("No DNSKEY records found for {}", zone))?;
+        let keys: Vec<DNSKEY> = records
+            .iter()
+            .filter_map(|record| match record.data() {
+                Some(RData::DNSSEC(DNSSECRData::DNSKEY(key))) => Some(key.clone()),
+                _ => None,
+            })
+            .collect();
+        let trusted_keys = ZoneKeys {
+            zone: zone.clone(),
+            keys: keys.iter().filter(|key| trusted(key)).cloned().collect(),
+        };
+        if trusted_keys.keys.is_empty() {
+            return Err(anyhow!("No trusted DNSKEY records found for {}", zone));
+        }
+        let (ttl, _) = verify_rrset(&trusted_keys, &records, answers)?;
+        Ok((
+            ZoneKeys {
+                zone: zone.clone(),
+                keys,
+            },
+            ttl,
+        ))
+    }
+
+    fn cached(&self, name: &Name) -> Option<ZoneStatus> {
+        let zones = self.zones.lock().unwrap();
+        zones
+            .get(name)
+            .filter(|(expires, _)| *expires > Instant::now())
+            .map(|(_, status)| status.clone())
+    }
+
+    fn remember(&self, name: Name, status: ZoneStatus, ttl: u32) {
+        let now = Instant::now();
+        let mut zones = self.zones.lock().unwrap();
+        if zones.len() >= MAX_CACHED_ZONES {
+            zones.retain(|_, (expires, _)| *expires > now);
+        }
+        zones.insert(name, (now + Duration::from_secs(ttl.into()), status));
+    }
+}
+
+/// Returns the name whose zone signs an RRset. DS records are signed by the parent zone.
+fn signer(owner: &Name, rtype: RecordType) -> Name {
+    if rtype == RecordType::DS && !owner.is_root() {
+        owner.base_name()
+    } else {
+        owner.clone()
+    }
+}
+
+/// Groups the records of a section into RRsets by lowercased owner name and type, leaving out
+/// the RRSIG records.
+fn rrsets(records: &[Record]) -> HashMap<(Name, RecordType), Vec<Record>> {
+    let mut rrsets: HashMap<(Name, RecordType), Vec<Record>> = HashMap::new();
+    for record in records {
+        if record.record_type() == RecordType::RRSIG {
+            continue;
+        }
+        rrsets
+            .entry((record.name().to_lowercase(), record.record_type()))
+            .or_default()
+            .push(record.clone());
+    }
+    rrsets
+}
+
+#[allow(deprecated)]
+fn is_supported(algorithm: Algorithm) -> bool {
+    matches!(
+        algorithm,
+        Algorithm::RSASHA1
+            | Algorithm::RSASHA1NSEC3SHA1
+            | Algorithm::RSASHA256
+            | Algorithm::RSASHA512
+            | Algorithm::ECDSAP256SHA256
+            | Algorithm::ECDSAP384SHA384
+            | Algorithm::ED25519
+    )
+}
+
+/// Verifies an RRset with the RRSIG records of a section and the keys of its zone.
+///
+/// Returns how long the RRset can be trusted and the label count of the signature, which is
+/// lower than the owner's for records expanded from a wildcard.
+fn verify_rrset(keys: &ZoneKeys, records: &[Record], section: &[Record]) -> Result<(u32, u8)> {
+    let name = records[0].name();
+    let rtype = records[0].record_type();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs() as u32);
+    let rrsigs: Vec<&RRSIG> = section
+        .iter()
+        .filter(|record| record.name() == name)
+        .filter_map(|record| match record.data() {
+            Some(RData::DNSSEC(DNSSECRData::RRSIG(rrsig))) if rrsig.type_covered() == rtype => {
+                Some(rrsig)
+            }
+            _ => None,
+        })
+        .collect();
+    if rrsigs.is_empty() {
+        return Err(anyhow!("{} {} isn't signed", name, rtype));
+    }
+
+    for rrsig in rrsigs {
+        // Signature times use serial number arithmetic, see RFC 4034 section 3.1.5.
+        let valid = (now.wrapping_sub(rrsig.sig_inception()) as i32) >= 0
+            && (rrsig.sig_expiration().wrapping_sub(now) as i32) >= 0;
+        if !valid
+            || rrsig.signer_name() != &keys.zone
+            || !keys.zone.zone_of(name)
+            || rrsig.num_labels() > name.num_labels()
+        {
+            continue;
+        }
+        let verified = keys
+            .keys
+            .iter()
+            .filter(|key| key.zone_key() && !key.revoke())
+            .filter(|key| key.algorithm() == rrsig.algorithm() && is_supported(key.algorithm()))
+            .filter(|key| key.calculate_key_tag().ok() == Some(rrsig.key_tag()))
+            .any(|key| key.verify_rrsig(name, DNSClass::IN, rrsig, records).is_ok());
+        if verified {
+            let ttl = records
+                .iter()
+                .map(|record| record.ttl())
+                .chain([
+                    rrsig.original_ttl(),
+                    rrsig.sig_expiration().wrapping_sub(now),
+                ])
+                .min()
+                .unwrap_or_default();
+            return Ok((ttl, rrsig.num_labels()));
+        }
+    }
+    Err(anyhow!(
+        "No valid signature from {} for {} {}",
+        keys.zone,
+        name,
+        rtype
+    ))
+}
+
+/// The verified NSEC and NSEC3 records of a zone in a section, with how long they can be
+/// trusted. Records that don't verify with the zone's keys are left out.
+#[allow(clippy::type_complexity)]
+fn denial_records(
+    keys: &ZoneKeys,
+    section: &[Record],
+) -> (Vec<(Name, NSEC)>, Vec<(Name, NSEC3)>, u32) {
+    let mut nsecs = vec![];
+    let mut nsec3s = vec![];
+    let mut ttl = u32::MAX;
+    for ((owner, rtype), records) in rrsets(section) {
+        if rtype != RecordType::NSEC && rtype != RecordType::NSEC3 {
+            continue;
+        }
+        let rrset_ttl = match verify_rrset(keys, &records, section) {
+            Ok((rrset_ttl, _)) => rrset_ttl,
+            Err(err) => {
+                debug!("Ignoring {} {}: {}", owner, rtype, err);
+                continue;
+            }
+        };
+        ttl = ttl.min(rrset_ttl);
+        for record in records {
+            match record.data() {
+                Some(RData::DNSSEC(DNSSECRData::NSEC(nsec))) => {
+                    nsecs.push((owner.clone(), nsec.clone()))
+                }
+                Some(RData::DNSSEC(DNSSECRData::NSEC3(nsec3))) => {
+                    nsec3s.push((owner.clone(), nsec3.clone()))
+                }
+                _ => {}
+            }
+        }
+    }
+    (nsecs, nsec3s, ttl)
+}
+
+/// Proves from the authority section of a negative answer what doesn't exist at a name.
+fn deny(keys: &ZoneKeys, authority: &[Record], name: &Name) -> Result<(Denial, u32)> {
+    let (nsecs, nsec3s, ttl) = denial_records(keys, authority);
+    let denial = if !nsecs.is_empty() {
+        nsec_denial(&keys.zone, name, &nsecs)?
+    } else if !nsec3s.is_empty() {
+        nsec3_denial(&keys.zone, name, &nsec3s)?
+    } else {
+        return Err(anyhow!(
+            "No signed NSEC or NSEC3 records from {} for {}",
+            keys.zone,
+            name
+        ));
+    };
+    Ok((denial, ttl))
+}
+
+/// Proves that the name a wildcard was expanded for doesn't exist itself.
+fn prove_expansion(
+    keys: &ZoneKeys,
+    authority: &[Record],
+    name: &Name,
+    labels: u8,
+) -> Result<Security> {
+    let (nsecs, nsec3s, _) = denial_records(keys, authority);
+    if nsecs
+        .iter()
+        .any(|(owner, nsec)| covers(owner, nsec.next_domain_name(), name))
+    {
+        return Ok(Security::Secure);
+    }
+    if let Some(params) = nsec3s.first().map(|(_, nsec3)| nsec3) {
+        if params.iterations() > MAX_NSEC3_ITERATIONS {
+            return Ok(Security::Insecure);
+        }
+        let next_closer = name.trim_to(labels as usize + 1);
+        let hashes = nsec3_hashes(&keys.zone, &nsec3s, params);
+        let hash = nsec3_hash(params, &next_closer)?;
+        if let Some(nsec3) = hashes.covering(&hash) {
+            return Ok(if nsec3.opt_out() {
+                Security::Insecure
+            } else {
+                Security::Secure
+            });
+        }
+    }
+    Err(anyhow!(
+        "Nothing proves that {} doesn't exist for its wildcard expansion",
+        name
+    ))
+}
+
+/// Whether an NSEC record from `owner` to `next` covers a name. The last NSEC record of a zone
+/// wraps around to the apex.
+fn covers(owner: &Name, next: &Name, name: &Name) -> bool {
+    owner < name && (name < next || next <= owner)
+}
+
+/// Returns the longest common ancestor of two names.
+fn common_ancestor(a: &Name, b: &Name) -> Name {
+    let mut labels = a.num_labels().min(b.num_labels()) as usize;
+    while labels > 0 && a.trim_to(labels) != b.trim_to(labels) {
+        labels -= 1;
+    }
+    a.trim_to(labels)
+}
+
+fn wildcard(encloser: &Name) -> Result<Name> {
+    Ok(Name::from_ascii("*")?.append_domain(encloser)?)
+}
+
+/// Whether an NSEC bitmap belongs to a delegation, whose NSEC records can't prove anything about
+/// the names below it.
+fn is_delegation(types: &[RecordType]) -> bool {
+    types.contains(&RecordType::NS) && !types.contains(&RecordType::SOA)
+}
+
+/// Proves a denial with NSEC records, see RFC 4035 section 5.4.
+fn nsec_denial(zone: &Name, name: &Name, nsecs: &[(Name, NSEC)]) -> Result<Denial> {
+    if let Some((_, nsec)) = nsecs.iter().find(|(owner, _)| owner == name) {
+        return Ok(Denial::NoData(nsec.type_bit_maps().to_vec()));
+    }
+    let (owner, nsec) = nsecs
+        .iter()
+        .find(|(owner, nsec)| covers(owner, nsec.next_domain_name(), name))
+        .ok_or_else(|| anyhow!("No NSEC record proves that {} doesn't exist", name))?;
+    if owner.zone_of(name) && is_delegation(nsec.type_bit_maps()) {
+        return Err(anyhow!(
+            "The NSEC record of {} is from above a delegation",
+            owner
+        ));
+    }
+
+    // The closest encloser is the longest ancestor of the name that exists in the zone.
+    let encloser = [owner, nsec.next_domain_name()]
+        .into_iter()
+        .map(|other| common_ancestor(name, other))
+        .max_by_key(|ancestor| ancestor.num_labels())
+        .unwrap_or_else(Name::root);
+    if !zone.zone_of(&encloser) {
+        return Err(anyhow!("The NSEC record of {} is outside {}", owner, zone));
+    }
+    let wildcard = wildcard(&encloser)?;
+    if let Some((_, nsec)) = nsecs.iter().find(|(owner, _)| *owner == wildcard) {
+        return Ok(Denial::NoData(nsec.type_bit_maps().to_vec()));
+    }
+    if nsecs
+        .iter()
+        .any(|(owner, nsec)| covers(owner, nsec.next_domain_name(), &wildcard))
+    {
+        return Ok(Denial::NoName);
+    }
+    Err(anyhow!(
+        "No NSEC record proves that {} doesn't exist",
+        wildcard
+    ))
+}
+
+/// The hashed owner names of NSEC3 records that share the same parameters.
+struct Nsec3Hashes<'a>(Vec<(Vec<u8>, &'a NSEC3)>);
+
+impl<'a> Nsec3Hashes<'a> {
+    fn matching(&self, hash: &[u8]) -> Option<&'a NSEC3> {
+        self.0
+            .iter()
+            .find(|(owner, _)| owner == hash)
+            .map(|(_, nsec3)| *nsec3)
+    }
+
+    fn covering(&self, hash: &[u8]) -> Option<&'a NSEC3> {
+        self.0
+            .iter()
+            .find(|(owner, nsec3)| {
+                let next = nsec3.next_hashed_owner_name();
+                owner.as_slice() < hash && (hash < next || next <= owner.as_slice())
+            })
+            .map(|(_, nsec3)| *nsec3)
+    }
+}
+
+fn nsec3_hashes<'a>(zone: &Name, nsec3s: &'a [(Name, NSEC3)], params: &NSEC3) -> Nsec3Hashes<'a> {
+    Nsec3Hashes(
+        nsec3s
+            .iter()
+            .filter(|(_, nsec3)| {
+                nsec3.hash_algorithm() == params.hash_algorithm()
+                    && nsec3.iterations() == params.iterations()
+                    && nsec3.salt() == params.salt()
+            })
+            .filter(|(owner, _)| owner.num_labels() > 0 && owner.base_name() == *zone)
+            .filter_map(|(owner, nsec3)| {
+                let label = owner.iter().next()?;
+                Some((base32hex_decode(label)?, nsec3))
+            })
+            .collect(),
+    )
+}
+
+fn nsec3_hash(params: &NSEC3, name: &Name) -> Result<Vec<u8>> {
+    Ok(params
+        .hash_algorithm()
+        .hash(params.salt(), name, params.iterations())?
+        .as_ref()
+        .to_vec())
+}
+
+/// Decodes the base32hex encoding of NSEC3 owner names, without padding.
+fn base32hex_decode(label: &[u8]) -> Option<Vec<u8>> {
+    let mut bits = 0u32;
+    let mut count = 0;
+    let mut decoded = vec![];
+    for c in label {
+        let value = match c.to_ascii_lowercase() {
+            c @ b'0'..=b'9' => c - b'0',
+            c @ b'a'..=b'v' => c - b'a' + 10,
+            _ => return None,
+        };
+        bits = (bits << 5) | u32::from(value);
+        count += 5;
+        if count >= 8 {
+            count -= 8;
+            decoded.push((bits >> count) as u8);
+            bits &= (1 << count) - 1;
+        }
+    }
+    Some(decoded)
+}
+
+/// Proves a denial with NSEC3 records, see RFC 5155 section 8.
+fn nsec3_denial(zone: &Name, name: &Name, nsec3s: &[(Name, NSEC3)]) -> Result<Denial> {
+    let params = &nsec3s[0].1;
+    if params.iterations() > MAX_NSEC3_ITERATIONS {
+        debug!(
+            "Not validating NSEC3 records with {} iterations",
+            params.iterations()
+        );
+        return Ok(Denial::Insecure);
+    }
+    let hashes = nsec3_hashes(zone, nsec3s, params);
+    if let Some(nsec3) = hashes.matching(&nsec3_hash(params, name)?) {
+        return Ok(Denial::NoData(nsec3.type_bit_maps().to_vec()));
+    }
+
+    // The closest encloser is the longest ancestor of the name that exists in the zone.
+    let mut encloser = None;
+    for labels in (zone.num_labels()..name.num_labels()).rev() {
+        let ancestor = name.trim_to(labels as usize);
+        if let Some(nsec3) = hashes.matching(&nsec3_hash(params, &ancestor)?) {
+            if labels > zone.num_labels() && is_delegation(nsec3.type_bit_maps()) {
+                return Err(anyhow!(
+                    "The NSEC3 record of {} is from above a delegation",
+                    ancestor
+                ));
+            }
+            encloser = Some(ancestor);
+            break;
+        }
+    }
+    let encloser =
+        encloser.ok_or_else(|| anyhow!("No NSEC3 record proves a closest encloser of {}", name))?;
+    let next_closer = name.trim_to(encloser.num_labels() as usize + 1);
+    let cover = hashes
+        .covering(&nsec3_hash(params, &next_closer)?)
+        .ok_or_else(|| anyhow!("No NSEC3 record proves that {} doesn't exist", next_closer))?;
+    if cover.opt_out() {
+        return Ok(Denial::Insecure);
+    }
+
+    let wildcard = wildcard(&encloser)?;
+    let hash = nsec3_hash(params, &wildcard)?;
+    if let Some(nsec3) = hashes.matching(&hash) {
+        return Ok(Denial::NoData(nsec3.type_bit_maps().to_vec()));
+    }
+    if hashes.covering(&hash).is_some() {
+        return Ok(Denial::NoName);
+    }
+    Err(anyhow!(
+        "No NSEC3 record proves that {} doesn't exist",
+        wildcard
+    ))
+}
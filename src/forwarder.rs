@@ -0,0 +1,311 @@
+use crate::dnssec::{Security, Validator};
+use crate::request_handler::override_response;
+use crate::util::{canonical_ip, client_network, parse_cidr};
+use crate::ClientSubnetConfig;
+use anyhow::{anyhow, Result};
+use hickory_server::authority::LookupError;
+use hickory_server::proto::op::{Edns, Message, Query, ResponseCode};
+use hickory_server::proto::rr::dnssec::TrustAnchor;
+use hickory_server::proto::rr::rdata::opt::{ClientSubnet, EdnsOption};
+use hickory_server::proto::rr::{LowerName, Record, RecordType};
+use hickory_server::proto::xfer::{DnsHandle, DnsRequest, DnsRequestOptions, DnsResponse};
+use hickory_server::resolver::config::{NameServerConfigGroup, ResolverOpts};
+use hickory_server::resolver::error::ResolveErrorKind;
+use hickory_server::resolver::lookup::Lookup;
+use hickory_server::resolver::name_server::{
+    ConnectionProvider, GenericConnection, NameServerPool, TokioConnectionProvider,
+};
+use hickory_server::resolver::Name;
+use ipnet::IpNet;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use tokio_stream::StreamExt;
+use tracing::{debug, warn};
+
+/// EDNS payload size advertised upstream, as recommended by DNS flag day 2020.
+static MAX_PAYLOAD: u16 = 1232;
+
+enum ForwardHandle {
+    Plain(NameServerPool<TokioConnectionProvider>),
+    Validating(Upstreams, Validator),
+}
+
+/// Builds a recursive query. With `dnssec` the upstream is asked for DNSSEC records and to leave
+/// validation to us.
+fn upstream_request(query: Query, mut edns: Edns, dnssec: bool) -> DnsRequest {
+    edns.set_max_payload(MAX_PAYLOAD);
+    edns.set_dnssec_ok(dnssec);
+    let mut message = Message::new();
+    message
+        .add_query(query)
+        .set_recursion_desired(true)
+        .set_checking_disabled(dnssec)
+        .set_edns(edns);
+    let mut options = DnsRequestOptions::default();
+    options.use_edns = true;
+    DnsRequest::new(message, options)
+}
+
+/// Sends requests to the upstream name servers in order, moving on to the next one when a
+/// server fails or truncates its answer.
+///
+/// Unlike `NameServerPool`, responses are returned whatever their response code, so negative
+/// answers keep the NSEC and NSEC3 records that prove them.
+pub struct Upstreams {
+    name_servers: NameServerConfigGroup,
+    options: ResolverOpts,
+    provider: TokioConnectionProvider,
+    connections: Mutex<Vec<Option<GenericConnection>>>,
+}
+
+impl Upstreams {
+    pub fn new(name_servers: NameServerConfigGroup) -> Self {
+        let connections = Mutex::new(vec![None; name_servers.len()]);
+        Self {
+            name_servers,
+            options: ResolverOpts::default(),
+            provider: TokioConnectionProvider::default(),
+            connections,
+        }
+    }
+
+    async fn connection(&self, i: usize) -> Result<GenericConnection> {
+        let cached = self.connections.lock().unwrap()[i].clone();
+        if let Some(connection) = cached {
+            return Ok(connection);
+        }
+        let connection = self
+            .provider
+            .new_connection(&self.name_servers[i], &self.options)
+            .await?;
+        self.connections.lock().unwrap()[i] = Some(connection.clone());
+        Ok(connection)
+    }
+
+    async fn send(&self, request: DnsRequest) -> Result<DnsResponse> {
+        let mut last_error = anyhow!("No upstream name servers");
+        for (i, config) in self.name_servers.iter().enumerate() {
+            let connection = match self.connection(i).await {
+                Ok(connection) => connection,
+                Err(err) => {
+                    last_error = err;
+                    continue;
+                }
+            };
+            match connection.send(request.clone()).next().await {
+                Some(Ok(response)) if response.truncated() => {
+                    debug!("Truncated response from {}", config.socket_addr);
+                    last_error = anyhow!("Truncated response from {}", config.socket_addr);
+                }
+                Some(Ok(response)) => return Ok(response),
+                Some(Err(err)) => {
+                    // Reconnect next time, the connection may be broken.
+                    self.connections.lock().unwrap()[i] = None;
+                    last_error = err.into();
+                }
+                None => last_error = anyhow!("No response from {}", config.socket_addr),
+            }
+        }
+        Err(last_error)
+    }
+
+    /// Looks up records with their signatures for validation.
+    pub async fn query(&self, name: &Name, rtype: RecordType) -> Result<DnsResponse> {
+        let query = Query::query(name.clone(), rtype);
+        let response = self
+            .send(upstream_request(query, Edns::new(), true))
+            .await?;
+        match response.response_code() {
+            ResponseCode::NoError | ResponseCode::NXDomain => Ok(response),
+            code => Err(anyhow!(
+                "Looking up {} {} failed with {}",
+                name,
+                rtype,
+                code
+            )),
+        }
+    }
+}
+
+/// Decides which subnet, if any, is sent upstream on behalf of a client.
+#[derive(Debug, Clone)]
+pub struct ClientSubnetPolicy {
+    ipv4_prefix_length: u8,
+    ipv6_prefix_length: u8,
+    overrides: Vec<(IpNet, IpNet)>,
+}
+
+impl ClientSubnetPolicy {
+    pub fn from_config(cfg: &ClientSubnetConfig) -> Result<Self> {
+        let overrides = cfg
+            .overrides
+            .iter()
+            .map(|o| Ok((parse_cidr(&o.clients)?, parse_cidr(&o.subnet)?.trunc())))
+            .collect::<Result<Vec<(IpNet, IpNet)>>>()?;
+        Ok(Self {
+            ipv4_prefix_length: cfg.ipv4_prefix_length,
+            ipv6_prefix_length: cfg.ipv6_prefix_length,
+            overrides,
+        })
+    }
+
+    /// Returns the subnet to announce for a client.
+    ///
+    /// Overrides are checked first. Otherwise private and loopback clients are never announced,
+    /// since their addresses mean nothing to upstream servers.
+    pub fn subnet_for(&self, client: IpAddr) -> Option<IpNet> {
+        let client = canonical_ip(client);
+        if let Some((_, subnet)) = self
+            .overrides
+            .iter()
+            .find(|(clients, _)| clients.contains(&client))
+        {
+            return Some(*subnet);
+        }
+        let private = match client {
+            IpAddr::V4(v4) => {
+                v4.is_private() || v4.is_loopback() || v4.is_link_local() || v4.is_unspecified()
+            }
+            // Unique local (fc00::/7) and link local (fe80::/10) addresses.
+            IpAddr::V6(v6) => {
+                v6.is_loopback()
+                    || v6.is_unspecified()
+                    || (v6.segments()[0] & 0xfe00) == 0xfc00
+                    || (v6.segments()[0] & 0xffc0) == 0xfe80
+            }
+        };
+        if private {
+            return None;
+        }
+        Some(client_network(
+            client,
+            self.ipv4_prefix_length,
+            self.ipv6_prefix_length,
+        ))
+    }
+}
+
+/// Forwards queries upstream without the caching `ForwardAuthority`, for when the query has to
+/// be built per client.
+///
+/// With a trust anchor the answers are validated with DNSSEC. Secure answers set the AD bit on
+/// the response, answers from zones proven to be unsigned are passed through without it, and
+/// anything that fails validation, including negative answers that aren't proven, is answered
+/// with SERVFAIL. With a client subnet policy the client's network is sent upstream as an EDNS Client
+/// Subnet option.
+pub struct Forwarder {
+    handle: ForwardHandle,
+    client_subnet: Option<ClientSubnetPolicy>,
+}
+
+impl Forwarder {
+    pub fn new(
+        name_servers: NameServerConfigGroup,
+        trust_anchor: Option<TrustAnchor>,
+        client_subnet: Option<ClientSubnetPolicy>,
+    ) -> Self {
+        let handle = match trust_anchor {
+            Some(trust_anchor) => ForwardHandle::Validating(
+                Upstreams::new(name_servers),
+                Validator::new(trust_anchor),
+            ),
+            None => ForwardHandle::Plain(NameServerPool::from_config(
+                name_servers,
+                ResolverOpts::default(),
+                TokioConnectionProvider::default(),
+            )),
+        };
+        Self {
+            handle,
+            client_subnet,
+        }
+    }
+
+    pub fn is_validating(&self) -> bool {
+        matches!(self.handle, ForwardHandle::Validating(..))
+    }
+
+    fn build_request(&self, query: Query, client: Option<IpAddr>) -> DnsRequest {
+        let mut edns = Edns::new();
+        let subnet = self
+            .client_subnet
+            .as_ref()
+            .zip(client)
+            .and_then(|(policy, client)| policy.subnet_for(client));
+        if let Some(subnet) = subnet {
+            debug!("Sending client subnet {} for {}", subnet, query.name());
+            edns.options_mut()
+                .insert(EdnsOption::Subnet(ClientSubnet::new(
+                    subnet.network(),
+                    subnet.prefix_len(),
+                    0,
+                )));
+        }
+        upstream_request(query, edns, self.is_validating())
+    }
+
+    pub async fn lookup(
+        &self,
+        name: &LowerName,
+        rtype: RecordType,
+        client: Option<IpAddr>,
+    ) -> Result<Lookup, LookupError> {
+        let query = Query::query(Name::from(name), rtype);
+        let request = self.build_request(query.clone(), client);
+        let response = match &self.handle {
+            ForwardHandle::Plain(pool) => match pool.send(request).next().await {
+                Some(Ok(response)) => response,
+                Some(Err(err)) => {
+                    if let ResolveErrorKind::NoRecordsFound { response_code, .. } = err.kind() {
+                        return Err(LookupError::ResponseCode(*response_code));
+                    }
+                    warn!("Forwarding failed for {} {}: {}", name, rtype, err);
+                    override_response(|o| o.response_code = Some(ResponseCode::ServFail));
+                    return Err(LookupError::from(err));
+                }
+                None => {
+                    override_response(|o| o.response_code = Some(ResponseCode::ServFail));
+                    return Err(LookupError::ResponseCode(ResponseCode::ServFail));
+                }
+            },
+            ForwardHandle::Validating(upstreams, validator) => {
+                let response = match upstreams.send(request).await {
+                    Ok(response) => response,
+                    Err(err) => {
+                        warn!("Forwarding failed for {} {}: {}", name, rtype, err);
+                        override_response(|o| o.response_code = Some(ResponseCode::ServFail));
+                        return Err(LookupError::ResponseCode(ResponseCode::ServFail));
+                    }
+                };
+                match response.response_code() {
+                    ResponseCode::NoError | ResponseCode::NXDomain => {}
+                    code => return Err(LookupError::ResponseCode(code)),
+                }
+                match validator.validate(upstreams, &query, &response).await {
+                    Ok(Security::Secure) => override_response(|o| o.authentic_data = true),
+                    Ok(Security::Insecure) => debug!("{} {} is insecure", name, rtype),
+                    Err(err) => {
+                        warn!("DNSSEC validation failed for {} {}: {}", name, rtype, err);
+                        override_response(|o| o.response_code = Some(ResponseCode::ServFail));
+                        return Err(LookupError::ResponseCode(ResponseCode::ServFail));
+                    }
+                }
+                response
+            }
+        };
+
+        if response.response_code() == ResponseCode::NXDomain {
+            return Err(LookupError::ResponseCode(ResponseCode::NXDomain));
+        }
+        let records: Vec<Record> = response
+            .answers()
+            .iter()
+            .filter(|record| record.record_type() != RecordType::RRSIG)
+            .cloned()
+            .collect();
+        if records.is_empty() {
+            return Err(LookupError::ResponseCode(response.response_code()));
+        }
+        Ok(Lookup::new_with_max_ttl(query, Arc::from(records)))
+    }
+}
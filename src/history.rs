@@ -0,0 +1,40 @@
+use crate::record_store::RecordStore;
+use crate::ServerConfig;
+use anyhow::Result;
+use std::sync::Arc;
+use std::time::Duration;
+use time::OffsetDateTime;
+use tokio::sync::watch;
+use tracing::{debug, error};
+
+/// How often expired leases are recorded and old changes pruned.
+static MAINTENANCE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Keeps the history complete and within its retention.
+///
+/// Expiries are recorded when they're noticed, so they're timestamped up to a minute late.
+pub async fn maintain_history(
+    repo: Arc<dyn RecordStore>,
+    configs: watch::Receiver<Arc<ServerConfig>>,
+) -> Result<()> {
+    let mut interval = tokio::time::interval(MAINTENANCE_INTERVAL);
+    loop {
+        interval.tick().await;
+        match repo.record_expiries().await {
+            Ok(0) => {}
+            Ok(expired) => debug!("Recorded {} expired RRsets in the history", expired),
+            Err(err) => error!("Failed to record expired RRsets: {}", err),
+        }
+        let retention_days = configs.borrow().history.retention_days;
+        if retention_days == 0 {
+            continue;
+        }
+        let cutoff =
+            OffsetDateTime::now_utc() - Duration::from_secs(retention_days as u64 * 24 * 60 * 60);
+        match repo.prune_history(cutoff).await {
+            Ok(0) => {}
+            Ok(pruned) => debug!("Pruned {} changes from the history", pruned),
+            Err(err) => error!("Failed to prune the history: {}", err),
+        }
+    }
+}
@@ -1,11 +1,33 @@
+pub mod acl;
+pub mod address_filter;
+pub mod address_source;
+pub mod backup;
 pub mod client;
 mod config;
+pub mod config_watcher;
 pub mod dns_server;
+pub mod dnssec;
+pub mod forwarder;
+pub mod history;
+pub mod memory_store;
+#[cfg(target_os = "linux")]
+pub mod netlink;
+pub mod output;
+pub mod postgres_store;
 pub mod proto;
+pub mod rate_limit;
 pub mod record_repository;
+pub mod record_store;
+pub mod request_handler;
 pub mod rpc_server;
+pub mod sortlist;
 pub mod split_authority;
 pub mod sqlite_authority;
+pub mod stun;
+pub mod update_state;
 pub mod util;
+pub mod validation;
+pub mod zone_file;
+pub mod zone_includes;
 
 pub use config::*;
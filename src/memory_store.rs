@@ -0,0 +1,569 @@
+use crate::proto::error_details::FieldViolation;
+use crate::proto::record_operation::Operation;
+use crate::proto::{
+    BatchReply, Conflict, FindUniqueRecordRequest, HealthFilter, HistoryAction, HistoryEntry,
+    HistoryRequest, LeaseRenewal, RecordOperation, RecordOrder, RecordReply, RecordsPage,
+    RecordsQueryRequest, UpsertRecordRequest,
+};
+use crate::record_store::{
+    check_owner, current_actor, decode_page_token, encode_page_token, is_healthy, operation_key,
+    upsert_key, validate_batch_shape, validate_rrset_shape, OwnerConflict, RecordError,
+    RecordStore, DEFAULT_HISTORY_LIMIT, DEFAULT_LEASE, MAX_CONFLICTS,
+};
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Mutex;
+use time::OffsetDateTime;
+use tracing::warn;
+
+type Result<T, E = RecordError> = std::result::Result<T, E>;
+
+/// Name, type and view of an RRset.
+type RRsetKey = (String, String, String);
+
+#[derive(Debug, Clone)]
+struct RRset {
+    values: Vec<String>,
+    ttl: u32,
+    static_: bool,
+    created_at: OffsetDateTime,
+    updated_at: OffsetDateTime,
+    revision: u64,
+    lease: u32,
+    owner: String,
+}
+
+impl RRset {
+    fn healthy(&self) -> bool {
+        is_healthy(self.static_, self.updated_at, self.lease)
+    }
+
+    fn reply(&self, (name, r#type, view): &RRsetKey) -> RecordReply {
+        RecordReply {
+            name: name.clone(),
+            r#type: r#type.clone(),
+            data: self.values.first().cloned().unwrap_or_default(),
+            ttl: self.ttl,
+            created_at: self.created_at.unix_timestamp(),
+            updated_at: self.updated_at.unix_timestamp(),
+            healthy: self.healthy(),
+            view: view.clone(),
+            r#static: self.static_,
+            values: self.values.clone(),
+            revision: self.revision,
+            lease: self.lease,
+            owner: self.owner.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    rrsets: BTreeMap<RRsetKey, RRset>,
+    conflicts: VecDeque<Conflict>,
+    /// Oldest first, with the exact time of each change.
+    history: Vec<(HistoryEntry, OffsetDateTime)>,
+}
+
+fn key(request: &FindUniqueRecordRequest) -> RRsetKey {
+    (
+        request.name.clone(),
+        request.r#type.clone(),
+        request.view.clone(),
+    )
+}
+
+/// Timestamps in sort keys are fixed width, so they compare like the other columns.
+fn timestamp_key(timestamp: OffsetDateTime) -> String {
+    format!("{:024}", timestamp.unix_timestamp_nanos())
+}
+
+/// Matches a name against a glob like SQLite's `GLOB`, with `*`, `?` and `[...]` classes.
+fn glob_matches(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len()).any(|i| glob_matches(&pattern[1..], &name[i..])),
+        Some('?') => !name.is_empty() && glob_matches(&pattern[1..], &name[1..]),
+        Some('[') => {
+            let Some(c) = name.first() else {
+                return false;
+            };
+            let Some(end) = pattern
+                .iter()
+                .skip(2)
+                .position(|p| *p == ']')
+                .map(|i| i + 2)
+            else {
+                return pattern[0] == *c && glob_matches(&pattern[1..], &name[1..]);
+            };
+            let (negated, class) = match pattern[1] {
+                '^' => (true, &pattern[2..end]),
+                _ => (false, &pattern[1..end]),
+            };
+            let mut matched = false;
+            let mut i = 0;
+            while i < class.len() {
+                if i + 2 < class.len() && class[i + 1] == '-' {
+                    matched |= class[i] <= *c && *c <= class[i + 2];
+                    i += 3;
+                } else {
+                    matched |= class[i] == *c;
+                    i += 1;
+                }
+            }
+            matched != negated && glob_matches(&pattern[end + 1..], &name[1..])
+        }
+        Some(p) => name.first() == Some(p) && glob_matches(&pattern[1..], &name[1..]),
+    }
+}
+
+fn in_zone(name: &str, zone: &str) -> bool {
+    name == zone || name.ends_with(&format!(".{}", zone))
+}
+
+impl State {
+    fn owner_conflict(&self, request: &FindUniqueRecordRequest) -> Option<OwnerConflict> {
+        let rrset = self.rrsets.get(&key(request))?;
+        check_owner(request, rrset.owner.clone(), rrset.healthy())
+    }
+
+    /// Remembers a rejected change, forgetting the oldest ones beyond [`MAX_CONFLICTS`].
+    fn record_conflict(&mut self, conflict: &OwnerConflict, now: OffsetDateTime) {
+        warn!("Rejected change: {}", conflict);
+        self.conflicts.push_front(Conflict {
+            name: conflict.name.clone(),
+            r#type: conflict.r#type.clone(),
+            view: conflict.view.clone(),
+            owner: conflict.owner.clone(),
+            challenger: conflict.challenger.clone(),
+            created_at: now.unix_timestamp(),
+        });
+        self.conflicts.truncate(MAX_CONFLICTS);
+    }
+
+    fn record_history(&mut self, mut entry: HistoryEntry, now: OffsetDateTime) {
+        entry.created_at = now.unix_timestamp();
+        self.history.push((entry, now));
+    }
+
+    /// Replaces an RRset, bumping its revision if anything changed.
+    fn upsert_rrset(&mut self, request: UpsertRecordRequest, now: OffsetDateTime, actor: &str) {
+        let key = (request.name, request.r#type, request.view);
+        let values = if request.values.is_empty() {
+            vec![request.value]
+        } else {
+            request.values
+        };
+        let lease = if request.lease == 0 {
+            DEFAULT_LEASE
+        } else {
+            request.lease
+        };
+        let existing = self.rrsets.get(&key);
+        // Refreshing an unchanged RRset keeps its revision, so clients can keep it alive.
+        let changed = match existing {
+            Some(rrset) => {
+                rrset.values.len() != values.len()
+                    || rrset.values.iter().any(|value| !values.contains(value))
+                    || rrset.ttl != request.ttl
+                    || rrset.static_ != request.r#static
+            }
+            None => true,
+        };
+        let revision = existing.map_or(1, |rrset| {
+            if changed {
+                rrset.revision + 1
+            } else {
+                rrset.revision
+            }
+        });
+        let entry = match existing {
+            Some(rrset) if !changed && rrset.owner == request.owner => None,
+            _ => Some(HistoryEntry {
+                name: key.0.clone(),
+                r#type: key.1.clone(),
+                view: key.2.clone(),
+                action: HistoryAction::Upsert as i32,
+                old_values: existing.map_or(vec![], |rrset| rrset.values.clone()),
+                new_values: values.clone(),
+                ttl: request.ttl,
+                revision,
+                owner: request.owner.clone(),
+                actor: actor.to_string(),
+                ..Default::default()
+            }),
+        };
+        let created_at = existing.map_or(now, |rrset| rrset.created_at);
+        self.rrsets.insert(
+            key,
+            RRset {
+                values,
+                ttl: request.ttl,
+                static_: request.r#static,
+                created_at,
+                updated_at: now,
+                revision,
+                lease,
+                owner: request.owner,
+            },
+        );
+        if let Some(entry) = entry {
+            self.record_history(entry, now);
+        }
+    }
+
+    fn delete_rrset(
+        &mut self,
+        request: &FindUniqueRecordRequest,
+        now: OffsetDateTime,
+        actor: &str,
+    ) {
+        if let Some(rrset) = self.rrsets.remove(&key(request)) {
+            let entry = HistoryEntry {
+                name: request.name.clone(),
+                r#type: request.r#type.clone(),
+                view: request.view.clone(),
+                action: HistoryAction::Delete as i32,
+                old_values: rrset.values,
+                ttl: rrset.ttl,
+                revision: rrset.revision,
+                owner: rrset.owner,
+                actor: actor.to_string(),
+                ..Default::default()
+            };
+            self.record_history(entry, now);
+        }
+    }
+
+    /// Finds an RRset, or returns one without values and revision 0 if it doesn't exist.
+    fn find_or_missing(&self, request: FindUniqueRecordRequest) -> RecordReply {
+        let key = key(&request);
+        match self.rrsets.get(&key) {
+            Some(rrset) => rrset.reply(&key),
+            None => RecordReply {
+                name: request.name,
+                r#type: request.r#type,
+                view: request.view,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// A store that keeps the records in memory, for tests and ephemeral deployments. Everything
+/// is lost when the server stops.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    state: Mutex<State>,
+}
+
+impl MemoryStore {
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().expect("memory store lock poisoned")
+    }
+}
+
+#[async_trait::async_trait]
+impl RecordStore for MemoryStore {
+    async fn find_unique(&self, request: FindUniqueRecordRequest) -> Result<RecordReply> {
+        let key = key(&request);
+        self.state()
+            .rrsets
+            .get(&key)
+            .map(|rrset| rrset.reply(&key))
+            .ok_or(RecordError::NotFound)
+    }
+
+    async fn find_for_view(
+        &self,
+        name: String,
+        r#type: String,
+        view: String,
+    ) -> Result<RecordReply> {
+        let state = self.state();
+        let tagged = (name.clone(), r#type.clone(), view);
+        let untagged = (name, r#type, String::new());
+        let key = if state.rrsets.contains_key(&tagged) {
+            tagged
+        } else {
+            untagged
+        };
+        state
+            .rrsets
+            .get(&key)
+            .filter(|rrset| rrset.healthy())
+            .map(|rrset| rrset.reply(&key))
+            .ok_or(RecordError::NotFound)
+    }
+
+    async fn has_name(&self, name: String, view: String) -> Result<bool> {
+        let suffix = format!(".{}", name);
+        Ok(self
+            .state()
+            .rrsets
+            .iter()
+            .any(|((rrset_name, _, rrset_view), rrset)| {
+                (*rrset_name == name || rrset_name.ends_with(&suffix))
+                    && (rrset_view.is_empty() || *rrset_view == view)
+                    && rrset.healthy()
+            }))
+    }
+
+    async fn upsert(&self, request: UpsertRecordRequest) -> Result<RecordReply> {
+        let violations = validate_rrset_shape(&request, "");
+        if !violations.is_empty() {
+            return Err(RecordError::Invalid(violations));
+        }
+        let find = upsert_key(&request);
+        let now = OffsetDateTime::now_utc();
+        let mut state = self.state();
+        if let Some(conflict) = state.owner_conflict(&find) {
+            state.record_conflict(&conflict, now);
+            return Err(conflict.into());
+        }
+        state.upsert_rrset(request, now, &current_actor());
+        Ok(state.find_or_missing(find))
+    }
+
+    async fn batch(&self, operations: Vec<RecordOperation>) -> Result<BatchReply> {
+        let violations = validate_batch_shape(&operations);
+        if !violations.is_empty() {
+            return Err(RecordError::Invalid(violations));
+        }
+        let now = OffsetDateTime::now_utc();
+        let actor = current_actor();
+        let mut state = self.state();
+
+        let mut owner_conflicts = vec![];
+        for key in operations.iter().filter_map(operation_key) {
+            if let Some(conflict) = state.owner_conflict(&key) {
+                state.record_conflict(&conflict, now);
+                owner_conflicts.push(conflict);
+            }
+        }
+        if let Some(conflict) = owner_conflicts.into_iter().next() {
+            return Err(conflict.into());
+        }
+
+        let conflicts: Vec<FindUniqueRecordRequest> = operations
+            .iter()
+            .filter(|operation| operation.if_revision != 0)
+            .filter_map(|operation| {
+                operation_key(operation).map(|key| (key, operation.if_revision))
+            })
+            .filter(|(request, if_revision)| {
+                state.rrsets.get(&key(request)).map(|rrset| rrset.revision) != Some(*if_revision)
+            })
+            .map(|(request, _)| request)
+            .collect();
+        if !conflicts.is_empty() {
+            return Ok(BatchReply {
+                applied: false,
+                conflicts: conflicts
+                    .into_iter()
+                    .map(|request| state.find_or_missing(request))
+                    .collect(),
+                ..Default::default()
+            });
+        }
+
+        let mut upserted = vec![];
+        for operation in operations {
+            match operation.operation {
+                Some(Operation::Upsert(request)) => {
+                    upserted.push(upsert_key(&request));
+                    state.upsert_rrset(request, now, &actor);
+                }
+                Some(Operation::Delete(request)) => state.delete_rrset(&request, now, &actor),
+                None => {}
+            }
+        }
+        Ok(BatchReply {
+            applied: true,
+            records: upserted
+                .into_iter()
+                .map(|request| state.find_or_missing(request))
+                .collect(),
+            ..Default::default()
+        })
+    }
+
+    async fn keep_alive(&self, renewals: Vec<LeaseRenewal>) -> Result<Vec<Option<RecordReply>>> {
+        let now = OffsetDateTime::now_utc();
+        let mut state = self.state();
+        let results = renewals
+            .into_iter()
+            .map(|renewal| {
+                let key = (renewal.name, renewal.r#type, renewal.view);
+                let rrset = state.rrsets.get_mut(&key).filter(|rrset| {
+                    rrset.revision == renewal.revision && rrset.owner == renewal.owner
+                })?;
+                rrset.updated_at = now;
+                Some(rrset.reply(&key))
+            })
+            .collect();
+        Ok(results)
+    }
+
+    async fn query(&self, query: RecordsQueryRequest) -> Result<RecordsPage> {
+        let since = match query.updated_since {
+            0 => None,
+            since => Some(OffsetDateTime::from_unix_timestamp(since).map_err(|_| {
+                RecordError::Invalid(vec![FieldViolation::new(
+                    "updated_since",
+                    "is out of range",
+                )])
+            })?),
+        };
+        let sort_key = |(name, r#type, view): &RRsetKey, rrset: &RRset| -> Vec<String> {
+            let mut sort_key = match query.order_by() {
+                RecordOrder::Name => vec![],
+                RecordOrder::UpdatedAt => vec![timestamp_key(rrset.updated_at)],
+                RecordOrder::CreatedAt => vec![timestamp_key(rrset.created_at)],
+            };
+            sort_key.extend([name.clone(), r#type.clone(), view.clone()]);
+            sort_key
+        };
+        let columns = match query.order_by() {
+            RecordOrder::Name => 3,
+            RecordOrder::UpdatedAt | RecordOrder::CreatedAt => 4,
+        };
+        let after = match query.page_token.as_str() {
+            "" => None,
+            token => Some(decode_page_token(token, columns)?),
+        };
+        let pattern: Vec<char> = query.name.chars().collect();
+        let view = if query.view == "*" { "" } else { &query.view };
+
+        let state = self.state();
+        let mut rrsets: Vec<(Vec<String>, RecordReply)> = state
+            .rrsets
+            .iter()
+            .filter(|((name, r#type, rrset_view), rrset)| {
+                (query.zone.is_empty() || in_zone(name, &query.zone))
+                    && (pattern.is_empty()
+                        || glob_matches(&pattern, &name.chars().collect::<Vec<char>>()))
+                    && (query.r#type.is_empty() || *r#type == query.r#type)
+                    && (query.view.is_empty() || rrset_view == view)
+                    && match query.health() {
+                        HealthFilter::Any => true,
+                        HealthFilter::Healthy => rrset.healthy(),
+                        HealthFilter::Unhealthy => !rrset.healthy(),
+                    }
+                    && since.filter(|since| rrset.updated_at < *since).is_none()
+                    && (query.owner.is_empty() || rrset.owner == query.owner)
+            })
+            .map(|(key, rrset)| (sort_key(key, rrset), rrset.reply(key)))
+            .filter(|(sort_key, _)| match &after {
+                Some(after) if query.descending => sort_key < after,
+                Some(after) => sort_key > after,
+                None => true,
+            })
+            .collect();
+        rrsets.sort_by(|(a, _), (b, _)| a.cmp(b));
+        if query.descending {
+            rrsets.reverse();
+        }
+
+        let page_size = query.page_size as usize;
+        let mut next_page_token = String::new();
+        if page_size > 0 && rrsets.len() > page_size {
+            rrsets.truncate(page_size);
+            next_page_token = encode_page_token(&rrsets[page_size - 1].0);
+        }
+        Ok(RecordsPage {
+            records: rrsets.into_iter().map(|(_, record)| record).collect(),
+            next_page_token,
+        })
+    }
+
+    async fn conflicts(&self) -> Result<Vec<Conflict>> {
+        Ok(self.state().conflicts.iter().cloned().collect())
+    }
+
+    async fn history(&self, request: HistoryRequest) -> Result<Vec<HistoryEntry>> {
+        let since = match request.since {
+            0 => None,
+            since => Some(OffsetDateTime::from_unix_timestamp(since).map_err(|_| {
+                RecordError::Invalid(vec![FieldViolation::new("since", "is out of range")])
+            })?),
+        };
+        let limit = if request.limit == 0 {
+            DEFAULT_HISTORY_LIMIT
+        } else {
+            request.limit
+        };
+        let matches = |filter: &str, value: &str| filter.is_empty() || filter == value;
+        let entries = self
+            .state()
+            .history
+            .iter()
+            .rev()
+            .filter(|(entry, created_at)| {
+                (request.zone.is_empty() || in_zone(&entry.name, &request.zone))
+                    && matches(&request.name, &entry.name)
+                    && matches(&request.r#type, &entry.r#type)
+                    && matches(&request.view, &entry.view)
+                    && matches(&request.actor, &entry.actor)
+                    && since.filter(|since| *created_at < *since).is_none()
+            })
+            .take(limit as usize)
+            .map(|(entry, _)| entry.clone())
+            .collect();
+        Ok(entries)
+    }
+
+    async fn record_expiries(&self) -> Result<usize> {
+        let now = OffsetDateTime::now_utc();
+        let mut state = self.state();
+        let expired: Vec<HistoryEntry> = state
+            .rrsets
+            .iter()
+            .filter(|(_, rrset)| !rrset.healthy())
+            .filter(|((name, r#type, view), rrset)| {
+                !state.history.iter().any(|(entry, created_at)| {
+                    entry.action() == HistoryAction::Expire
+                        && entry.name == *name
+                        && entry.r#type == *r#type
+                        && entry.view == *view
+                        && *created_at >= rrset.updated_at
+                })
+            })
+            .map(|((name, r#type, view), rrset)| HistoryEntry {
+                name: name.clone(),
+                r#type: r#type.clone(),
+                view: view.clone(),
+                action: HistoryAction::Expire as i32,
+                old_values: rrset.values.clone(),
+                ttl: rrset.ttl,
+                revision: rrset.revision,
+                owner: rrset.owner.clone(),
+                ..Default::default()
+            })
+            .collect();
+        let count = expired.len();
+        for entry in expired {
+            state.record_history(entry, now);
+        }
+        Ok(count)
+    }
+
+    async fn prune_history(&self, cutoff: OffsetDateTime) -> Result<usize> {
+        let mut state = self.state();
+        let before = state.history.len();
+        state
+            .history
+            .retain(|(_, created_at)| *created_at >= cutoff);
+        Ok(before - state.history.len())
+    }
+
+    async fn delete(&self, request: FindUniqueRecordRequest) -> Result<()> {
+        let now = OffsetDateTime::now_utc();
+        let mut state = self.state();
+        if let Some(conflict) = state.owner_conflict(&request) {
+            state.record_conflict(&conflict, now);
+            return Err(conflict.into());
+        }
+        state.delete_rrset(&request, now, &current_actor());
+        Ok(())
+    }
+}
@@ -0,0 +1,143 @@
+use std::ffi::CStr;
+use std::io;
+use std::mem;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use tokio::io::unix::AsyncFd;
+use tracing::{debug, warn};
+
+/// Size of `struct nlmsghdr`.
+static NLMSG_HDRLEN: usize = 16;
+
+/// Interface index reported when events were lost and every interface may have changed.
+pub static ALL_INTERFACES: u32 = 0;
+
+/// Subscribes to rtnetlink address and link events.
+pub struct AddressWatcher {
+    fd: AsyncFd<OwnedFd>,
+    buf: Vec<u8>,
+}
+
+impl AddressWatcher {
+    pub fn new() -> io::Result<Self> {
+        // SAFETY: Plain socket syscalls, the returned descriptor is owned by `OwnedFd`.
+        let fd = unsafe {
+            let fd = libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_RAW | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+                libc::NETLINK_ROUTE,
+            );
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let fd = OwnedFd::from_raw_fd(fd);
+
+            let mut addr: libc::sockaddr_nl = mem::zeroed();
+            addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+            addr.nl_groups =
+                (libc::RTMGRP_LINK | libc::RTMGRP_IPV4_IFADDR | libc::RTMGRP_IPV6_IFADDR) as u32;
+            if libc::bind(
+                fd.as_raw_fd(),
+                &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            ) < 0
+            {
+                return Err(io::Error::last_os_error());
+            }
+            fd
+        };
+        Ok(Self {
+            fd: AsyncFd::new(fd)?,
+            buf: vec![0; 64 * 1024],
+        })
+    }
+
+    /// Waits for the next batch of events and returns the indexes of the interfaces that changed.
+    ///
+    /// When the kernel drops events because they weren't read fast enough, this returns
+    /// [`ALL_INTERFACES`].
+    pub async fn changed(&mut self) -> io::Result<Vec<u32>> {
+        loop {
+            let mut guard = self.fd.readable().await?;
+            let buf = &mut self.buf;
+            // SAFETY: `buf` is valid for writes of its length.
+            let result = guard.try_io(|fd| {
+                let len = unsafe {
+                    libc::recv(
+                        fd.as_raw_fd(),
+                        buf.as_mut_ptr() as *mut libc::c_void,
+                        buf.len(),
+                        0,
+                    )
+                };
+                if len < 0 {
+                    Err(io::Error::last_os_error())
+                } else {
+                    Ok(len as usize)
+                }
+            });
+            match result {
+                Ok(Ok(len)) => {
+                    let indexes = parse_messages(&self.buf[..len]);
+                    if !indexes.is_empty() {
+                        return Ok(indexes);
+                    }
+                }
+                Ok(Err(err)) if err.raw_os_error() == Some(libc::ENOBUFS) => {
+                    warn!("Netlink events were dropped, assuming every interface changed");
+                    return Ok(vec![ALL_INTERFACES]);
+                }
+                Ok(Err(err)) => return Err(err),
+                Err(_would_block) => continue,
+            }
+        }
+    }
+}
+
+/// Parses the interface indexes out of a buffer of rtnetlink messages.
+///
+/// Only address and link messages are considered. Both `struct ifaddrmsg` and `struct ifinfomsg`
+/// keep the interface index at offset 4 of the payload.
+pub fn parse_messages(buf: &[u8]) -> Vec<u32> {
+    let mut indexes = vec![];
+    let mut offset = 0;
+    while offset + NLMSG_HDRLEN <= buf.len() {
+        let header = &buf[offset..];
+        let len = u32::from_ne_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let r#type = u16::from_ne_bytes([header[4], header[5]]);
+        if len < NLMSG_HDRLEN || offset + len > buf.len() {
+            break;
+        }
+        match r#type {
+            libc::RTM_NEWADDR | libc::RTM_DELADDR | libc::RTM_NEWLINK | libc::RTM_DELLINK
+                if len >= NLMSG_HDRLEN + 8 =>
+            {
+                let payload = &header[NLMSG_HDRLEN..];
+                let index = u32::from_ne_bytes([payload[4], payload[5], payload[6], payload[7]]);
+                debug!("Netlink message {} for interface {}", r#type, index);
+                if !indexes.contains(&index) {
+                    indexes.push(index);
+                }
+            }
+            _ => {}
+        }
+        // Messages are aligned to 4 bytes.
+        offset += (len + 3) & !3;
+    }
+    indexes
+}
+
+/// Looks up the name of an interface, which fails for interfaces that have been removed.
+pub fn interface_name(index: u32) -> Option<String> {
+    if index == ALL_INTERFACES {
+        return None;
+    }
+    let mut name = [0 as libc::c_char; libc::IF_NAMESIZE];
+    // SAFETY: `name` is IF_NAMESIZE bytes, as `if_indextoname` requires.
+    let result = unsafe { libc::if_indextoname(index, name.as_mut_ptr()) };
+    if result.is_null() {
+        return None;
+    }
+    // SAFETY: `if_indextoname` wrote a nul-terminated string on success.
+    let name = unsafe { CStr::from_ptr(name.as_ptr()) };
+    Some(name.to_string_lossy().into_owned())
+}
@@ -0,0 +1,57 @@
+use crate::OutputFormat;
+use anyhow::Result;
+use serde::Serialize;
+use std::fmt;
+
+/// Plain text table with left-aligned columns, for humans reading `swandns-ctl` output.
+#[derive(Debug, Default)]
+pub struct Table {
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new(headers: &[&str]) -> Self {
+        Self {
+            headers: headers.iter().map(|header| header.to_string()).collect(),
+            rows: vec![],
+        }
+    }
+
+    pub fn row(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+}
+
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut widths: Vec<usize> = self.headers.iter().map(|header| header.len()).collect();
+        for row in self.rows.iter() {
+            for (width, cell) in widths.iter_mut().zip(row.iter()) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        for row in std::iter::once(&self.headers).chain(self.rows.iter()) {
+            let cells: Vec<String> = row
+                .iter()
+                .zip(widths.iter())
+                .map(|(cell, width)| format!("{:width$}", cell, width = width))
+                .collect();
+            writeln!(f, "{}", cells.join("  ").trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+/// Renders `value` as JSON or YAML, or as the text built by `table`, usually a [`Table`].
+pub fn render<T: Serialize + ?Sized, D: fmt::Display>(
+    format: OutputFormat,
+    value: &T,
+    table: impl FnOnce(&T) -> D,
+) -> Result<String> {
+    Ok(match format {
+        OutputFormat::Table => table(value).to_string(),
+        OutputFormat::Json => serde_json::to_string_pretty(value)? + "\n",
+        OutputFormat::Yaml => serde_yaml::to_string(value)?,
+    })
+}
@@ -0,0 +1,1157 @@
+use crate::proto::error_details::FieldViolation;
+use crate::proto::record_operation::Operation;
+use crate::proto::{
+    BatchReply, Conflict, FindUniqueRecordRequest, HealthFilter, HistoryAction, HistoryEntry,
+    HistoryRequest, LeaseRenewal, RecordOperation, RecordOrder, RecordReply, RecordsPage,
+    RecordsQueryRequest, UpsertRecordRequest,
+};
+use crate::record_store::{
+    check_owner, current_actor, decode_page_token, encode_page_token, is_healthy, operation_key,
+    upsert_key, validate_batch_shape, validate_rrset_shape, OwnerConflict, RecordError,
+    RecordStore, DEFAULT_HISTORY_LIMIT, DEFAULT_LEASE, MAX_CONFLICTS,
+};
+use crate::util::SchemaStatus;
+use anyhow::{bail, Context};
+use time::OffsetDateTime;
+use tokio::sync::{Mutex, MutexGuard};
+use tokio_postgres::error::SqlState;
+use tokio_postgres::types::ToSql;
+use tokio_postgres::{Client, GenericClient, NoTls, Row, Transaction};
+use tracing::{debug, error, info, warn};
+
+type Result<T, E = RecordError> = std::result::Result<T, E>;
+
+impl From<tokio_postgres::Error> for RecordError {
+    fn from(err: tokio_postgres::Error) -> Self {
+        match err.code() {
+            Some(code)
+                if *code == SqlState::T_R_SERIALIZATION_FAILURE
+                    || *code == SqlState::T_R_DEADLOCK_DETECTED =>
+            {
+                RecordError::Conflict(err.into())
+            }
+            _ => RecordError::Storage(err.into()),
+        }
+    }
+}
+
+/// Schema changes, applied in order. Each one is applied once and recorded in
+/// `schema_migrations`, so released ones must never change.
+static MIGRATIONS: &[&str] = &[r#"
+CREATE TABLE records(
+    name TEXT COLLATE "C" NOT NULL,
+    type TEXT COLLATE "C" NOT NULL,
+    view TEXT COLLATE "C" NOT NULL DEFAULT '',
+    data TEXT[] NOT NULL,
+    ttl BIGINT NOT NULL,
+    static BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL,
+    revision BIGINT NOT NULL DEFAULT 1,
+    lease BIGINT NOT NULL,
+    owner TEXT NOT NULL DEFAULT '',
+    PRIMARY KEY (name, type, view)
+);
+CREATE INDEX records_updated_at ON records (updated_at);
+CREATE INDEX records_type ON records (type);
+CREATE INDEX records_owner ON records (owner);
+
+CREATE TABLE conflicts(
+    id BIGSERIAL PRIMARY KEY,
+    name TEXT NOT NULL,
+    type TEXT NOT NULL,
+    view TEXT NOT NULL DEFAULT '',
+    owner TEXT NOT NULL,
+    challenger TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE history(
+    id BIGSERIAL PRIMARY KEY,
+    name TEXT NOT NULL,
+    type TEXT NOT NULL,
+    view TEXT NOT NULL DEFAULT '',
+    action TEXT NOT NULL,
+    old_values TEXT[] NOT NULL DEFAULT '{}',
+    new_values TEXT[] NOT NULL DEFAULT '{}',
+    ttl BIGINT NOT NULL DEFAULT 0,
+    revision BIGINT NOT NULL DEFAULT 0,
+    owner TEXT NOT NULL DEFAULT '',
+    actor TEXT NOT NULL DEFAULT '',
+    created_at TIMESTAMPTZ NOT NULL
+);
+CREATE INDEX history_created_at ON history (created_at);
+CREATE INDEX history_name ON history (name, type, view);
+"#];
+
+/// Serializes migrations of servers sharing the database, "swandns" in ASCII.
+static MIGRATION_LOCK: i64 = 0x0073_7761_6e64_6e73;
+
+async fn create_migrations_table(client: &impl GenericClient) -> anyhow::Result<()> {
+    client
+        .batch_execute(
+            r#"
+CREATE TABLE IF NOT EXISTS schema_migrations(
+    version INTEGER PRIMARY KEY,
+    applied_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+)"#,
+        )
+        .await
+        .context("Couldn't create the schema_migrations table")?;
+    Ok(())
+}
+
+async fn applied_migrations(client: &impl GenericClient) -> anyhow::Result<usize> {
+    let row = client
+        .query_one(
+            "SELECT COALESCE(MAX(version), 0) FROM schema_migrations",
+            &[],
+        )
+        .await
+        .context("Couldn't read the database's schema version")?;
+    Ok(row.get::<_, i32>(0) as usize)
+}
+
+pub async fn schema_status(client: &Client) -> anyhow::Result<SchemaStatus> {
+    create_migrations_table(client).await?;
+    Ok(SchemaStatus {
+        current: applied_migrations(client).await?,
+        latest: MIGRATIONS.len(),
+    })
+}
+
+/// Migrates the database to the latest schema, refusing databases migrated by a newer version.
+pub async fn migrate_database(client: &mut Client) -> anyhow::Result<()> {
+    create_migrations_table(&*client).await?;
+    let tx = client.transaction().await?;
+    tx.execute("SELECT pg_advisory_xact_lock($1)", &[&MIGRATION_LOCK])
+        .await
+        .context("Couldn't lock the database for migrating it")?;
+    let status = SchemaStatus {
+        current: applied_migrations(&tx).await?,
+        latest: MIGRATIONS.len(),
+    };
+    if status.is_newer() {
+        bail!(
+            "The database is at {}, from a newer version of Swan DNS",
+            status
+        );
+    }
+    if status.is_current() {
+        debug!("Database is at the latest {}", status);
+        return Ok(());
+    }
+    info!(
+        "Migrating database from schema version {} to {}",
+        status.current, status.latest
+    );
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(status.current) {
+        let version = i as i32 + 1;
+        tx.batch_execute(migration)
+            .await
+            .with_context(|| format!("Couldn't migrate the database to version {}", version))?;
+        tx.execute(
+            "INSERT INTO schema_migrations (version) VALUES ($1)",
+            &[&version],
+        )
+        .await?;
+    }
+    tx.commit().await.context("Couldn't migrate the database")?;
+    Ok(())
+}
+
+/// Connects to the database at `url`, a `postgres://` URL or `key=value` connection string.
+pub async fn connect(url: &str) -> anyhow::Result<Client> {
+    let (client, connection) = tokio_postgres::connect(url, NoTls)
+        .await
+        .context("Couldn't connect to PostgreSQL")?;
+    tokio::spawn(async move {
+        if let Err(err) = connection.await {
+            error!("PostgreSQL connection failed: {}", err);
+        }
+    });
+    Ok(client)
+}
+
+/// Parameters of a query that's put together from optional conditions.
+#[derive(Default)]
+struct Params(Vec<Box<dyn ToSql + Sync + Send>>);
+
+impl Params {
+    /// Adds a parameter, returning its placeholder.
+    fn push(&mut self, value: impl ToSql + Sync + Send + 'static) -> String {
+        self.0.push(Box::new(value));
+        format!("${}", self.0.len())
+    }
+
+    fn as_refs(&self) -> Vec<&(dyn ToSql + Sync)> {
+        self.0
+            .iter()
+            .map(|value| value.as_ref() as &(dyn ToSql + Sync))
+            .collect()
+    }
+}
+
+/// Translates a glob like SQLite's `GLOB`, with `*`, `?` and `[...]` classes, into an anchored
+/// regular expression for `~`.
+fn glob_regex(pattern: &str) -> String {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut regex = String::from("^");
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            '[' => {
+                let Some(end) = chars
+                    .iter()
+                    .skip(i + 2)
+                    .position(|c| *c == ']')
+                    .map(|end| end + i + 2)
+                else {
+                    regex.push_str("\\[");
+                    i += 1;
+                    continue;
+                };
+                regex.push('[');
+                for c in &chars[i + 1..end] {
+                    if *c == '\\' {
+                        regex.push('\\');
+                    }
+                    regex.push(*c);
+                }
+                regex.push(']');
+                i = end;
+            }
+            c => {
+                if !c.is_alphanumeric() {
+                    regex.push('\\');
+                }
+                regex.push(c);
+            }
+        }
+        i += 1;
+    }
+    regex.push('$');
+    regex
+}
+
+static RECORD_COLUMNS: &str =
+    "name, type, view, data, ttl, static, created_at, updated_at, revision, lease, owner";
+
+/// Whether an RRset's lease is still running at the time in the placeholder.
+fn healthy_condition(now: &str) -> String {
+    format!(
+        "(static OR updated_at + lease * INTERVAL '1 second' >= {})",
+        now
+    )
+}
+
+fn record_reply(row: &Row) -> Result<RecordReply, tokio_postgres::Error> {
+    let values: Vec<String> = row.try_get(3)?;
+    let static_: bool = row.try_get(5)?;
+    let created_at: OffsetDateTime = row.try_get(6)?;
+    let updated_at: OffsetDateTime = row.try_get(7)?;
+    let lease = row.try_get::<_, i64>(9)? as u32;
+    Ok(RecordReply {
+        name: row.try_get(0)?,
+        r#type: row.try_get(1)?,
+        data: values.first().cloned().unwrap_or_default(),
+        ttl: row.try_get::<_, i64>(4)? as u32,
+        created_at: created_at.unix_timestamp(),
+        updated_at: updated_at.unix_timestamp(),
+        healthy: is_healthy(static_, updated_at, lease),
+        view: row.try_get(2)?,
+        r#static: static_,
+        values,
+        revision: row.try_get::<_, i64>(8)? as u64,
+        lease,
+        owner: row.try_get(10)?,
+    })
+}
+
+/// Checks whether an upsert or delete would change an RRset that another publisher still holds,
+/// locking the RRset for the rest of the transaction.
+async fn owner_conflict(
+    tx: &Transaction<'_>,
+    request: &FindUniqueRecordRequest,
+) -> Result<Option<OwnerConflict>, tokio_postgres::Error> {
+    let row = tx
+        .query_opt(
+            r#"
+SELECT owner, static, updated_at, lease
+FROM records
+WHERE name = $1
+  AND type = $2
+  AND view = $3
+FOR UPDATE"#,
+            &[&request.name, &request.r#type, &request.view],
+        )
+        .await?;
+    let Some(row) = row else {
+        return Ok(None);
+    };
+    let healthy = is_healthy(
+        row.try_get(1)?,
+        row.try_get(2)?,
+        row.try_get::<_, i64>(3)? as u32,
+    );
+    Ok(check_owner(request, row.try_get(0)?, healthy))
+}
+
+/// Remembers a rejected change, forgetting the oldest ones beyond [`MAX_CONFLICTS`].
+async fn record_conflict(
+    tx: &Transaction<'_>,
+    conflict: &OwnerConflict,
+    now: OffsetDateTime,
+) -> Result<(), tokio_postgres::Error> {
+    warn!("Rejected change: {}", conflict);
+    tx.execute(
+        r#"
+INSERT INTO conflicts (name, type, view, owner, challenger, created_at)
+VALUES ($1, $2, $3, $4, $5, $6)"#,
+        &[
+            &conflict.name,
+            &conflict.r#type,
+            &conflict.view,
+            &conflict.owner,
+            &conflict.challenger,
+            &now,
+        ],
+    )
+    .await?;
+    tx.execute(
+        "DELETE FROM conflicts WHERE id <= (SELECT MAX(id) FROM conflicts) - $1",
+        &[&(MAX_CONFLICTS as i64)],
+    )
+    .await?;
+    Ok(())
+}
+
+fn history_action(action: HistoryAction) -> &'static str {
+    match action {
+        HistoryAction::Upsert => "upsert",
+        HistoryAction::Delete => "delete",
+        HistoryAction::Expire => "expire",
+    }
+}
+
+fn parse_history_action(action: &str) -> HistoryAction {
+    match action {
+        "delete" => HistoryAction::Delete,
+        "expire" => HistoryAction::Expire,
+        _ => HistoryAction::Upsert,
+    }
+}
+
+async fn record_history(
+    tx: &Transaction<'_>,
+    entry: &HistoryEntry,
+    now: OffsetDateTime,
+) -> Result<(), tokio_postgres::Error> {
+    tx.execute(
+        r#"
+INSERT INTO history (name, type, view, action, old_values, new_values, ttl, revision, owner, actor, created_at)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)"#,
+        &[
+            &entry.name,
+            &entry.r#type,
+            &entry.view,
+            &history_action(entry.action()),
+            &entry.old_values,
+            &entry.new_values,
+            &(entry.ttl as i64),
+            &(entry.revision as i64),
+            &entry.owner,
+            &entry.actor,
+            &now,
+        ],
+    )
+    .await?;
+    Ok(())
+}
+
+/// Replaces an RRset within a transaction, bumping its revision if anything changed.
+///
+/// Changes are recorded in the history as made by `actor`, refreshes of an unchanged RRset aren't.
+async fn upsert_rrset(
+    tx: &Transaction<'_>,
+    request: UpsertRecordRequest,
+    now: OffsetDateTime,
+    actor: &str,
+) -> Result<(), tokio_postgres::Error> {
+    let values = if request.values.is_empty() {
+        vec![request.value]
+    } else {
+        request.values
+    };
+    let lease = if request.lease == 0 {
+        DEFAULT_LEASE
+    } else {
+        request.lease
+    };
+    let existing = tx
+        .query_opt(
+            r#"
+SELECT data, ttl, static, revision, owner
+FROM records
+WHERE name = $1
+  AND type = $2
+  AND view = $3
+FOR UPDATE"#,
+            &[&request.name, &request.r#type, &request.view],
+        )
+        .await?;
+    let existing = match existing {
+        Some(row) => Some((
+            row.try_get::<_, Vec<String>>(0)?,
+            row.try_get::<_, i64>(1)? as u32,
+            row.try_get::<_, bool>(2)?,
+            row.try_get::<_, i64>(3)? as u64,
+            row.try_get::<_, String>(4)?,
+        )),
+        None => None,
+    };
+    // Refreshing an unchanged RRset keeps its revision, so clients can keep it alive.
+    let changed = match &existing {
+        Some((existing_values, ttl, static_, _, _)) => {
+            existing_values.len() != values.len()
+                || existing_values.iter().any(|value| !values.contains(value))
+                || *ttl != request.ttl
+                || *static_ != request.r#static
+        }
+        None => true,
+    };
+    let revision = existing.as_ref().map_or(
+        1,
+        |(_, _, _, revision, _)| {
+            if changed {
+                revision + 1
+            } else {
+                *revision
+            }
+        },
+    );
+    tx.execute(
+        r#"
+INSERT INTO records (name, type, view, data, ttl, static, created_at, updated_at, revision, lease, owner)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $7, $8, $9, $10)
+ON CONFLICT (name, type, view)
+    DO UPDATE SET data       = excluded.data,
+                  ttl        = excluded.ttl,
+                  static     = excluded.static,
+                  updated_at = excluded.updated_at,
+                  revision   = excluded.revision,
+                  lease      = excluded.lease,
+                  owner      = excluded.owner"#,
+        &[
+            &request.name,
+            &request.r#type,
+            &request.view,
+            &values,
+            &(request.ttl as i64),
+            &request.r#static,
+            &now,
+            &(revision as i64),
+            &(lease as i64),
+            &request.owner,
+        ],
+    )
+    .await?;
+    let unchanged_owner = existing
+        .as_ref()
+        .filter(|(_, _, _, _, owner)| *owner == request.owner)
+        .is_some();
+    if !changed && unchanged_owner {
+        return Ok(());
+    }
+    let entry = HistoryEntry {
+        name: request.name,
+        r#type: request.r#type,
+        view: request.view,
+        action: HistoryAction::Upsert as i32,
+        old_values: existing.map_or(vec![], |(values, _, _, _, _)| values),
+        new_values: values,
+        ttl: request.ttl,
+        revision,
+        owner: request.owner,
+        actor: actor.to_string(),
+        ..Default::default()
+    };
+    record_history(tx, &entry, now).await
+}
+
+async fn delete_rrset(
+    tx: &Transaction<'_>,
+    request: &FindUniqueRecordRequest,
+    now: OffsetDateTime,
+    actor: &str,
+) -> Result<(), tokio_postgres::Error> {
+    let deleted = tx
+        .query_opt(
+            r#"
+DELETE
+FROM records
+WHERE name = $1
+  AND type = $2
+  AND view = $3
+RETURNING data, ttl, revision, owner"#,
+            &[&request.name, &request.r#type, &request.view],
+        )
+        .await?;
+    let Some(row) = deleted else {
+        return Ok(());
+    };
+    let entry = HistoryEntry {
+        name: request.name.clone(),
+        r#type: request.r#type.clone(),
+        view: request.view.clone(),
+        action: HistoryAction::Delete as i32,
+        old_values: row.try_get(0)?,
+        ttl: row.try_get::<_, i64>(1)? as u32,
+        revision: row.try_get::<_, i64>(2)? as u64,
+        owner: row.try_get(3)?,
+        actor: actor.to_string(),
+        ..Default::default()
+    };
+    record_history(tx, &entry, now).await
+}
+
+/// A store in a PostgreSQL database, which several servers can share.
+///
+/// Every RRset is a single row with its values in an array. Queries are made one at a time over
+/// a single connection, which is opened again when the database closes it.
+#[derive(Debug)]
+pub struct PostgresStore {
+    url: String,
+    client: Mutex<Client>,
+}
+
+/// Connects to the database at `url` and migrates it to the latest schema.
+async fn open(url: &str) -> anyhow::Result<Client> {
+    let mut client = connect(url).await?;
+    migrate_database(&mut client).await?;
+    Ok(client)
+}
+
+impl PostgresStore {
+    /// Connects to the database at `url` and migrates it to the latest schema.
+    pub async fn connect(url: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            url: url.to_string(),
+            client: Mutex::new(open(url).await?),
+        })
+    }
+
+    /// Locks the connection, reconnecting first if the last one was closed, e.g. by a database
+    /// restart or a network failure.
+    async fn client(&self) -> Result<MutexGuard<'_, Client>> {
+        let mut client = self.client.lock().await;
+        if client.is_closed() {
+            warn!("PostgreSQL connection was closed, reconnecting");
+            *client = open(&self.url)
+                .await
+                .map_err(|err| RecordError::Storage(err.into()))?;
+        }
+        Ok(client)
+    }
+
+    /// Finds an RRset, or returns one without values and revision 0 if it doesn't exist.
+    async fn find_or_missing(&self, request: FindUniqueRecordRequest) -> Result<RecordReply> {
+        match self.find_unique(request.clone()).await {
+            Err(RecordError::NotFound) => Ok(RecordReply {
+                name: request.name,
+                r#type: request.r#type,
+                view: request.view,
+                ..Default::default()
+            }),
+            result => result,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl RecordStore for PostgresStore {
+    async fn find_unique(&self, request: FindUniqueRecordRequest) -> Result<RecordReply> {
+        let row = self
+            .client()
+            .await?
+            .query_opt(
+                &format!(
+                    "SELECT {} FROM records WHERE name = $1 AND type = $2 AND view = $3",
+                    RECORD_COLUMNS
+                ),
+                &[&request.name, &request.r#type, &request.view],
+            )
+            .await?;
+        match row {
+            Some(row) => Ok(record_reply(&row)?),
+            None => Err(RecordError::NotFound),
+        }
+    }
+
+    async fn find_for_view(
+        &self,
+        name: String,
+        r#type: String,
+        view: String,
+    ) -> Result<RecordReply> {
+        let row = self
+            .client()
+            .await?
+            .query_opt(
+                &format!(
+                    r#"
+SELECT {}
+FROM records
+WHERE name = $1
+  AND type = $2
+  AND view IN ($3, '')
+ORDER BY view = $3 DESC
+LIMIT 1"#,
+                    RECORD_COLUMNS
+                ),
+                &[&name, &r#type, &view],
+            )
+            .await?;
+        let record = match row {
+            Some(row) => Some(record_reply(&row)?),
+            None => None,
+        };
+        record
+            .filter(|record| record.healthy)
+            .ok_or(RecordError::NotFound)
+    }
+
+    async fn has_name(&self, name: String, view: String) -> Result<bool> {
+        let row = self
+            .client()
+            .await?
+            .query_one(
+                &format!(
+                    r#"
+SELECT EXISTS(SELECT 1
+              FROM records
+              WHERE (name = $1 OR right(name, $2) = $3)
+                AND view IN ($4, '')
+                AND {})"#,
+                    healthy_condition("$5")
+                ),
+                &[
+                    &name,
+                    &(name.len() as i32 + 1),
+                    &format!(".{}", name),
+                    &view,
+                    &OffsetDateTime::now_utc(),
+                ],
+            )
+            .await?;
+        Ok(row.try_get(0)?)
+    }
+
+    async fn upsert(&self, request: UpsertRecordRequest) -> Result<RecordReply> {
+        let violations = validate_rrset_shape(&request, "");
+        if !violations.is_empty() {
+            return Err(RecordError::Invalid(violations));
+        }
+        let find = upsert_key(&request);
+        let now = OffsetDateTime::now_utc();
+
+        let conflict = {
+            let mut client = self.client().await?;
+            let tx = client.transaction().await?;
+            let conflict = owner_conflict(&tx, &find).await?;
+            match conflict {
+                Some(ref conflict) => record_conflict(&tx, conflict, now).await?,
+                None => upsert_rrset(&tx, request, now, &current_actor()).await?,
+            }
+            tx.commit().await?;
+            conflict
+        };
+        if let Some(conflict) = conflict {
+            return Err(conflict.into());
+        }
+
+        self.find_unique(find).await
+    }
+
+    async fn batch(&self, operations: Vec<RecordOperation>) -> Result<BatchReply> {
+        let violations = validate_batch_shape(&operations);
+        if !violations.is_empty() {
+            return Err(RecordError::Invalid(violations));
+        }
+        let now = OffsetDateTime::now_utc();
+        let actor = current_actor();
+        let upserted: Vec<FindUniqueRecordRequest> = operations
+            .iter()
+            .filter_map(|operation| match &operation.operation {
+                Some(Operation::Upsert(request)) => Some(upsert_key(request)),
+                _ => None,
+            })
+            .collect();
+
+        let (conflicts, owner_conflicts) = {
+            let mut client = self.client().await?;
+            let tx = client.transaction().await?;
+            let mut owner_conflicts = vec![];
+            for key in operations.iter().filter_map(operation_key) {
+                if let Some(conflict) = owner_conflict(&tx, &key).await? {
+                    record_conflict(&tx, &conflict, now).await?;
+                    owner_conflicts.push(conflict);
+                }
+            }
+            let mut conflicts = vec![];
+            if owner_conflicts.is_empty() {
+                for operation in operations.iter().filter(|op| op.if_revision != 0) {
+                    let Some(key) = operation_key(operation) else {
+                        continue;
+                    };
+                    let revision: Option<i64> = tx
+                        .query_opt(
+                            "SELECT revision FROM records WHERE name = $1 AND type = $2 AND view = $3",
+                            &[&key.name, &key.r#type, &key.view],
+                        )
+                        .await?
+                        .map(|row| row.try_get(0))
+                        .transpose()?;
+                    if revision != Some(operation.if_revision as i64) {
+                        conflicts.push(key);
+                    }
+                }
+            }
+            // Nothing but the owner conflicts has been written yet, dropping the transaction
+            // rolls it back.
+            if !owner_conflicts.is_empty() {
+                tx.commit().await?;
+            } else if conflicts.is_empty() {
+                for operation in operations {
+                    match operation.operation {
+                        Some(Operation::Upsert(request)) => {
+                            upsert_rrset(&tx, request, now, &actor).await?
+                        }
+                        Some(Operation::Delete(request)) => {
+                            delete_rrset(&tx, &request, now, &actor).await?
+                        }
+                        None => {}
+                    }
+                }
+                tx.commit().await?;
+            }
+            (conflicts, owner_conflicts)
+        };
+        if let Some(conflict) = owner_conflicts.into_iter().next() {
+            return Err(conflict.into());
+        }
+
+        let mut reply = BatchReply {
+            applied: conflicts.is_empty(),
+            ..Default::default()
+        };
+        if reply.applied {
+            for key in upserted {
+                reply.records.push(self.find_or_missing(key).await?);
+            }
+        } else {
+            for key in conflicts {
+                reply.conflicts.push(self.find_or_missing(key).await?);
+            }
+        }
+        Ok(reply)
+    }
+
+    async fn keep_alive(&self, renewals: Vec<LeaseRenewal>) -> Result<Vec<Option<RecordReply>>> {
+        let now = OffsetDateTime::now_utc();
+        let sql = format!(
+            r#"
+UPDATE records
+SET updated_at = $5
+WHERE name = $1
+  AND type = $2
+  AND view = $3
+  AND revision = $4
+  AND owner = $6
+RETURNING {}"#,
+            RECORD_COLUMNS
+        );
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        let mut results = vec![];
+        for renewal in renewals {
+            let row = tx
+                .query_opt(
+                    &sql,
+                    &[
+                        &renewal.name,
+                        &renewal.r#type,
+                        &renewal.view,
+                        &(renewal.revision as i64),
+                        &now,
+                        &renewal.owner,
+                    ],
+                )
+                .await?;
+            results.push(match row {
+                Some(row) => Some(record_reply(&row)?),
+                None => None,
+            });
+        }
+        tx.commit().await?;
+        Ok(results)
+    }
+
+    async fn query(&self, query: RecordsQueryRequest) -> Result<RecordsPage> {
+        // Timestamps in sort keys are fixed width text, so they compare like the other columns.
+        let columns: &[&str] = match query.order_by() {
+            RecordOrder::Name => &["name", "type", "view"],
+            RecordOrder::UpdatedAt => &[
+                "to_char(updated_at AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS.US')",
+                "name",
+                "type",
+                "view",
+            ],
+            RecordOrder::CreatedAt => &[
+                "to_char(created_at AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS.US')",
+                "name",
+                "type",
+                "view",
+            ],
+        };
+        let (direction, comparison) = if query.descending {
+            ("DESC", "<")
+        } else {
+            ("ASC", ">")
+        };
+
+        let mut params = Params::default();
+        let mut conditions = vec![];
+        if !query.zone.is_empty() {
+            conditions.push(format!(
+                "(name = {} OR right(name, {}) = {})",
+                params.push(query.zone.clone()),
+                params.push(query.zone.len() as i32 + 1),
+                params.push(format!(".{}", query.zone))
+            ));
+        }
+        if !query.name.is_empty() {
+            conditions.push(format!("name ~ {}", params.push(glob_regex(&query.name))));
+        }
+        if !query.r#type.is_empty() {
+            conditions.push(format!("type = {}", params.push(query.r#type.clone())));
+        }
+        if !query.view.is_empty() {
+            let view = if query.view == "*" { "" } else { &query.view };
+            conditions.push(format!("view = {}", params.push(view.to_string())));
+        }
+        match query.health() {
+            HealthFilter::Any => {}
+            HealthFilter::Healthy => {
+                conditions.push(healthy_condition(&params.push(OffsetDateTime::now_utc())))
+            }
+            HealthFilter::Unhealthy => conditions.push(format!(
+                "NOT {}",
+                healthy_condition(&params.push(OffsetDateTime::now_utc()))
+            )),
+        }
+        if query.updated_since != 0 {
+            let since = OffsetDateTime::from_unix_timestamp(query.updated_since).map_err(|_| {
+                RecordError::Invalid(vec![FieldViolation::new(
+                    "updated_since",
+                    "is out of range",
+                )])
+            })?;
+            conditions.push(format!("updated_at >= {}", params.push(since)));
+        }
+        if !query.owner.is_empty() {
+            conditions.push(format!("owner = {}", params.push(query.owner.clone())));
+        }
+        if !query.page_token.is_empty() {
+            let key = decode_page_token(&query.page_token, columns.len())?;
+            let placeholders: Vec<String> = key.into_iter().map(|k| params.push(k)).collect();
+            conditions.push(format!(
+                "({}) {} ({})",
+                columns.join(", "),
+                comparison,
+                placeholders.join(", ")
+            ));
+        }
+        let filter = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", conditions.join(" AND "))
+        };
+        // One more RRset than asked for tells whether there's another page, no limit is NULL.
+        let page_size = query.page_size as usize;
+        let limit = params.push((page_size > 0).then_some(page_size as i64 + 1));
+        let sql = format!(
+            r#"
+SELECT {}, {}
+FROM records
+{}
+ORDER BY {}
+LIMIT {}"#,
+            RECORD_COLUMNS,
+            columns
+                .iter()
+                .map(|column| format!("{}::TEXT", column))
+                .collect::<Vec<String>>()
+                .join(", "),
+            filter,
+            columns
+                .iter()
+                .map(|column| format!("{} {}", column, direction))
+                .collect::<Vec<String>>()
+                .join(", "),
+            limit
+        );
+
+        let rows = self.client().await?.query(&sql, &params.as_refs()).await?;
+        let mut records = vec![];
+        let mut keys = vec![];
+        for row in rows {
+            records.push(record_reply(&row)?);
+            keys.push(
+                (0..columns.len())
+                    .map(|i| row.try_get(11 + i))
+                    .collect::<Result<Vec<String>, _>>()?,
+            );
+        }
+        let mut next_page_token = String::new();
+        if page_size > 0 && records.len() > page_size {
+            records.truncate(page_size);
+            next_page_token = encode_page_token(&keys[page_size - 1]);
+        }
+        Ok(RecordsPage {
+            records,
+            next_page_token,
+        })
+    }
+
+    async fn conflicts(&self) -> Result<Vec<Conflict>> {
+        let rows = self
+            .client()
+            .await?
+            .query(
+                r#"
+SELECT name, type, view, owner, challenger, created_at
+FROM conflicts
+ORDER BY id DESC"#,
+                &[],
+            )
+            .await?;
+        let mut conflicts = vec![];
+        for row in rows {
+            let created_at: OffsetDateTime = row.try_get(5)?;
+            conflicts.push(Conflict {
+                name: row.try_get(0)?,
+                r#type: row.try_get(1)?,
+                view: row.try_get(2)?,
+                owner: row.try_get(3)?,
+                challenger: row.try_get(4)?,
+                created_at: created_at.unix_timestamp(),
+            });
+        }
+        Ok(conflicts)
+    }
+
+    async fn history(&self, request: HistoryRequest) -> Result<Vec<HistoryEntry>> {
+        let mut params = Params::default();
+        let mut conditions = vec![];
+        if !request.zone.is_empty() {
+            conditions.push(format!(
+                "(name = {} OR right(name, {}) = {})",
+                params.push(request.zone.clone()),
+                params.push(request.zone.len() as i32 + 1),
+                params.push(format!(".{}", request.zone))
+            ));
+        }
+        for (column, value) in [
+            ("name", &request.name),
+            ("type", &request.r#type),
+            ("view", &request.view),
+            ("actor", &request.actor),
+        ] {
+            if !value.is_empty() {
+                conditions.push(format!("{} = {}", column, params.push(value.clone())));
+            }
+        }
+        if request.since != 0 {
+            let since = OffsetDateTime::from_unix_timestamp(request.since).map_err(|_| {
+                RecordError::Invalid(vec![FieldViolation::new("since", "is out of range")])
+            })?;
+            conditions.push(format!("created_at >= {}", params.push(since)));
+        }
+        let filter = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", conditions.join(" AND "))
+        };
+        let limit = if request.limit == 0 {
+            DEFAULT_HISTORY_LIMIT
+        } else {
+            request.limit
+        };
+        let sql = format!(
+            r#"
+SELECT name, type, view, action, old_values, new_values, ttl, revision, owner, actor, created_at
+FROM history
+{}
+ORDER BY id DESC
+LIMIT {}"#,
+            filter,
+            params.push(limit as i64)
+        );
+
+        let rows = self.client().await?.query(&sql, &params.as_refs()).await?;
+        let mut entries = vec![];
+        for row in rows {
+            let action: String = row.try_get(3)?;
+            let created_at: OffsetDateTime = row.try_get(10)?;
+            entries.push(HistoryEntry {
+                name: row.try_get(0)?,
+                r#type: row.try_get(1)?,
+                view: row.try_get(2)?,
+                action: parse_history_action(&action) as i32,
+                old_values: row.try_get(4)?,
+                new_values: row.try_get(5)?,
+                ttl: row.try_get::<_, i64>(6)? as u32,
+                revision: row.try_get::<_, i64>(7)? as u64,
+                owner: row.try_get(8)?,
+                actor: row.try_get(9)?,
+                created_at: created_at.unix_timestamp(),
+            });
+        }
+        Ok(entries)
+    }
+
+    async fn record_expiries(&self) -> Result<usize> {
+        let expired = self
+            .client()
+            .await?
+            .execute(
+                r#"
+INSERT
+INTO history (name, type, view, action, old_values, ttl, revision, owner, created_at)
+SELECT name, type, view, $1, data, ttl, revision, owner, $2
+FROM records
+WHERE NOT static
+  AND updated_at + lease * INTERVAL '1 second' < $2
+  AND NOT EXISTS (SELECT 1
+                  FROM history
+                  WHERE history.name = records.name
+                    AND history.type = records.type
+                    AND history.view = records.view
+                    AND history.action = $1
+                    AND history.created_at >= records.updated_at)"#,
+                &[
+                    &history_action(HistoryAction::Expire),
+                    &OffsetDateTime::now_utc(),
+                ],
+            )
+            .await?;
+        Ok(expired as usize)
+    }
+
+    async fn prune_history(&self, cutoff: OffsetDateTime) -> Result<usize> {
+        let pruned = self
+            .client()
+            .await?
+            .execute("DELETE FROM history WHERE created_at < $1", &[&cutoff])
+            .await?;
+        Ok(pruned as usize)
+    }
+
+    async fn delete(&self, request: FindUniqueRecordRequest) -> Result<()> {
+        let now = OffsetDateTime::now_utc();
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        let conflict = owner_conflict(&tx, &request).await?;
+        match conflict {
+            Some(ref conflict) => record_conflict(&tx, conflict, now).await?,
+            None => delete_rrset(&tx, &request, now, &current_actor()).await?,
+        }
+        tx.commit().await?;
+        match conflict {
+            Some(conflict) => Err(conflict.into()),
+            None => Ok(()),
+        }
+    }
+    async fn integrity_check(&self) -> Result<Vec<String>> {
+        let client = self.client().await?;
+        let mut problems = vec![];
+        let status = SchemaStatus {
+            current: applied_migrations(&*client)
+                .await
+                .map_err(|err| RecordError::Storage(err.into()))?,
+            latest: MIGRATIONS.len(),
+        };
+        if !status.is_current() {
+            problems.push(format!("The database is at {}", status));
+        }
+
+        // PostgreSQL checks its own pages, but only amcheck can check that indexes match
+        let amcheck = client
+            .query_opt(
+                "SELECT extnamespace::regnamespace::text FROM pg_extension WHERE extname = 'amcheck'",
+                &[],
+            )
+            .await?;
+        if let Some(amcheck) = amcheck {
+            let amcheck_schema: String = amcheck.try_get(0)?;
+            let indexes = client
+                .query(
+                    r#"
+SELECT indexrelid, indexrelid::regclass::text
+FROM pg_index
+WHERE indrelid IN ('records'::regclass, 'conflicts'::regclass, 'history'::regclass)"#,
+                    &[],
+                )
+                .await?;
+            for index in indexes {
+                let oid: u32 = index.try_get(0)?;
+                let name: String = index.try_get(1)?;
+                match client
+                    .execute(
+                        &format!(
+                            "SELECT {}.bt_index_check($1::oid::regclass)",
+                            amcheck_schema
+                        ),
+                        &[&oid],
+                    )
+                    .await
+                {
+                    Ok(_) => {}
+                    Err(err) => match err.as_db_error() {
+                        Some(db_err) => problems.push(format!("{}: {}", name, db_err.message())),
+                        None => return Err(err.into()),
+                    },
+                }
+            }
+        } else {
+            debug!("amcheck isn't installed, not checking indexes");
+        }
+
+        let invalid = client
+            .query(
+                r#"
+SELECT name, type, view
+FROM records
+WHERE cardinality(data) = 0
+   OR ttl < 0
+   OR lease < 0
+   OR revision < 1
+   OR updated_at < created_at
+ORDER BY name, type, view"#,
+                &[],
+            )
+            .await?;
+        for row in invalid {
+            problems.push(format!(
+                "RRset {} {} in view {:?} is invalid",
+                row.try_get::<_, String>(0)?,
+                row.try_get::<_, String>(1)?,
+                row.try_get::<_, String>(2)?,
+            ));
+        }
+        Ok(problems)
+    }
+}
@@ -0,0 +1,87 @@
+//! The part of the `google.rpc` error model the API uses, wire compatible with
+//! `google/rpc/status.proto` and `google/rpc/error_details.proto` so any gRPC client can decode
+//! the details.
+
+use tonic::{Code, Status};
+
+static BAD_REQUEST_TYPE_URL: &str = "type.googleapis.com/google.rpc.BadRequest";
+
+/// `google.rpc.Status`, sent in the `grpc-status-details-bin` trailer.
+#[derive(Clone, PartialEq, ::prost::Message)]
+struct RpcStatus {
+    #[prost(int32, tag = "1")]
+    code: i32,
+    #[prost(string, tag = "2")]
+    message: ::prost::alloc::string::String,
+    #[prost(message, repeated, tag = "3")]
+    details: ::prost::alloc::vec::Vec<Any>,
+}
+
+/// `google.protobuf.Any`
+#[derive(Clone, PartialEq, ::prost::Message)]
+struct Any {
+    #[prost(string, tag = "1")]
+    type_url: ::prost::alloc::string::String,
+    #[prost(bytes = "vec", tag = "2")]
+    value: ::prost::alloc::vec::Vec<u8>,
+}
+
+/// `google.rpc.BadRequest`
+#[derive(Clone, PartialEq, ::prost::Message)]
+struct BadRequest {
+    #[prost(message, repeated, tag = "1")]
+    field_violations: ::prost::alloc::vec::Vec<FieldViolation>,
+}
+
+/// `google.rpc.BadRequest.FieldViolation`, a request field and what's wrong with it.
+#[derive(Clone, PartialEq, Eq, ::prost::Message)]
+pub struct FieldViolation {
+    #[prost(string, tag = "1")]
+    pub field: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub description: ::prost::alloc::string::String,
+}
+
+impl FieldViolation {
+    pub fn new(field: impl Into<String>, description: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            description: description.into(),
+        }
+    }
+}
+
+/// An `INVALID_ARGUMENT` status carrying the field violations as a `google.rpc.BadRequest`.
+pub fn invalid_argument(message: impl Into<String>, violations: Vec<FieldViolation>) -> Status {
+    let message = message.into();
+    let bad_request = BadRequest {
+        field_violations: violations,
+    };
+    let status = RpcStatus {
+        code: Code::InvalidArgument as i32,
+        message: message.clone(),
+        details: vec![Any {
+            type_url: BAD_REQUEST_TYPE_URL.to_string(),
+            value: ::prost::Message::encode_to_vec(&bad_request),
+        }],
+    };
+    Status::with_details(
+        Code::InvalidArgument,
+        message,
+        ::prost::Message::encode_to_vec(&status).into(),
+    )
+}
+
+/// The field violations sent with a status, empty if there aren't any.
+pub fn field_violations(status: &Status) -> Vec<FieldViolation> {
+    let Ok(status) = <RpcStatus as ::prost::Message>::decode(status.details()) else {
+        return vec![];
+    };
+    status
+        .details
+        .into_iter()
+        .filter(|detail| detail.type_url == BAD_REQUEST_TYPE_URL)
+        .filter_map(|detail| <BadRequest as ::prost::Message>::decode(detail.value.as_slice()).ok())
+        .flat_map(|bad_request| bad_request.field_violations)
+        .collect()
+}
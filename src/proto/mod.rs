@@ -1,3 +1,36 @@
+pub mod error_details;
 mod swandns;
 
 pub use self::swandns::*;
+
+use serde::Serializer;
+
+/// Proto enums, which prost stores in `i32` fields.
+pub trait EnumName: TryFrom<i32> {
+    /// Name of the value in the proto file.
+    fn name(&self) -> &'static str;
+}
+
+macro_rules! enum_name {
+    ($($enum:ty),*) => {
+        $(impl EnumName for $enum {
+            fn name(&self) -> &'static str {
+                self.as_str_name()
+            }
+        })*
+    };
+}
+
+enum_name!(HealthFilter, RecordOrder, ChangeAction, HistoryAction);
+
+/// Serializes a proto enum field by the name of its value like the protobuf JSON mapping,
+/// rather than as a number. Values that aren't known stay numbers.
+pub fn serialize_enum<E: EnumName, S: Serializer>(
+    value: &i32,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match E::try_from(*value) {
+        Ok(value) => serializer.serialize_str(value.name()),
+        Err(_) => serializer.serialize_i32(*value),
+    }
+}
@@ -1,15 +1,18 @@
+#[derive(serde::Serialize)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct PingRequest {
     #[prost(string, tag = "1")]
     pub message: ::prost::alloc::string::String,
 }
+#[derive(serde::Serialize)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct PingReply {
     #[prost(string, tag = "1")]
     pub message: ::prost::alloc::string::String,
 }
+#[derive(serde::Serialize)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct UpsertRecordRequest {
@@ -21,7 +24,25 @@ pub struct UpsertRecordRequest {
     pub value: ::prost::alloc::string::String,
     #[prost(uint32, tag = "4")]
     pub ttl: u32,
+    #[prost(string, tag = "5")]
+    pub view: ::prost::alloc::string::String,
+    #[prost(bool, tag = "6")]
+    pub r#static: bool,
+    /// Every value of the RRset, replacing the existing set. Defaults to just `value`.
+    #[prost(string, repeated, tag = "7")]
+    pub values: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// Seconds the RRset is served without being refreshed, separate from the DNS TTL.
+    /// Defaults to 7 minutes. Static records never expire.
+    #[prost(uint32, tag = "8")]
+    pub lease: u32,
+    /// Identity of the publisher, e.g. its machine id. An RRset owned by someone else is only replaced
+    /// once its lease expired, or with `force`.
+    #[prost(string, tag = "9")]
+    pub owner: ::prost::alloc::string::String,
+    #[prost(bool, tag = "10")]
+    pub force: bool,
 }
+#[derive(serde::Serialize)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct RecordReply {
@@ -39,13 +60,77 @@ pub struct RecordReply {
     pub updated_at: i64,
     #[prost(bool, tag = "7")]
     pub healthy: bool,
+    #[prost(string, tag = "8")]
+    pub view: ::prost::alloc::string::String,
+    #[prost(bool, tag = "9")]
+    pub r#static: bool,
+    /// Every value of the RRset, `data` is the first.
+    #[prost(string, repeated, tag = "10")]
+    pub values: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// Incremented whenever the values, TTL or static flag of the RRset change.
+    #[prost(uint64, tag = "11")]
+    pub revision: u64,
+    #[prost(uint32, tag = "12")]
+    pub lease: u32,
+    /// Empty for records anyone may replace.
+    #[prost(string, tag = "13")]
+    pub owner: ::prost::alloc::string::String,
 }
+/// Empty fields don't filter.
+#[derive(serde::Serialize)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
-pub struct RecordsQueryRequest {}
+pub struct RecordsQueryRequest {
+    /// Only RRsets at or below this name.
+    #[prost(string, tag = "1")]
+    pub zone: ::prost::alloc::string::String,
+    /// Glob for the names, e.g. `web-*.example.com`.
+    #[prost(string, tag = "2")]
+    pub name: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub r#type: ::prost::alloc::string::String,
+    /// `*` for RRsets published to all views.
+    #[prost(string, tag = "4")]
+    pub view: ::prost::alloc::string::String,
+    #[prost(enumeration = "HealthFilter", tag = "5")]
+    #[serde(
+        serialize_with = "crate::proto::serialize_enum::<crate::proto::HealthFilter, _>"
+    )]
+    pub health: i32,
+    /// Unix timestamp, only RRsets updated at or after it.
+    #[prost(int64, tag = "6")]
+    pub updated_since: i64,
+    #[prost(string, tag = "7")]
+    pub owner: ::prost::alloc::string::String,
+    #[prost(enumeration = "RecordOrder", tag = "8")]
+    #[serde(
+        serialize_with = "crate::proto::serialize_enum::<crate::proto::RecordOrder, _>"
+    )]
+    pub order_by: i32,
+    #[prost(bool, tag = "9")]
+    pub descending: bool,
+    /// Maximum number of RRsets, 0 for all of them.
+    #[prost(uint32, tag = "10")]
+    pub page_size: u32,
+    /// `next_page_token` of the previous page, with the same filters and order.
+    #[prost(string, tag = "11")]
+    pub page_token: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RecordsPage {
+    #[prost(message, repeated, tag = "1")]
+    pub records: ::prost::alloc::vec::Vec<RecordReply>,
+    /// Empty on the last page.
+    #[prost(string, tag = "2")]
+    pub next_page_token: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct EmptyReply {}
+#[derive(serde::Serialize)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct FindUniqueRecordRequest {
@@ -53,6 +138,463 @@ pub struct FindUniqueRecordRequest {
     pub name: ::prost::alloc::string::String,
     #[prost(string, tag = "2")]
     pub r#type: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub view: ::prost::alloc::string::String,
+    /// Identity of the publisher deleting the RRset. An RRset owned by someone else is only deleted
+    /// once its lease expired, or with `force`. Lookups ignore both.
+    #[prost(string, tag = "4")]
+    pub owner: ::prost::alloc::string::String,
+    #[prost(bool, tag = "5")]
+    pub force: bool,
+}
+#[derive(serde::Serialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ImportRequest {
+    /// RFC 1035 master file contents.
+    #[prost(string, tag = "1")]
+    pub zone_file: ::prost::alloc::string::String,
+    /// Origin for relative names, unless the file sets $ORIGIN.
+    #[prost(string, tag = "2")]
+    pub origin: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub view: ::prost::alloc::string::String,
+    #[prost(bool, tag = "4")]
+    pub r#static: bool,
+    #[prost(bool, tag = "5")]
+    pub dry_run: bool,
+}
+#[derive(serde::Serialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RecordChange {
+    #[prost(enumeration = "ChangeAction", tag = "1")]
+    #[serde(
+        serialize_with = "crate::proto::serialize_enum::<crate::proto::ChangeAction, _>"
+    )]
+    pub action: i32,
+    #[prost(message, optional, tag = "2")]
+    pub record: ::core::option::Option<RecordReply>,
+    /// Data and TTL before the import, for updates.
+    #[prost(string, tag = "3")]
+    pub previous_data: ::prost::alloc::string::String,
+    #[prost(uint32, tag = "4")]
+    pub previous_ttl: u32,
+    #[prost(string, repeated, tag = "5")]
+    pub previous_values: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[derive(serde::Serialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ImportReply {
+    #[prost(message, repeated, tag = "1")]
+    pub changes: ::prost::alloc::vec::Vec<RecordChange>,
+}
+#[derive(serde::Serialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExportRequest {
+    /// Only export records within this zone, defaults to every zone.
+    #[prost(string, tag = "1")]
+    pub zone: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub view: ::prost::alloc::string::String,
+    #[prost(bool, tag = "3")]
+    pub include_static: bool,
+}
+#[derive(serde::Serialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExportReply {
+    #[prost(string, tag = "1")]
+    pub zone_file: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct LeaseRenewal {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub r#type: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub view: ::prost::alloc::string::String,
+    /// Revision the client last published, the lease is only renewed while it's still current.
+    #[prost(uint64, tag = "4")]
+    pub revision: u64,
+    /// Only the owner of an RRset can renew it.
+    #[prost(string, tag = "5")]
+    pub owner: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct KeepAliveRequest {
+    #[prost(message, repeated, tag = "1")]
+    pub renewals: ::prost::alloc::vec::Vec<LeaseRenewal>,
+}
+#[derive(serde::Serialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RenewalResult {
+    /// False when the record changed or doesn't exist anymore, and has to be upserted again.
+    #[prost(bool, tag = "1")]
+    pub current: bool,
+    #[prost(message, optional, tag = "2")]
+    pub record: ::core::option::Option<RecordReply>,
+}
+#[derive(serde::Serialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct KeepAliveReply {
+    /// One result for each renewal, in the same order.
+    #[prost(message, repeated, tag = "1")]
+    pub results: ::prost::alloc::vec::Vec<RenewalResult>,
+}
+#[derive(serde::Serialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RecordOperation {
+    /// Only apply the batch while the RRset is at this revision. 0 skips the check.
+    #[prost(uint64, tag = "3")]
+    pub if_revision: u64,
+    #[prost(oneof = "record_operation::Operation", tags = "1, 2")]
+    pub operation: ::core::option::Option<record_operation::Operation>,
+}
+/// Nested message and enum types in `RecordOperation`.
+pub mod record_operation {
+    #[derive(serde::Serialize)]
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Operation {
+        #[prost(message, tag = "1")]
+        Upsert(super::UpsertRecordRequest),
+        #[prost(message, tag = "2")]
+        Delete(super::FindUniqueRecordRequest),
+    }
+}
+#[derive(serde::Serialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BatchRequest {
+    /// Applied in order, in a single transaction.
+    #[prost(message, repeated, tag = "1")]
+    pub operations: ::prost::alloc::vec::Vec<RecordOperation>,
+}
+#[derive(serde::Serialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BatchReply {
+    /// False when a revision precondition failed, in which case none of the operations were applied.
+    #[prost(bool, tag = "1")]
+    pub applied: bool,
+    /// The resulting RRset of each upsert, in order.
+    #[prost(message, repeated, tag = "2")]
+    pub records: ::prost::alloc::vec::Vec<RecordReply>,
+    /// The current state of the RRsets whose precondition failed, with revision 0 if they don't exist.
+    #[prost(message, repeated, tag = "3")]
+    pub conflicts: ::prost::alloc::vec::Vec<RecordReply>,
+}
+#[derive(serde::Serialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct InfoRequest {}
+#[derive(serde::Serialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct InfoReply {
+    #[prost(string, tag = "1")]
+    pub version: ::prost::alloc::string::String,
+    /// Unix timestamp of when the server started.
+    #[prost(int64, tag = "2")]
+    pub started_at: i64,
+    #[prost(uint32, tag = "3")]
+    pub dns_port: u32,
+    #[prost(uint32, tag = "4")]
+    pub api_port: u32,
+    #[prost(string, repeated, tag = "5")]
+    pub nameservers: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(string, repeated, tag = "6")]
+    pub views: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(uint32, tag = "7")]
+    pub records: u32,
+    #[prost(uint32, tag = "8")]
+    pub healthy_records: u32,
+}
+#[derive(serde::Serialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ZonesRequest {}
+#[derive(serde::Serialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ZoneStatus {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+    /// Empty for the default view.
+    #[prost(string, tag = "2")]
+    pub view: ::prost::alloc::string::String,
+    #[prost(uint32, tag = "3")]
+    pub static_records: u32,
+    /// RRsets from the database, including those published to every view.
+    #[prost(uint32, tag = "4")]
+    pub records: u32,
+    #[prost(uint32, tag = "5")]
+    pub healthy_records: u32,
+    #[prost(bool, tag = "6")]
+    pub dnssec_validate: bool,
+}
+#[derive(serde::Serialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ZonesReply {
+    #[prost(message, repeated, tag = "1")]
+    pub zones: ::prost::alloc::vec::Vec<ZoneStatus>,
+}
+#[derive(serde::Serialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ConflictsRequest {}
+#[derive(serde::Serialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Conflict {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub r#type: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub view: ::prost::alloc::string::String,
+    /// Publisher that owned the RRset.
+    #[prost(string, tag = "4")]
+    pub owner: ::prost::alloc::string::String,
+    /// Publisher whose upsert or delete was rejected.
+    #[prost(string, tag = "5")]
+    pub challenger: ::prost::alloc::string::String,
+    /// Unix timestamp of the rejected change.
+    #[prost(int64, tag = "6")]
+    pub created_at: i64,
+}
+#[derive(serde::Serialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ConflictsReply {
+    /// Most recent first.
+    #[prost(message, repeated, tag = "1")]
+    pub conflicts: ::prost::alloc::vec::Vec<Conflict>,
+}
+#[derive(serde::Serialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HistoryRequest {
+    /// Only changes within this zone.
+    #[prost(string, tag = "1")]
+    pub zone: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub name: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub r#type: ::prost::alloc::string::String,
+    #[prost(string, tag = "4")]
+    pub view: ::prost::alloc::string::String,
+    /// Only changes made by this actor, e.g. a client's address.
+    #[prost(string, tag = "5")]
+    pub actor: ::prost::alloc::string::String,
+    /// Unix timestamp, only changes made since.
+    #[prost(int64, tag = "6")]
+    pub since: i64,
+    /// At most this many changes, 100 if not set.
+    #[prost(uint32, tag = "7")]
+    pub limit: u32,
+}
+#[derive(serde::Serialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HistoryEntry {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub r#type: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub view: ::prost::alloc::string::String,
+    #[prost(enumeration = "HistoryAction", tag = "4")]
+    #[serde(
+        serialize_with = "crate::proto::serialize_enum::<crate::proto::HistoryAction, _>"
+    )]
+    pub action: i32,
+    /// Values before the change, empty if the RRset didn't exist.
+    #[prost(string, repeated, tag = "5")]
+    pub old_values: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// Values after the change, empty if the RRset was deleted or expired.
+    #[prost(string, repeated, tag = "6")]
+    pub new_values: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(uint32, tag = "7")]
+    pub ttl: u32,
+    #[prost(uint64, tag = "8")]
+    pub revision: u64,
+    /// Publisher that owned the RRset after the change.
+    #[prost(string, tag = "9")]
+    pub owner: ::prost::alloc::string::String,
+    /// Who made the change, the address of the API client. Empty for expiries.
+    #[prost(string, tag = "10")]
+    pub actor: ::prost::alloc::string::String,
+    /// Unix timestamp of the change.
+    #[prost(int64, tag = "11")]
+    pub created_at: i64,
+}
+#[derive(serde::Serialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HistoryReply {
+    /// Most recent first.
+    #[prost(message, repeated, tag = "1")]
+    pub entries: ::prost::alloc::vec::Vec<HistoryEntry>,
+}
+#[derive(serde::Serialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BackupRequest {}
+#[derive(serde::Serialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BackupReply {
+    /// Path of the backup on the server.
+    #[prost(string, tag = "1")]
+    pub path: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct IntegrityCheckRequest {}
+#[derive(serde::Serialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct IntegrityCheckReply {
+    #[prost(bool, tag = "1")]
+    pub ok: bool,
+    /// Problems found in the stored data, empty if it's ok.
+    #[prost(string, repeated, tag = "2")]
+    pub problems: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[derive(serde::Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum HealthFilter {
+    Any = 0,
+    Healthy = 1,
+    Unhealthy = 2,
+}
+impl HealthFilter {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            HealthFilter::Any => "HEALTH_FILTER_ANY",
+            HealthFilter::Healthy => "HEALTH_FILTER_HEALTHY",
+            HealthFilter::Unhealthy => "HEALTH_FILTER_UNHEALTHY",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "HEALTH_FILTER_ANY" => Some(Self::Any),
+            "HEALTH_FILTER_HEALTHY" => Some(Self::Healthy),
+            "HEALTH_FILTER_UNHEALTHY" => Some(Self::Unhealthy),
+            _ => None,
+        }
+    }
+}
+#[derive(serde::Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum RecordOrder {
+    Name = 0,
+    UpdatedAt = 1,
+    CreatedAt = 2,
+}
+impl RecordOrder {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            RecordOrder::Name => "RECORD_ORDER_NAME",
+            RecordOrder::UpdatedAt => "RECORD_ORDER_UPDATED_AT",
+            RecordOrder::CreatedAt => "RECORD_ORDER_CREATED_AT",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "RECORD_ORDER_NAME" => Some(Self::Name),
+            "RECORD_ORDER_UPDATED_AT" => Some(Self::UpdatedAt),
+            "RECORD_ORDER_CREATED_AT" => Some(Self::CreatedAt),
+            _ => None,
+        }
+    }
+}
+#[derive(serde::Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum ChangeAction {
+    Unchanged = 0,
+    Add = 1,
+    Update = 2,
+}
+impl ChangeAction {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            ChangeAction::Unchanged => "UNCHANGED",
+            ChangeAction::Add => "ADD",
+            ChangeAction::Update => "UPDATE",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "UNCHANGED" => Some(Self::Unchanged),
+            "ADD" => Some(Self::Add),
+            "UPDATE" => Some(Self::Update),
+            _ => None,
+        }
+    }
+}
+#[derive(serde::Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum HistoryAction {
+    Upsert = 0,
+    Delete = 1,
+    /// The RRset's lease ran out.
+    Expire = 2,
+}
+impl HistoryAction {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            HistoryAction::Upsert => "HISTORY_ACTION_UPSERT",
+            HistoryAction::Delete => "HISTORY_ACTION_DELETE",
+            HistoryAction::Expire => "HISTORY_ACTION_EXPIRE",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "HISTORY_ACTION_UPSERT" => Some(Self::Upsert),
+            "HISTORY_ACTION_DELETE" => Some(Self::Delete),
+            "HISTORY_ACTION_EXPIRE" => Some(Self::Expire),
+            _ => None,
+        }
+    }
 }
 /// Generated client implementations.
 pub mod ping_client {
@@ -308,6 +850,25 @@ pub mod records_client {
             req.extensions_mut().insert(GrpcMethod::new("swandns.Records", "List"));
             self.inner.server_streaming(req, path, codec).await
         }
+        pub async fn list_page(
+            &mut self,
+            request: impl tonic::IntoRequest<super::RecordsQueryRequest>,
+        ) -> std::result::Result<tonic::Response<super::RecordsPage>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/swandns.Records/ListPage");
+            let mut req = request.into_request();
+            req.extensions_mut().insert(GrpcMethod::new("swandns.Records", "ListPage"));
+            self.inner.unary(req, path, codec).await
+        }
         pub async fn delete(
             &mut self,
             request: impl tonic::IntoRequest<super::FindUniqueRecordRequest>,
@@ -327,27 +888,313 @@ pub mod records_client {
             req.extensions_mut().insert(GrpcMethod::new("swandns.Records", "Delete"));
             self.inner.unary(req, path, codec).await
         }
+        pub async fn import(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ImportRequest>,
+        ) -> std::result::Result<tonic::Response<super::ImportReply>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/swandns.Records/Import");
+            let mut req = request.into_request();
+            req.extensions_mut().insert(GrpcMethod::new("swandns.Records", "Import"));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn export(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ExportRequest>,
+        ) -> std::result::Result<tonic::Response<super::ExportReply>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/swandns.Records/Export");
+            let mut req = request.into_request();
+            req.extensions_mut().insert(GrpcMethod::new("swandns.Records", "Export"));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn keep_alive(
+            &mut self,
+            request: impl tonic::IntoRequest<super::KeepAliveRequest>,
+        ) -> std::result::Result<tonic::Response<super::KeepAliveReply>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/swandns.Records/KeepAlive",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut().insert(GrpcMethod::new("swandns.Records", "KeepAlive"));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn batch(
+            &mut self,
+            request: impl tonic::IntoRequest<super::BatchRequest>,
+        ) -> std::result::Result<tonic::Response<super::BatchReply>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/swandns.Records/Batch");
+            let mut req = request.into_request();
+            req.extensions_mut().insert(GrpcMethod::new("swandns.Records", "Batch"));
+            self.inner.unary(req, path, codec).await
+        }
     }
 }
-/// Generated server implementations.
-pub mod ping_server {
+/// Generated client implementations.
+pub mod admin_client {
     #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
     use tonic::codegen::*;
-    /// Generated trait containing gRPC methods that should be implemented for use with PingServer.
-    #[async_trait]
-    pub trait Ping: Send + Sync + 'static {
-        async fn ping(
-            &self,
-            request: tonic::Request<super::PingRequest>,
-        ) -> std::result::Result<tonic::Response<super::PingReply>, tonic::Status>;
+    use tonic::codegen::http::Uri;
+    #[derive(Debug, Clone)]
+    pub struct AdminClient<T> {
+        inner: tonic::client::Grpc<T>,
     }
-    #[derive(Debug)]
-    pub struct PingServer<T: Ping> {
-        inner: _Inner<T>,
-        accept_compression_encodings: EnabledCompressionEncodings,
-        send_compression_encodings: EnabledCompressionEncodings,
-        max_decoding_message_size: Option<usize>,
-        max_encoding_message_size: Option<usize>,
+    impl AdminClient<tonic::transport::Channel> {
+        /// Attempt to create a new client by connecting to a given endpoint.
+        pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
+        where
+            D: TryInto<tonic::transport::Endpoint>,
+            D::Error: Into<StdError>,
+        {
+            let conn = tonic::transport::Endpoint::new(dst)?.connect().await?;
+            Ok(Self::new(conn))
+        }
+    }
+    impl<T> AdminClient<T>
+    where
+        T: tonic::client::GrpcService<tonic::body::BoxBody>,
+        T::Error: Into<StdError>,
+        T::ResponseBody: Body<Data = Bytes> + Send + 'static,
+        <T::ResponseBody as Body>::Error: Into<StdError> + Send,
+    {
+        pub fn new(inner: T) -> Self {
+            let inner = tonic::client::Grpc::new(inner);
+            Self { inner }
+        }
+        pub fn with_origin(inner: T, origin: Uri) -> Self {
+            let inner = tonic::client::Grpc::with_origin(inner, origin);
+            Self { inner }
+        }
+        pub fn with_interceptor<F>(
+            inner: T,
+            interceptor: F,
+        ) -> AdminClient<InterceptedService<T, F>>
+        where
+            F: tonic::service::Interceptor,
+            T::ResponseBody: Default,
+            T: tonic::codegen::Service<
+                http::Request<tonic::body::BoxBody>,
+                Response = http::Response<
+                    <T as tonic::client::GrpcService<tonic::body::BoxBody>>::ResponseBody,
+                >,
+            >,
+            <T as tonic::codegen::Service<
+                http::Request<tonic::body::BoxBody>,
+            >>::Error: Into<StdError> + Send + Sync,
+        {
+            AdminClient::new(InterceptedService::new(inner, interceptor))
+        }
+        /// Compress requests with the given encoding.
+        ///
+        /// This requires the server to support it otherwise it might respond with an
+        /// error.
+        #[must_use]
+        pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.inner = self.inner.send_compressed(encoding);
+            self
+        }
+        /// Enable decompressing responses.
+        #[must_use]
+        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.inner = self.inner.accept_compressed(encoding);
+            self
+        }
+        /// Limits the maximum size of a decoded message.
+        ///
+        /// Default: `4MB`
+        #[must_use]
+        pub fn max_decoding_message_size(mut self, limit: usize) -> Self {
+            self.inner = self.inner.max_decoding_message_size(limit);
+            self
+        }
+        /// Limits the maximum size of an encoded message.
+        ///
+        /// Default: `usize::MAX`
+        #[must_use]
+        pub fn max_encoding_message_size(mut self, limit: usize) -> Self {
+            self.inner = self.inner.max_encoding_message_size(limit);
+            self
+        }
+        pub async fn info(
+            &mut self,
+            request: impl tonic::IntoRequest<super::InfoRequest>,
+        ) -> std::result::Result<tonic::Response<super::InfoReply>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/swandns.Admin/Info");
+            let mut req = request.into_request();
+            req.extensions_mut().insert(GrpcMethod::new("swandns.Admin", "Info"));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn zones(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ZonesRequest>,
+        ) -> std::result::Result<tonic::Response<super::ZonesReply>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/swandns.Admin/Zones");
+            let mut req = request.into_request();
+            req.extensions_mut().insert(GrpcMethod::new("swandns.Admin", "Zones"));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn conflicts(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ConflictsRequest>,
+        ) -> std::result::Result<tonic::Response<super::ConflictsReply>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/swandns.Admin/Conflicts");
+            let mut req = request.into_request();
+            req.extensions_mut().insert(GrpcMethod::new("swandns.Admin", "Conflicts"));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn history(
+            &mut self,
+            request: impl tonic::IntoRequest<super::HistoryRequest>,
+        ) -> std::result::Result<tonic::Response<super::HistoryReply>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/swandns.Admin/History");
+            let mut req = request.into_request();
+            req.extensions_mut().insert(GrpcMethod::new("swandns.Admin", "History"));
+            self.inner.unary(req, path, codec).await
+        }
+        /// Writes a consistent snapshot of the database to the backup directory.
+        pub async fn backup(
+            &mut self,
+            request: impl tonic::IntoRequest<super::BackupRequest>,
+        ) -> std::result::Result<tonic::Response<super::BackupReply>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/swandns.Admin/Backup");
+            let mut req = request.into_request();
+            req.extensions_mut().insert(GrpcMethod::new("swandns.Admin", "Backup"));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn integrity_check(
+            &mut self,
+            request: impl tonic::IntoRequest<super::IntegrityCheckRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::IntegrityCheckReply>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/swandns.Admin/IntegrityCheck",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("swandns.Admin", "IntegrityCheck"));
+            self.inner.unary(req, path, codec).await
+        }
+    }
+}
+/// Generated server implementations.
+pub mod ping_server {
+    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
+    use tonic::codegen::*;
+    /// Generated trait containing gRPC methods that should be implemented for use with PingServer.
+    #[async_trait]
+    pub trait Ping: Send + Sync + 'static {
+        async fn ping(
+            &self,
+            request: tonic::Request<super::PingRequest>,
+        ) -> std::result::Result<tonic::Response<super::PingReply>, tonic::Status>;
+    }
+    #[derive(Debug)]
+    pub struct PingServer<T: Ping> {
+        inner: _Inner<T>,
+        accept_compression_encodings: EnabledCompressionEncodings,
+        send_compression_encodings: EnabledCompressionEncodings,
+        max_decoding_message_size: Option<usize>,
+        max_encoding_message_size: Option<usize>,
     }
     struct _Inner<T>(Arc<T>);
     impl<T: Ping> PingServer<T> {
@@ -422,21 +1269,510 @@ pub mod ping_server {
             match req.uri().path() {
                 "/swandns.Ping/Ping" => {
                     #[allow(non_camel_case_types)]
-                    struct PingSvc<T: Ping>(pub Arc<T>);
-                    impl<T: Ping> tonic::server::UnaryService<super::PingRequest>
-                    for PingSvc<T> {
-                        type Response = super::PingReply;
+                    struct PingSvc<T: Ping>(pub Arc<T>);
+                    impl<T: Ping> tonic::server::UnaryService<super::PingRequest>
+                    for PingSvc<T> {
+                        type Response = super::PingReply;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::PingRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Ping>::ping(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = PingSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                _ => {
+                    Box::pin(async move {
+                        Ok(
+                            http::Response::builder()
+                                .status(200)
+                                .header("grpc-status", "12")
+                                .header("content-type", "application/grpc")
+                                .body(empty_body())
+                                .unwrap(),
+                        )
+                    })
+                }
+            }
+        }
+    }
+    impl<T: Ping> Clone for PingServer<T> {
+        fn clone(&self) -> Self {
+            let inner = self.inner.clone();
+            Self {
+                inner,
+                accept_compression_encodings: self.accept_compression_encodings,
+                send_compression_encodings: self.send_compression_encodings,
+                max_decoding_message_size: self.max_decoding_message_size,
+                max_encoding_message_size: self.max_encoding_message_size,
+            }
+        }
+    }
+    impl<T: Ping> Clone for _Inner<T> {
+        fn clone(&self) -> Self {
+            Self(Arc::clone(&self.0))
+        }
+    }
+    impl<T: std::fmt::Debug> std::fmt::Debug for _Inner<T> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{:?}", self.0)
+        }
+    }
+    impl<T: Ping> tonic::server::NamedService for PingServer<T> {
+        const NAME: &'static str = "swandns.Ping";
+    }
+}
+/// Generated server implementations.
+pub mod records_server {
+    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
+    use tonic::codegen::*;
+    /// Generated trait containing gRPC methods that should be implemented for use with RecordsServer.
+    #[async_trait]
+    pub trait Records: Send + Sync + 'static {
+        async fn find_unique(
+            &self,
+            request: tonic::Request<super::FindUniqueRecordRequest>,
+        ) -> std::result::Result<tonic::Response<super::RecordReply>, tonic::Status>;
+        async fn upsert(
+            &self,
+            request: tonic::Request<super::UpsertRecordRequest>,
+        ) -> std::result::Result<tonic::Response<super::RecordReply>, tonic::Status>;
+        /// Server streaming response type for the List method.
+        type ListStream: tonic::codegen::tokio_stream::Stream<
+                Item = std::result::Result<super::RecordReply, tonic::Status>,
+            >
+            + Send
+            + 'static;
+        async fn list(
+            &self,
+            request: tonic::Request<super::RecordsQueryRequest>,
+        ) -> std::result::Result<tonic::Response<Self::ListStream>, tonic::Status>;
+        async fn list_page(
+            &self,
+            request: tonic::Request<super::RecordsQueryRequest>,
+        ) -> std::result::Result<tonic::Response<super::RecordsPage>, tonic::Status>;
+        async fn delete(
+            &self,
+            request: tonic::Request<super::FindUniqueRecordRequest>,
+        ) -> std::result::Result<tonic::Response<super::EmptyReply>, tonic::Status>;
+        async fn import(
+            &self,
+            request: tonic::Request<super::ImportRequest>,
+        ) -> std::result::Result<tonic::Response<super::ImportReply>, tonic::Status>;
+        async fn export(
+            &self,
+            request: tonic::Request<super::ExportRequest>,
+        ) -> std::result::Result<tonic::Response<super::ExportReply>, tonic::Status>;
+        async fn keep_alive(
+            &self,
+            request: tonic::Request<super::KeepAliveRequest>,
+        ) -> std::result::Result<tonic::Response<super::KeepAliveReply>, tonic::Status>;
+        async fn batch(
+            &self,
+            request: tonic::Request<super::BatchRequest>,
+        ) -> std::result::Result<tonic::Response<super::BatchReply>, tonic::Status>;
+    }
+    #[derive(Debug)]
+    pub struct RecordsServer<T: Records> {
+        inner: _Inner<T>,
+        accept_compression_encodings: EnabledCompressionEncodings,
+        send_compression_encodings: EnabledCompressionEncodings,
+        max_decoding_message_size: Option<usize>,
+        max_encoding_message_size: Option<usize>,
+    }
+    struct _Inner<T>(Arc<T>);
+    impl<T: Records> RecordsServer<T> {
+        pub fn new(inner: T) -> Self {
+            Self::from_arc(Arc::new(inner))
+        }
+        pub fn from_arc(inner: Arc<T>) -> Self {
+            let inner = _Inner(inner);
+            Self {
+                inner,
+                accept_compression_encodings: Default::default(),
+                send_compression_encodings: Default::default(),
+                max_decoding_message_size: None,
+                max_encoding_message_size: None,
+            }
+        }
+        pub fn with_interceptor<F>(
+            inner: T,
+            interceptor: F,
+        ) -> InterceptedService<Self, F>
+        where
+            F: tonic::service::Interceptor,
+        {
+            InterceptedService::new(Self::new(inner), interceptor)
+        }
+        /// Enable decompressing requests with the given encoding.
+        #[must_use]
+        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.accept_compression_encodings.enable(encoding);
+            self
+        }
+        /// Compress responses with the given encoding, if the client supports it.
+        #[must_use]
+        pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.send_compression_encodings.enable(encoding);
+            self
+        }
+        /// Limits the maximum size of a decoded message.
+        ///
+        /// Default: `4MB`
+        #[must_use]
+        pub fn max_decoding_message_size(mut self, limit: usize) -> Self {
+            self.max_decoding_message_size = Some(limit);
+            self
+        }
+        /// Limits the maximum size of an encoded message.
+        ///
+        /// Default: `usize::MAX`
+        #[must_use]
+        pub fn max_encoding_message_size(mut self, limit: usize) -> Self {
+            self.max_encoding_message_size = Some(limit);
+            self
+        }
+    }
+    impl<T, B> tonic::codegen::Service<http::Request<B>> for RecordsServer<T>
+    where
+        T: Records,
+        B: Body + Send + 'static,
+        B::Error: Into<StdError> + Send + 'static,
+    {
+        type Response = http::Response<tonic::body::BoxBody>;
+        type Error = std::convert::Infallible;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+        fn poll_ready(
+            &mut self,
+            _cx: &mut Context<'_>,
+        ) -> Poll<std::result::Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+        fn call(&mut self, req: http::Request<B>) -> Self::Future {
+            let inner = self.inner.clone();
+            match req.uri().path() {
+                "/swandns.Records/FindUnique" => {
+                    #[allow(non_camel_case_types)]
+                    struct FindUniqueSvc<T: Records>(pub Arc<T>);
+                    impl<
+                        T: Records,
+                    > tonic::server::UnaryService<super::FindUniqueRecordRequest>
+                    for FindUniqueSvc<T> {
+                        type Response = super::RecordReply;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::FindUniqueRecordRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Records>::find_unique(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = FindUniqueSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/swandns.Records/Upsert" => {
+                    #[allow(non_camel_case_types)]
+                    struct UpsertSvc<T: Records>(pub Arc<T>);
+                    impl<
+                        T: Records,
+                    > tonic::server::UnaryService<super::UpsertRecordRequest>
+                    for UpsertSvc<T> {
+                        type Response = super::RecordReply;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::UpsertRecordRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Records>::upsert(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = UpsertSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/swandns.Records/List" => {
+                    #[allow(non_camel_case_types)]
+                    struct ListSvc<T: Records>(pub Arc<T>);
+                    impl<
+                        T: Records,
+                    > tonic::server::ServerStreamingService<super::RecordsQueryRequest>
+                    for ListSvc<T> {
+                        type Response = super::RecordReply;
+                        type ResponseStream = T::ListStream;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::ResponseStream>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::RecordsQueryRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Records>::list(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = ListSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/swandns.Records/ListPage" => {
+                    #[allow(non_camel_case_types)]
+                    struct ListPageSvc<T: Records>(pub Arc<T>);
+                    impl<
+                        T: Records,
+                    > tonic::server::UnaryService<super::RecordsQueryRequest>
+                    for ListPageSvc<T> {
+                        type Response = super::RecordsPage;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::RecordsQueryRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Records>::list_page(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = ListPageSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/swandns.Records/Delete" => {
+                    #[allow(non_camel_case_types)]
+                    struct DeleteSvc<T: Records>(pub Arc<T>);
+                    impl<
+                        T: Records,
+                    > tonic::server::UnaryService<super::FindUniqueRecordRequest>
+                    for DeleteSvc<T> {
+                        type Response = super::EmptyReply;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::FindUniqueRecordRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Records>::delete(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = DeleteSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/swandns.Records/Import" => {
+                    #[allow(non_camel_case_types)]
+                    struct ImportSvc<T: Records>(pub Arc<T>);
+                    impl<T: Records> tonic::server::UnaryService<super::ImportRequest>
+                    for ImportSvc<T> {
+                        type Response = super::ImportReply;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ImportRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Records>::import(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = ImportSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/swandns.Records/Export" => {
+                    #[allow(non_camel_case_types)]
+                    struct ExportSvc<T: Records>(pub Arc<T>);
+                    impl<T: Records> tonic::server::UnaryService<super::ExportRequest>
+                    for ExportSvc<T> {
+                        type Response = super::ExportReply;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::PingRequest>,
+                            request: tonic::Request<super::ExportRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as Ping>::ping(&inner, request).await
+                                <T as Records>::export(&inner, request).await
                             };
                             Box::pin(fut)
                         }
@@ -448,7 +1784,95 @@ pub mod ping_server {
                     let inner = self.inner.clone();
                     let fut = async move {
                         let inner = inner.0;
-                        let method = PingSvc(inner);
+                        let method = ExportSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/swandns.Records/KeepAlive" => {
+                    #[allow(non_camel_case_types)]
+                    struct KeepAliveSvc<T: Records>(pub Arc<T>);
+                    impl<T: Records> tonic::server::UnaryService<super::KeepAliveRequest>
+                    for KeepAliveSvc<T> {
+                        type Response = super::KeepAliveReply;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::KeepAliveRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Records>::keep_alive(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = KeepAliveSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/swandns.Records/Batch" => {
+                    #[allow(non_camel_case_types)]
+                    struct BatchSvc<T: Records>(pub Arc<T>);
+                    impl<T: Records> tonic::server::UnaryService<super::BatchRequest>
+                    for BatchSvc<T> {
+                        type Response = super::BatchReply;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::BatchRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Records>::batch(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = BatchSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -479,7 +1903,7 @@ pub mod ping_server {
             }
         }
     }
-    impl<T: Ping> Clone for PingServer<T> {
+    impl<T: Records> Clone for RecordsServer<T> {
         fn clone(&self) -> Self {
             let inner = self.inner.clone();
             Self {
@@ -491,7 +1915,7 @@ pub mod ping_server {
             }
         }
     }
-    impl<T: Ping> Clone for _Inner<T> {
+    impl<T: Records> Clone for _Inner<T> {
         fn clone(&self) -> Self {
             Self(Arc::clone(&self.0))
         }
@@ -501,42 +1925,48 @@ pub mod ping_server {
             write!(f, "{:?}", self.0)
         }
     }
-    impl<T: Ping> tonic::server::NamedService for PingServer<T> {
-        const NAME: &'static str = "swandns.Ping";
+    impl<T: Records> tonic::server::NamedService for RecordsServer<T> {
+        const NAME: &'static str = "swandns.Records";
     }
 }
 /// Generated server implementations.
-pub mod records_server {
+pub mod admin_server {
     #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
     use tonic::codegen::*;
-    /// Generated trait containing gRPC methods that should be implemented for use with RecordsServer.
+    /// Generated trait containing gRPC methods that should be implemented for use with AdminServer.
     #[async_trait]
-    pub trait Records: Send + Sync + 'static {
-        async fn find_unique(
+    pub trait Admin: Send + Sync + 'static {
+        async fn info(
             &self,
-            request: tonic::Request<super::FindUniqueRecordRequest>,
-        ) -> std::result::Result<tonic::Response<super::RecordReply>, tonic::Status>;
-        async fn upsert(
+            request: tonic::Request<super::InfoRequest>,
+        ) -> std::result::Result<tonic::Response<super::InfoReply>, tonic::Status>;
+        async fn zones(
             &self,
-            request: tonic::Request<super::UpsertRecordRequest>,
-        ) -> std::result::Result<tonic::Response<super::RecordReply>, tonic::Status>;
-        /// Server streaming response type for the List method.
-        type ListStream: tonic::codegen::tokio_stream::Stream<
-                Item = std::result::Result<super::RecordReply, tonic::Status>,
-            >
-            + Send
-            + 'static;
-        async fn list(
+            request: tonic::Request<super::ZonesRequest>,
+        ) -> std::result::Result<tonic::Response<super::ZonesReply>, tonic::Status>;
+        async fn conflicts(
             &self,
-            request: tonic::Request<super::RecordsQueryRequest>,
-        ) -> std::result::Result<tonic::Response<Self::ListStream>, tonic::Status>;
-        async fn delete(
+            request: tonic::Request<super::ConflictsRequest>,
+        ) -> std::result::Result<tonic::Response<super::ConflictsReply>, tonic::Status>;
+        async fn history(
             &self,
-            request: tonic::Request<super::FindUniqueRecordRequest>,
-        ) -> std::result::Result<tonic::Response<super::EmptyReply>, tonic::Status>;
+            request: tonic::Request<super::HistoryRequest>,
+        ) -> std::result::Result<tonic::Response<super::HistoryReply>, tonic::Status>;
+        /// Writes a consistent snapshot of the database to the backup directory.
+        async fn backup(
+            &self,
+            request: tonic::Request<super::BackupRequest>,
+        ) -> std::result::Result<tonic::Response<super::BackupReply>, tonic::Status>;
+        async fn integrity_check(
+            &self,
+            request: tonic::Request<super::IntegrityCheckRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::IntegrityCheckReply>,
+            tonic::Status,
+        >;
     }
     #[derive(Debug)]
-    pub struct RecordsServer<T: Records> {
+    pub struct AdminServer<T: Admin> {
         inner: _Inner<T>,
         accept_compression_encodings: EnabledCompressionEncodings,
         send_compression_encodings: EnabledCompressionEncodings,
@@ -544,7 +1974,7 @@ pub mod records_server {
         max_encoding_message_size: Option<usize>,
     }
     struct _Inner<T>(Arc<T>);
-    impl<T: Records> RecordsServer<T> {
+    impl<T: Admin> AdminServer<T> {
         pub fn new(inner: T) -> Self {
             Self::from_arc(Arc::new(inner))
         }
@@ -596,9 +2026,9 @@ pub mod records_server {
             self
         }
     }
-    impl<T, B> tonic::codegen::Service<http::Request<B>> for RecordsServer<T>
+    impl<T, B> tonic::codegen::Service<http::Request<B>> for AdminServer<T>
     where
-        T: Records,
+        T: Admin,
         B: Body + Send + 'static,
         B::Error: Into<StdError> + Send + 'static,
     {
@@ -614,25 +2044,23 @@ pub mod records_server {
         fn call(&mut self, req: http::Request<B>) -> Self::Future {
             let inner = self.inner.clone();
             match req.uri().path() {
-                "/swandns.Records/FindUnique" => {
+                "/swandns.Admin/Info" => {
                     #[allow(non_camel_case_types)]
-                    struct FindUniqueSvc<T: Records>(pub Arc<T>);
-                    impl<
-                        T: Records,
-                    > tonic::server::UnaryService<super::FindUniqueRecordRequest>
-                    for FindUniqueSvc<T> {
-                        type Response = super::RecordReply;
+                    struct InfoSvc<T: Admin>(pub Arc<T>);
+                    impl<T: Admin> tonic::server::UnaryService<super::InfoRequest>
+                    for InfoSvc<T> {
+                        type Response = super::InfoReply;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::FindUniqueRecordRequest>,
+                            request: tonic::Request<super::InfoRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as Records>::find_unique(&inner, request).await
+                                <T as Admin>::info(&inner, request).await
                             };
                             Box::pin(fut)
                         }
@@ -644,7 +2072,7 @@ pub mod records_server {
                     let inner = self.inner.clone();
                     let fut = async move {
                         let inner = inner.0;
-                        let method = FindUniqueSvc(inner);
+                        let method = InfoSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -660,25 +2088,23 @@ pub mod records_server {
                     };
                     Box::pin(fut)
                 }
-                "/swandns.Records/Upsert" => {
+                "/swandns.Admin/Zones" => {
                     #[allow(non_camel_case_types)]
-                    struct UpsertSvc<T: Records>(pub Arc<T>);
-                    impl<
-                        T: Records,
-                    > tonic::server::UnaryService<super::UpsertRecordRequest>
-                    for UpsertSvc<T> {
-                        type Response = super::RecordReply;
+                    struct ZonesSvc<T: Admin>(pub Arc<T>);
+                    impl<T: Admin> tonic::server::UnaryService<super::ZonesRequest>
+                    for ZonesSvc<T> {
+                        type Response = super::ZonesReply;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::UpsertRecordRequest>,
+                            request: tonic::Request<super::ZonesRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as Records>::upsert(&inner, request).await
+                                <T as Admin>::zones(&inner, request).await
                             };
                             Box::pin(fut)
                         }
@@ -690,7 +2116,7 @@ pub mod records_server {
                     let inner = self.inner.clone();
                     let fut = async move {
                         let inner = inner.0;
-                        let method = UpsertSvc(inner);
+                        let method = ZonesSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -706,26 +2132,23 @@ pub mod records_server {
                     };
                     Box::pin(fut)
                 }
-                "/swandns.Records/List" => {
+                "/swandns.Admin/Conflicts" => {
                     #[allow(non_camel_case_types)]
-                    struct ListSvc<T: Records>(pub Arc<T>);
-                    impl<
-                        T: Records,
-                    > tonic::server::ServerStreamingService<super::RecordsQueryRequest>
-                    for ListSvc<T> {
-                        type Response = super::RecordReply;
-                        type ResponseStream = T::ListStream;
+                    struct ConflictsSvc<T: Admin>(pub Arc<T>);
+                    impl<T: Admin> tonic::server::UnaryService<super::ConflictsRequest>
+                    for ConflictsSvc<T> {
+                        type Response = super::ConflictsReply;
                         type Future = BoxFuture<
-                            tonic::Response<Self::ResponseStream>,
+                            tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::RecordsQueryRequest>,
+                            request: tonic::Request<super::ConflictsRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as Records>::list(&inner, request).await
+                                <T as Admin>::conflicts(&inner, request).await
                             };
                             Box::pin(fut)
                         }
@@ -737,7 +2160,7 @@ pub mod records_server {
                     let inner = self.inner.clone();
                     let fut = async move {
                         let inner = inner.0;
-                        let method = ListSvc(inner);
+                        let method = ConflictsSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -748,30 +2171,118 @@ pub mod records_server {
                                 max_decoding_message_size,
                                 max_encoding_message_size,
                             );
-                        let res = grpc.server_streaming(method, req).await;
+                        let res = grpc.unary(method, req).await;
                         Ok(res)
                     };
                     Box::pin(fut)
                 }
-                "/swandns.Records/Delete" => {
+                "/swandns.Admin/History" => {
                     #[allow(non_camel_case_types)]
-                    struct DeleteSvc<T: Records>(pub Arc<T>);
+                    struct HistorySvc<T: Admin>(pub Arc<T>);
+                    impl<T: Admin> tonic::server::UnaryService<super::HistoryRequest>
+                    for HistorySvc<T> {
+                        type Response = super::HistoryReply;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::HistoryRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Admin>::history(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = HistorySvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/swandns.Admin/Backup" => {
+                    #[allow(non_camel_case_types)]
+                    struct BackupSvc<T: Admin>(pub Arc<T>);
+                    impl<T: Admin> tonic::server::UnaryService<super::BackupRequest>
+                    for BackupSvc<T> {
+                        type Response = super::BackupReply;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::BackupRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Admin>::backup(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = BackupSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/swandns.Admin/IntegrityCheck" => {
+                    #[allow(non_camel_case_types)]
+                    struct IntegrityCheckSvc<T: Admin>(pub Arc<T>);
                     impl<
-                        T: Records,
-                    > tonic::server::UnaryService<super::FindUniqueRecordRequest>
-                    for DeleteSvc<T> {
-                        type Response = super::EmptyReply;
+                        T: Admin,
+                    > tonic::server::UnaryService<super::IntegrityCheckRequest>
+                    for IntegrityCheckSvc<T> {
+                        type Response = super::IntegrityCheckReply;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::FindUniqueRecordRequest>,
+                            request: tonic::Request<super::IntegrityCheckRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as Records>::delete(&inner, request).await
+                                <T as Admin>::integrity_check(&inner, request).await
                             };
                             Box::pin(fut)
                         }
@@ -783,7 +2294,7 @@ pub mod records_server {
                     let inner = self.inner.clone();
                     let fut = async move {
                         let inner = inner.0;
-                        let method = DeleteSvc(inner);
+                        let method = IntegrityCheckSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -814,7 +2325,7 @@ pub mod records_server {
             }
         }
     }
-    impl<T: Records> Clone for RecordsServer<T> {
+    impl<T: Admin> Clone for AdminServer<T> {
         fn clone(&self) -> Self {
             let inner = self.inner.clone();
             Self {
@@ -826,7 +2337,7 @@ pub mod records_server {
             }
         }
     }
-    impl<T: Records> Clone for _Inner<T> {
+    impl<T: Admin> Clone for _Inner<T> {
         fn clone(&self) -> Self {
             Self(Arc::clone(&self.0))
         }
@@ -836,7 +2347,7 @@ pub mod records_server {
             write!(f, "{:?}", self.0)
         }
     }
-    impl<T: Records> tonic::server::NamedService for RecordsServer<T> {
-        const NAME: &'static str = "swandns.Records";
+    impl<T: Admin> tonic::server::NamedService for AdminServer<T> {
+        const NAME: &'static str = "swandns.Admin";
     }
 }
@@ -0,0 +1,90 @@
+use crate::util::{canonical_ip, client_network};
+use crate::RateLimitConfig;
+use ipnet::IpNet;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Number of tracked clients before expired windows are pruned.
+static PRUNE_THRESHOLD: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitAction {
+    /// Answer the request normally.
+    Allow,
+    /// Send an empty truncated response so legitimate clients retry over TCP.
+    Slip,
+    /// Don't respond at all.
+    Drop,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    window_start: Instant,
+    responses: u32,
+    limited: u32,
+}
+
+/// Per-client response rate limiting, with clients grouped by network prefix.
+#[derive(Debug)]
+pub struct ResponseRateLimiter {
+    cfg: RateLimitConfig,
+    buckets: Mutex<HashMap<IpNet, Bucket>>,
+}
+
+impl ResponseRateLimiter {
+    pub fn new(cfg: RateLimitConfig) -> Self {
+        Self {
+            cfg,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn client_network(&self, ip_addr: IpAddr) -> IpNet {
+        client_network(
+            ip_addr,
+            self.cfg.ipv4_prefix_length,
+            self.cfg.ipv6_prefix_length,
+        )
+    }
+
+    pub fn check(&self, ip_addr: IpAddr) -> RateLimitAction {
+        self.check_at(ip_addr, Instant::now())
+    }
+
+    pub fn check_at(&self, ip_addr: IpAddr, now: Instant) -> RateLimitAction {
+        let window = Duration::from_secs(self.cfg.window.max(1));
+        let limit = self
+            .cfg
+            .responses_per_second
+            .saturating_mul(window.as_secs() as u32);
+        let network = self.client_network(canonical_ip(ip_addr));
+
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() > PRUNE_THRESHOLD {
+            buckets.retain(|_, bucket| now.duration_since(bucket.window_start) < window);
+        }
+        let bucket = buckets.entry(network).or_insert(Bucket {
+            window_start: now,
+            responses: 0,
+            limited: 0,
+        });
+        if now.duration_since(bucket.window_start) >= window {
+            bucket.window_start = now;
+            bucket.responses = 0;
+            bucket.limited = 0;
+        }
+
+        bucket.responses = bucket.responses.saturating_add(1);
+        if bucket.responses <= limit {
+            return RateLimitAction::Allow;
+        }
+        bucket.limited = bucket.limited.saturating_add(1);
+        if self.cfg.slip > 0 && bucket.limited % self.cfg.slip == 0 {
+            RateLimitAction::Slip
+        } else {
+            RateLimitAction::Drop
+        }
+    }
+}
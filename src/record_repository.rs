@@ -1,130 +1,1061 @@
-use crate::proto::{EmptyReply, FindUniqueRecordRequest, RecordReply, UpsertRecordRequest};
-use anyhow::Result;
-use tokio_rusqlite::params;
+use crate::proto::error_details::FieldViolation;
+use crate::proto::record_operation::Operation;
+use crate::proto::{
+    BatchReply, Conflict, FindUniqueRecordRequest, HealthFilter, HistoryAction, HistoryEntry,
+    HistoryRequest, LeaseRenewal, RecordOperation, RecordOrder, RecordReply, RecordsPage,
+    RecordsQueryRequest, UpsertRecordRequest,
+};
+use crate::record_store::{
+    check_owner, current_actor, decode_page_token, encode_page_token, is_healthy, operation_key,
+    upsert_key, validate_batch_shape, validate_rrset_shape, OwnerConflict, RecordError,
+    RecordStore, DEFAULT_HISTORY_LIMIT, DEFAULT_LEASE, MAX_CONFLICTS,
+};
+use rusqlite::DatabaseName;
+use std::path::PathBuf;
 use std::sync::Arc;
-use time::{Duration, OffsetDateTime};
-use tokio_rusqlite::Connection;
+use time::OffsetDateTime;
+use tokio_rusqlite::params;
+use tokio_rusqlite::types::{ToSql, ToSqlOutput, Value};
+use tokio_rusqlite::{params_from_iter, Connection, ErrorCode, Transaction};
+use tracing::warn;
+
+type Result<T, E = RecordError> = std::result::Result<T, E>;
+
+impl From<tokio_rusqlite::Error> for RecordError {
+    fn from(err: tokio_rusqlite::Error) -> Self {
+        match &err {
+            tokio_rusqlite::Error::Rusqlite(sqlite_err)
+                if matches!(
+                    sqlite_err.sqlite_error_code(),
+                    Some(ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked)
+                ) =>
+            {
+                RecordError::Conflict(err.into())
+            }
+            _ => RecordError::Storage(err.into()),
+        }
+    }
+}
 
-static HEALTHY_AGE: Duration = Duration::minutes(7);
+/// Converts a value to what it's stored as, e.g. to compare timestamps with stored ones.
+fn sql_value(value: impl ToSql) -> Value {
+    match value.to_sql() {
+        Ok(ToSqlOutput::Owned(value)) => value,
+        Ok(ToSqlOutput::Borrowed(value)) => value.into(),
+        _ => Value::Null,
+    }
+}
 
+/// The default store, an SQLite database.
 #[derive(Debug)]
 pub struct RecordRepository {
     pub conn: Arc<Connection>,
 }
 
+/// Merges the rows of each RRset into a single record.
+///
+/// Rows have to be ordered so the rows of an RRset are next to each other.
+fn group_records(rows: Vec<RecordReply>) -> Vec<RecordReply> {
+    let mut records: Vec<RecordReply> = vec![];
+    for row in rows {
+        match records.last_mut() {
+            Some(record)
+                if record.name == row.name
+                    && record.r#type == row.r#type
+                    && record.view == row.view =>
+            {
+                record.values.push(row.data);
+                record.created_at = record.created_at.min(row.created_at);
+                record.updated_at = record.updated_at.max(row.updated_at);
+                record.healthy = record.healthy || row.healthy;
+                record.revision = record.revision.max(row.revision);
+            }
+            _ => records.push(row),
+        }
+    }
+    records
+}
+
+/// Runs SQLite's `integrity_check`, which reports a single `ok` row if there's nothing wrong.
+pub(crate) fn integrity_problems(
+    conn: &mut rusqlite::Connection,
+) -> tokio_rusqlite::Result<Vec<String>> {
+    let mut stmt = conn.prepare("PRAGMA integrity_check")?;
+    let problems = stmt
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<Vec<String>, _>>()?;
+    Ok(problems
+        .into_iter()
+        .filter(|problem| problem != "ok")
+        .collect())
+}
+
+/// Checks whether an upsert or delete would change an RRset that another publisher still holds.
+fn owner_conflict(
+    tx: &Transaction,
+    request: &FindUniqueRecordRequest,
+) -> tokio_rusqlite::Result<Option<OwnerConflict>> {
+    let mut stmt = tx.prepare(
+        r#"
+SELECT owner, static, updated_at, lease
+FROM records
+WHERE name = ?1
+  AND type = ?2
+  AND view = ?3
+LIMIT 1"#,
+    )?;
+    let existing = stmt
+        .query_map(params![request.name, request.r#type, request.view], |row| {
+            let owner: String = row.get(0)?;
+            let healthy = is_healthy(row.get(1)?, row.get(2)?, row.get(3)?);
+            Ok((owner, healthy))
+        })?
+        .next()
+        .transpose()?;
+    Ok(existing.and_then(|(owner, healthy)| check_owner(request, owner, healthy)))
+}
+
+/// Remembers a rejected change, forgetting the oldest ones beyond [`MAX_CONFLICTS`].
+fn record_conflict(
+    tx: &Transaction,
+    conflict: &OwnerConflict,
+    now: OffsetDateTime,
+) -> tokio_rusqlite::Result<()> {
+    warn!("Rejected change: {}", conflict);
+    tx.execute(
+        r#"
+INSERT INTO conflicts (name, type, view, owner, challenger, created_at)
+VALUES (?1, ?2, ?3, ?4, ?5, ?6)"#,
+        params![
+            conflict.name,
+            conflict.r#type,
+            conflict.view,
+            conflict.owner,
+            conflict.challenger,
+            now
+        ],
+    )?;
+    tx.execute(
+        "DELETE FROM conflicts WHERE rowid <= (SELECT MAX(rowid) FROM conflicts) - ?1",
+        params![MAX_CONFLICTS as i64],
+    )?;
+    Ok(())
+}
+
+fn history_action(action: HistoryAction) -> &'static str {
+    match action {
+        HistoryAction::Upsert => "upsert",
+        HistoryAction::Delete => "delete",
+        HistoryAction::Expire => "expire",
+    }
+}
+
+fn parse_history_action(action: &str) -> HistoryAction {
+    match action {
+        "delete" => HistoryAction::Delete,
+        "expire" => HistoryAction::Expire,
+        _ => HistoryAction::Upsert,
+    }
+}
+
+/// Appends a change to the history. Values are stored as JSON arrays.
+fn record_history(
+    tx: &Transaction,
+    entry: &HistoryEntry,
+    now: OffsetDateTime,
+) -> tokio_rusqlite::Result<()> {
+    tx.execute(
+        r#"
+INSERT INTO history (name, type, view, action, old_values, new_values, ttl, revision, owner, actor, created_at)
+VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)"#,
+        params![
+            entry.name,
+            entry.r#type,
+            entry.view,
+            history_action(entry.action()),
+            serde_json::to_string(&entry.old_values).unwrap_or_default(),
+            serde_json::to_string(&entry.new_values).unwrap_or_default(),
+            entry.ttl,
+            entry.revision,
+            entry.owner,
+            entry.actor,
+            now
+        ],
+    )?;
+    Ok(())
+}
+
+/// Replaces an RRset within a transaction, bumping its revision if anything changed.
+///
+/// Changes are recorded in the history as made by `actor`, refreshes of an unchanged RRset aren't.
+fn upsert_rrset(
+    tx: &Transaction,
+    request: UpsertRecordRequest,
+    now: OffsetDateTime,
+    actor: &str,
+) -> tokio_rusqlite::Result<()> {
+    let name = request.name;
+    let r#type = request.r#type;
+    let view = request.view;
+    let values = if request.values.is_empty() {
+        vec![request.value]
+    } else {
+        request.values
+    };
+    let ttl = request.ttl;
+    let static_ = request.r#static;
+    let lease = if request.lease == 0 {
+        DEFAULT_LEASE
+    } else {
+        request.lease
+    };
+    let owner = request.owner;
+    let existing = {
+        let mut stmt = tx.prepare(
+            r#"
+SELECT data, ttl, static, revision
+FROM records
+WHERE name = ?1
+  AND type = ?2
+  AND view = ?3
+ORDER BY rowid"#,
+        )?;
+        let existing = stmt
+            .query_map(params![name, r#type, view], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })?
+            .collect::<Result<Vec<(String, u32, bool, u64)>, _>>()?;
+        existing
+    };
+    let existing_owner: Option<String> = tx
+        .query_row(
+            "SELECT owner FROM records WHERE name = ?1 AND type = ?2 AND view = ?3 LIMIT 1",
+            params![name, r#type, view],
+            |row| row.get(0),
+        )
+        .ok();
+    // Refreshing an unchanged RRset keeps its revision, so clients can keep it alive.
+    let changed = existing.len() != values.len()
+        || existing
+            .iter()
+            .any(|(data, existing_ttl, existing_static, _)| {
+                !values.contains(data) || *existing_ttl != ttl || *existing_static != static_
+            });
+    let revision = existing
+        .iter()
+        .map(|(_, _, _, revision)| *revision)
+        .max()
+        .map_or(1, |revision| if changed { revision + 1 } else { revision });
+    // Values that aren't in the request anymore.
+    let removed = existing
+        .iter()
+        .map(|(data, _, _, _)| data)
+        .filter(|data| !values.contains(data));
+    for data in removed {
+        tx.execute(
+            "DELETE FROM records WHERE name = ?1 AND type = ?2 AND view = ?3 AND data = ?4",
+            params![name, r#type, view, data],
+        )?;
+    }
+    for data in values.iter() {
+        tx.execute(
+            r#"
+INSERT INTO records (name,
+                     type,
+                     view,
+                     data,
+                     ttl,
+                     static,
+                     created_at,
+                     updated_at,
+                     revision,
+                     lease,
+                     owner)
+VALUES (?1,
+        ?2,
+        ?3,
+        ?4,
+        ?5,
+        ?6,
+        ?7,
+        ?8,
+        ?9,
+        ?10,
+        ?11)
+ON CONFLICT(name, type, view, data)
+    DO UPDATE SET ttl        = excluded.ttl,
+                  static     = excluded.static,
+                  updated_at = excluded.updated_at,
+                  revision   = excluded.revision,
+                  lease      = excluded.lease,
+                  owner      = excluded.owner"#,
+            params![name, r#type, view, data, ttl, static_, now, now, revision, lease, owner],
+        )?;
+    }
+    if existing.is_empty() || changed || existing_owner.as_ref() != Some(&owner) {
+        let entry = HistoryEntry {
+            name,
+            r#type,
+            view,
+            action: HistoryAction::Upsert as i32,
+            old_values: existing.into_iter().map(|(data, _, _, _)| data).collect(),
+            new_values: values,
+            ttl,
+            revision,
+            owner,
+            actor: actor.to_string(),
+            ..Default::default()
+        };
+        record_history(tx, &entry, now)?;
+    }
+    Ok(())
+}
+
+fn delete_rrset(
+    tx: &Transaction,
+    request: &FindUniqueRecordRequest,
+    now: OffsetDateTime,
+    actor: &str,
+) -> tokio_rusqlite::Result<()> {
+    let existing = {
+        let mut stmt = tx.prepare(
+            r#"
+SELECT data, ttl, revision, owner
+FROM records
+WHERE name = ?1
+  AND type = ?2
+  AND view = ?3
+ORDER BY rowid"#,
+        )?;
+        let existing = stmt
+            .query_map(params![request.name, request.r#type, request.view], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })?
+            .collect::<Result<Vec<(String, u32, u64, String)>, _>>()?;
+        existing
+    };
+    tx.execute(
+        "DELETE FROM records WHERE name = ?1 AND type = ?2 AND view = ?3",
+        params![request.name, request.r#type, request.view],
+    )?;
+    if let Some((_, ttl, revision, owner)) = existing.first().cloned() {
+        let entry = HistoryEntry {
+            name: request.name.clone(),
+            r#type: request.r#type.clone(),
+            view: request.view.clone(),
+            action: HistoryAction::Delete as i32,
+            old_values: existing.into_iter().map(|(data, _, _, _)| data).collect(),
+            ttl,
+            revision,
+            owner,
+            actor: actor.to_string(),
+            ..Default::default()
+        };
+        record_history(tx, &entry, now)?;
+    }
+    Ok(())
+}
+
 impl RecordRepository {
-    pub async fn find_unique(&self, request: FindUniqueRecordRequest) -> Result<RecordReply> {
+    /// Finds an RRset, or returns one without values and revision 0 if it doesn't exist.
+    async fn find_or_missing(&self, request: FindUniqueRecordRequest) -> Result<RecordReply> {
+        match self.find_unique(request.clone()).await {
+            Err(RecordError::NotFound) => Ok(RecordReply {
+                name: request.name,
+                r#type: request.r#type,
+                view: request.view,
+                ..Default::default()
+            }),
+            result => result,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl RecordStore for RecordRepository {
+    async fn find_unique(&self, request: FindUniqueRecordRequest) -> Result<RecordReply> {
         let name = request.name;
         let r#type = request.r#type.clone();
-        let record = self
+        let view = request.view;
+        let rows = self
             .conn
             .call(move |conn| {
                 let mut stmt = conn.prepare(
                     r#"
-SELECT name, type, data, ttl, created_at, updated_at
+SELECT name, type, data, ttl, created_at, updated_at, view, static, revision, lease, owner
 FROM records
 WHERE name = ?1
-  AND type = ?2"#,
+  AND type = ?2
+  AND view = ?3
+ORDER BY rowid"#,
                 )?;
-                Ok(stmt.query_row([name, r#type], |row| {
-                    let created_at: OffsetDateTime = row.get(4)?;
-                    let updated_at: OffsetDateTime = row.get(5)?;
-                    Ok(RecordReply {
-                        name: row.get(0)?,
-                        r#type: row.get(1)?,
-                        data: row.get(2)?,
-                        ttl: row.get(3)?,
-                        created_at: created_at.unix_timestamp(),
-                        updated_at: updated_at.unix_timestamp(),
-                        healthy: true,
-                    })
-                }))
+                let rows = stmt
+                    .query_map([name, r#type, view], |row| {
+                        let created_at: OffsetDateTime = row.get(4)?;
+                        let updated_at: OffsetDateTime = row.get(5)?;
+                        let static_: bool = row.get(7)?;
+                        let lease: u32 = row.get(9)?;
+                        let data: String = row.get(2)?;
+                        Ok(RecordReply {
+                            name: row.get(0)?,
+                            r#type: row.get(1)?,
+                            data: data.clone(),
+                            ttl: row.get(3)?,
+                            created_at: created_at.unix_timestamp(),
+                            updated_at: updated_at.unix_timestamp(),
+                            healthy: is_healthy(static_, updated_at, lease),
+                            view: row.get(6)?,
+                            r#static: static_,
+                            values: vec![data],
+                            revision: row.get(8)?,
+                            lease,
+                            owner: row.get(10)?,
+                        })
+                    })?
+                    .collect::<Result<Vec<RecordReply>, _>>()?;
+                Ok(rows)
             })
             .await?;
-        Ok(record?)
+        group_records(rows)
+            .into_iter()
+            .next()
+            .ok_or(RecordError::NotFound)
     }
 
-    pub async fn upsert(&self, request: UpsertRecordRequest) -> Result<RecordReply> {
-        let name = request.name.clone();
-        let name2 = name.clone();
-        let r#type = request.r#type;
-        let r#type2 = r#type.clone();
-        let data = request.value;
-        let ttl = request.ttl;
-        let now = OffsetDateTime::now_utc();
+    async fn find_for_view(
+        &self,
+        name: String,
+        r#type: String,
+        view: String,
+    ) -> Result<RecordReply> {
+        let rows = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    r#"
+SELECT name, type, data, ttl, created_at, updated_at, view, static, revision, lease, owner
+FROM records
+WHERE name = ?1
+  AND type = ?2
+  AND view = (SELECT view
+              FROM records
+              WHERE name = ?1
+                AND type = ?2
+                AND view IN (?3, '')
+              ORDER BY view = ?3 DESC
+              LIMIT 1)
+ORDER BY rowid"#,
+                )?;
+                let rows = stmt
+                    .query_map([name, r#type, view], |row| {
+                        let created_at: OffsetDateTime = row.get(4)?;
+                        let updated_at: OffsetDateTime = row.get(5)?;
+                        let static_: bool = row.get(7)?;
+                        let lease: u32 = row.get(9)?;
+                        let data: String = row.get(2)?;
+                        Ok(RecordReply {
+                            name: row.get(0)?,
+                            r#type: row.get(1)?,
+                            data: data.clone(),
+                            ttl: row.get(3)?,
+                            created_at: created_at.unix_timestamp(),
+                            updated_at: updated_at.unix_timestamp(),
+                            healthy: is_healthy(static_, updated_at, lease),
+                            view: row.get(6)?,
+                            r#static: static_,
+                            values: vec![data],
+                            revision: row.get(8)?,
+                            lease,
+                            owner: row.get(10)?,
+                        })
+                    })?
+                    .collect::<Result<Vec<RecordReply>, _>>()?;
+                Ok(rows)
+            })
+            .await?;
+        group_records(rows)
+            .into_iter()
+            .find(|record| record.healthy)
+            .ok_or(RecordError::NotFound)
+    }
 
-        self.conn
+    async fn has_name(&self, name: String, view: String) -> Result<bool> {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let exists = self
+            .conn
             .call(move |conn| {
-                conn.execute(
+                let exists = conn.query_row(
                     r#"
-INSERT INTO records (name,
-                     type,
-                     data,
-                     ttl,
-                     created_at,
-                     updated_at)
-VALUES (?1,
-        ?2,
-        ?3,
-        ?4,
-        ?5,
-        ?6)
-ON CONFLICT(name, type)
-    DO UPDATE SET data       = excluded.data,
-                  ttl        = excluded.ttl,
-                  updated_at = excluded.updated_at"#,
-                    params![name, r#type, data, ttl, now, now],
+SELECT EXISTS(SELECT 1
+              FROM records
+              WHERE (name = ?1 OR substr(name, -length(?1) - 1) = '.' || ?1)
+                AND view IN (?2, '')
+                AND (static OR unixepoch(updated_at) + lease >= ?3))"#,
+                    params![name, view, now],
+                    |row| row.get(0),
                 )?;
-                Ok(())
+                Ok(exists)
             })
             .await?;
+        Ok(exists)
+    }
 
-        self.find_unique(FindUniqueRecordRequest {
-            name: name2,
-            r#type: r#type2,
-        })
-        .await
+    async fn upsert(&self, request: UpsertRecordRequest) -> Result<RecordReply> {
+        let violations = validate_rrset_shape(&request, "");
+        if !violations.is_empty() {
+            return Err(RecordError::Invalid(violations));
+        }
+        let find = upsert_key(&request);
+        let key = find.clone();
+        let now = OffsetDateTime::now_utc();
+        let actor = current_actor();
+
+        let conflict = self
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let conflict = owner_conflict(&tx, &key)?;
+                match conflict {
+                    Some(ref conflict) => record_conflict(&tx, conflict, now)?,
+                    None => upsert_rrset(&tx, request, now, &actor)?,
+                }
+                tx.commit()?;
+                Ok(conflict)
+            })
+            .await?;
+        if let Some(conflict) = conflict {
+            return Err(conflict.into());
+        }
+
+        self.find_unique(find).await
     }
 
-    pub async fn list(&self) -> Result<Vec<RecordReply>> {
-        let records = self
+    async fn batch(&self, operations: Vec<RecordOperation>) -> Result<BatchReply> {
+        let violations = validate_batch_shape(&operations);
+        if !violations.is_empty() {
+            return Err(RecordError::Invalid(violations));
+        }
+        let mut preconditions = vec![];
+        let mut upserted = vec![];
+        for operation in operations.iter() {
+            let Some(key) = operation_key(operation) else {
+                continue;
+            };
+            if let Some(Operation::Upsert(_)) = operation.operation {
+                upserted.push(key.clone());
+            }
+            if operation.if_revision != 0 {
+                preconditions.push((key, operation.if_revision));
+            }
+        }
+        let now = OffsetDateTime::now_utc();
+        let actor = current_actor();
+
+        let (conflicts, owner_conflicts) = self
             .conn
-            .call(|conn| {
-                let mut stmt = conn
-                    .prepare("SELECT name, type, data, ttl, created_at, updated_at FROM records")?;
-                let records = stmt
-                    .query_map([], |row| {
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let mut owner_conflicts = vec![];
+                for key in operations.iter().filter_map(operation_key) {
+                    if let Some(conflict) = owner_conflict(&tx, &key)? {
+                        record_conflict(&tx, &conflict, now)?;
+                        owner_conflicts.push(conflict);
+                    }
+                }
+                // Nothing but the conflicts has been written yet.
+                if !owner_conflicts.is_empty() {
+                    tx.commit()?;
+                    return Ok((vec![], owner_conflicts));
+                }
+                let mut conflicts = vec![];
+                for (key, if_revision) in preconditions {
+                    let revision: Option<u64> = tx.query_row(
+                        "SELECT MAX(revision) FROM records WHERE name = ?1 AND type = ?2 AND view = ?3",
+                        params![key.name, key.r#type, key.view],
+                        |row| row.get(0),
+                    )?;
+                    if revision != Some(if_revision) {
+                        conflicts.push(key);
+                    }
+                }
+                // Dropping the transaction rolls it back.
+                if !conflicts.is_empty() {
+                    return Ok((conflicts, owner_conflicts));
+                }
+                for operation in operations {
+                    match operation.operation {
+                        Some(Operation::Upsert(request)) => {
+                            upsert_rrset(&tx, request, now, &actor)?
+                        }
+                        Some(Operation::Delete(request)) => {
+                            delete_rrset(&tx, &request, now, &actor)?
+                        }
+                        None => {}
+                    }
+                }
+                tx.commit()?;
+                Ok((conflicts, owner_conflicts))
+            })
+            .await?;
+        if let Some(conflict) = owner_conflicts.into_iter().next() {
+            return Err(conflict.into());
+        }
+
+        let mut reply = BatchReply {
+            applied: conflicts.is_empty(),
+            ..Default::default()
+        };
+        if reply.applied {
+            for key in upserted {
+                reply.records.push(self.find_or_missing(key).await?);
+            }
+        } else {
+            for key in conflicts {
+                reply.conflicts.push(self.find_or_missing(key).await?);
+            }
+        }
+        Ok(reply)
+    }
+
+    async fn keep_alive(&self, renewals: Vec<LeaseRenewal>) -> Result<Vec<Option<RecordReply>>> {
+        let now = OffsetDateTime::now_utc();
+        let keys: Vec<(String, String, String)> = renewals
+            .iter()
+            .map(|renewal| {
+                (
+                    renewal.name.clone(),
+                    renewal.r#type.clone(),
+                    renewal.view.clone(),
+                )
+            })
+            .collect();
+        let renewed = self
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let mut renewed = vec![];
+                for renewal in renewals.iter() {
+                    let updated = tx.execute(
+                        r#"
+UPDATE records
+SET updated_at = ?5
+WHERE name = ?1
+  AND type = ?2
+  AND view = ?3
+  AND revision = ?4
+  AND owner = ?6"#,
+                        params![
+                            renewal.name,
+                            renewal.r#type,
+                            renewal.view,
+                            renewal.revision,
+                            now,
+                            renewal.owner
+                        ],
+                    )?;
+                    renewed.push(updated > 0);
+                }
+                tx.commit()?;
+                Ok(renewed)
+            })
+            .await?;
+
+        let mut results = vec![];
+        for ((name, r#type, view), renewed) in keys.into_iter().zip(renewed) {
+            if !renewed {
+                results.push(None);
+                continue;
+            }
+            let record = self
+                .find_unique(FindUniqueRecordRequest {
+                    name,
+                    r#type,
+                    view,
+                    ..Default::default()
+                })
+                .await?;
+            results.push(Some(record));
+        }
+        Ok(results)
+    }
+
+    async fn query(&self, query: RecordsQueryRequest) -> Result<RecordsPage> {
+        let columns: &[&str] = match query.order_by() {
+            RecordOrder::Name => &["name", "type", "view"],
+            RecordOrder::UpdatedAt => &["updated_at", "name", "type", "view"],
+            RecordOrder::CreatedAt => &["created_at", "name", "type", "view"],
+        };
+        let (direction, comparison) = if query.descending {
+            ("DESC", "<")
+        } else {
+            ("ASC", ">")
+        };
+
+        let mut conditions = vec![];
+        let mut values = vec![];
+        if !query.zone.is_empty() {
+            conditions.push("(name = ? OR substr(name, -?) = ?)");
+            values.push(Value::Text(query.zone.clone()));
+            values.push(Value::Integer(query.zone.len() as i64 + 1));
+            values.push(Value::Text(format!(".{}", query.zone)));
+        }
+        if !query.name.is_empty() {
+            conditions.push("name GLOB ?");
+            values.push(Value::Text(query.name.clone()));
+        }
+        if !query.r#type.is_empty() {
+            conditions.push("type = ?");
+            values.push(Value::Text(query.r#type.clone()));
+        }
+        if !query.view.is_empty() {
+            conditions.push("view = ?");
+            let view = if query.view == "*" { "" } else { &query.view };
+            values.push(Value::Text(view.to_string()));
+        }
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        match query.health() {
+            HealthFilter::Any => {}
+            HealthFilter::Healthy => {
+                conditions.push("(static OR unixepoch(updated_at) + lease >= ?)");
+                values.push(Value::Integer(now));
+            }
+            HealthFilter::Unhealthy => {
+                conditions.push("NOT (static OR unixepoch(updated_at) + lease >= ?)");
+                values.push(Value::Integer(now));
+            }
+        }
+        if query.updated_since != 0 {
+            let since = OffsetDateTime::from_unix_timestamp(query.updated_since).map_err(|_| {
+                RecordError::Invalid(vec![FieldViolation::new(
+                    "updated_since",
+                    "is out of range",
+                )])
+            })?;
+            // Timestamps are stored as text that sorts in time order, so this can use the index.
+            conditions.push("updated_at >= ?");
+            values.push(sql_value(since));
+        }
+        if !query.owner.is_empty() {
+            conditions.push("owner = ?");
+            values.push(Value::Text(query.owner.clone()));
+        }
+        let filter = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", conditions.join(" AND "))
+        };
+
+        let mut after = String::new();
+        if !query.page_token.is_empty() {
+            let key = decode_page_token(&query.page_token, columns.len())?;
+            after = format!(
+                "WHERE ({}) {} ({})",
+                columns.join(", "),
+                comparison,
+                vec!["?"; columns.len()].join(", ")
+            );
+            values.extend(key.into_iter().map(Value::Text));
+        }
+        // One more RRset than asked for tells whether there's another page.
+        let page_size = query.page_size as usize;
+        values.push(Value::Integer(if page_size == 0 {
+            -1
+        } else {
+            page_size as i64 + 1
+        }));
+
+        let order = |table: &str| {
+            columns
+                .iter()
+                .map(|column| format!("{}.{} {}", table, column, direction))
+                .collect::<Vec<String>>()
+                .join(", ")
+        };
+        let sql = format!(
+            r#"
+WITH rrsets AS (SELECT name, type, view, MAX(updated_at) AS updated_at, MIN(created_at) AS created_at
+                FROM records
+                {}
+                GROUP BY name, type, view),
+     page AS (SELECT *
+              FROM rrsets
+              {}
+              ORDER BY {}
+              LIMIT ?)
+SELECT records.name,
+       records.type,
+       records.data,
+       records.ttl,
+       records.created_at,
+       records.updated_at,
+       records.view,
+       records.static,
+       records.revision,
+       records.lease,
+       records.owner,
+       {}
+FROM page
+         JOIN records USING (name, type, view)
+ORDER BY {}, records.rowid"#,
+            filter,
+            after,
+            order("rrsets"),
+            columns
+                .iter()
+                .map(|column| format!("CAST(page.{} AS TEXT)", column))
+                .collect::<Vec<String>>()
+                .join(", "),
+            order("page"),
+        );
+        let key_columns = columns.len();
+        let rows = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(&sql)?;
+                let rows = stmt
+                    .query_map(params_from_iter(values), |row| {
                         let created_at: OffsetDateTime = row.get(4)?;
                         let updated_at: OffsetDateTime = row.get(5)?;
-                        Ok(RecordReply {
+                        let static_: bool = row.get(7)?;
+                        let lease: u32 = row.get(9)?;
+                        let data: String = row.get(2)?;
+                        let record = RecordReply {
                             name: row.get(0)?,
                             r#type: row.get(1)?,
-                            data: row.get(2)?,
+                            data: data.clone(),
                             ttl: row.get(3)?,
                             created_at: created_at.unix_timestamp(),
                             updated_at: updated_at.unix_timestamp(),
-                            healthy: OffsetDateTime::now_utc() - updated_at <= HEALTHY_AGE,
+                            healthy: is_healthy(static_, updated_at, lease),
+                            view: row.get(6)?,
+                            r#static: static_,
+                            values: vec![data],
+                            revision: row.get(8)?,
+                            lease,
+                            owner: row.get(10)?,
+                        };
+                        let key =
+                            (0..key_columns)
+                                .map(|i| row.get(11 + i))
+                                .collect::<Result<Vec<String>, _>>()?;
+                        Ok((record, key))
+                    })?
+                    .collect::<Result<Vec<(RecordReply, Vec<String>)>, _>>()?;
+                Ok(rows)
+            })
+            .await?;
+
+        let (rows, mut keys): (Vec<RecordReply>, Vec<Vec<String>>) = rows.into_iter().unzip();
+        // The rows of an RRset share its key, so this leaves one key for each RRset.
+        keys.dedup();
+        let mut records = group_records(rows);
+        let mut next_page_token = String::new();
+        if page_size > 0 && records.len() > page_size {
+            records.truncate(page_size);
+            next_page_token = encode_page_token(&keys[page_size - 1]);
+        }
+        Ok(RecordsPage {
+            records,
+            next_page_token,
+        })
+    }
+
+    async fn conflicts(&self) -> Result<Vec<Conflict>> {
+        let conflicts = self
+            .conn
+            .call(|conn| {
+                let mut stmt = conn.prepare(
+                    r#"
+SELECT name, type, view, owner, challenger, created_at
+FROM conflicts
+ORDER BY rowid DESC"#,
+                )?;
+                let conflicts = stmt
+                    .query_map([], |row| {
+                        let created_at: OffsetDateTime = row.get(5)?;
+                        Ok(Conflict {
+                            name: row.get(0)?,
+                            r#type: row.get(1)?,
+                            view: row.get(2)?,
+                            owner: row.get(3)?,
+                            challenger: row.get(4)?,
+                            created_at: created_at.unix_timestamp(),
                         })
                     })?
-                    .collect::<Result<Vec<RecordReply>, _>>()?;
-                Ok(records)
+                    .collect::<Result<Vec<Conflict>, _>>()?;
+                Ok(conflicts)
             })
             .await?;
-        Ok(records)
+        Ok(conflicts)
     }
 
-    pub async fn delete(&self, request: FindUniqueRecordRequest) -> Result<()> {
-        let name = request.name;
-        let r#type = request.r#type;
-        self.conn
+    async fn history(&self, request: HistoryRequest) -> Result<Vec<HistoryEntry>> {
+        let mut conditions = vec![];
+        let mut values = vec![];
+        if !request.zone.is_empty() {
+            conditions.push("(name = ? OR substr(name, -?) = ?)");
+            values.push(Value::Text(request.zone.clone()));
+            values.push(Value::Integer(request.zone.len() as i64 + 1));
+            values.push(Value::Text(format!(".{}", request.zone)));
+        }
+        for (condition, value) in [
+            ("name = ?", &request.name),
+            ("type = ?", &request.r#type),
+            ("view = ?", &request.view),
+            ("actor = ?", &request.actor),
+        ] {
+            if !value.is_empty() {
+                conditions.push(condition);
+                values.push(Value::Text(value.clone()));
+            }
+        }
+        if request.since != 0 {
+            let since = OffsetDateTime::from_unix_timestamp(request.since).map_err(|_| {
+                RecordError::Invalid(vec![FieldViolation::new("since", "is out of range")])
+            })?;
+            conditions.push("created_at >= ?");
+            values.push(sql_value(since));
+        }
+        let filter = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", conditions.join(" AND "))
+        };
+        let limit = if request.limit == 0 {
+            DEFAULT_HISTORY_LIMIT
+        } else {
+            request.limit
+        };
+        values.push(Value::Integer(limit as i64));
+        let sql = format!(
+            r#"
+SELECT name, type, view, action, old_values, new_values, ttl, revision, owner, actor, created_at
+FROM history
+{}
+ORDER BY rowid DESC
+LIMIT ?"#,
+            filter
+        );
+        let entries = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(&sql)?;
+                let entries = stmt
+                    .query_map(params_from_iter(values), |row| {
+                        let action: String = row.get(3)?;
+                        let old_values: String = row.get(4)?;
+                        let new_values: String = row.get(5)?;
+                        let created_at: OffsetDateTime = row.get(10)?;
+                        Ok(HistoryEntry {
+                            name: row.get(0)?,
+                            r#type: row.get(1)?,
+                            view: row.get(2)?,
+                            action: parse_history_action(&action) as i32,
+                            old_values: serde_json::from_str(&old_values).unwrap_or_default(),
+                            new_values: serde_json::from_str(&new_values).unwrap_or_default(),
+                            ttl: row.get::<_, Option<u32>>(6)?.unwrap_or_default(),
+                            revision: row.get(7)?,
+                            owner: row.get(8)?,
+                            actor: row.get(9)?,
+                            created_at: created_at.unix_timestamp(),
+                        })
+                    })?
+                    .collect::<Result<Vec<HistoryEntry>, _>>()?;
+                Ok(entries)
+            })
+            .await?;
+        Ok(entries)
+    }
+
+    async fn record_expiries(&self) -> Result<usize> {
+        let now = OffsetDateTime::now_utc();
+        let expired = self
+            .conn
             .call(move |conn| {
-                conn.execute(
-                    "DELETE FROM records WHERE name = ?1 AND type = ?2",
-                    params![name, r#type],
+                let expired = conn.execute(
+                    r#"
+WITH expired AS (SELECT name,
+                        type,
+                        view,
+                        json_group_array(data) AS old_values,
+                        MAX(ttl)               AS ttl,
+                        MAX(revision)          AS revision,
+                        MAX(owner)             AS owner,
+                        MAX(updated_at)        AS updated_at
+                 FROM records
+                 WHERE NOT static
+                 GROUP BY name, type, view
+                 HAVING MAX(unixepoch(updated_at) + lease) < ?2)
+INSERT
+INTO history (name, type, view, action, old_values, ttl, revision, owner, created_at)
+SELECT name, type, view, ?1, old_values, ttl, revision, owner, ?3
+FROM expired
+WHERE NOT EXISTS (SELECT 1
+                  FROM history
+                  WHERE history.name = expired.name
+                    AND history.type = expired.type
+                    AND history.view = expired.view
+                    AND history.action = ?1
+                    AND history.created_at >= expired.updated_at)"#,
+                    params![
+                        history_action(HistoryAction::Expire),
+                        now.unix_timestamp(),
+                        now
+                    ],
                 )?;
-                Ok(EmptyReply {})
+                Ok(expired)
+            })
+            .await?;
+        Ok(expired)
+    }
+
+    async fn prune_history(&self, cutoff: OffsetDateTime) -> Result<usize> {
+        let pruned = self
+            .conn
+            .call(move |conn| {
+                let pruned =
+                    conn.execute("DELETE FROM history WHERE created_at < ?1", params![cutoff])?;
+                Ok(pruned)
+            })
+            .await?;
+        Ok(pruned)
+    }
+
+    async fn delete(&self, request: FindUniqueRecordRequest) -> Result<()> {
+        let now = OffsetDateTime::now_utc();
+        let actor = current_actor();
+        let conflict = self
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let conflict = owner_conflict(&tx, &request)?;
+                match conflict {
+                    Some(ref conflict) => record_conflict(&tx, conflict, now)?,
+                    None => delete_rrset(&tx, &request, now, &actor)?,
+                }
+                tx.commit()?;
+                Ok(conflict)
+            })
+            .await?;
+        match conflict {
+            Some(conflict) => Err(conflict.into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Copies the database with SQLite's online backup API, which is safe while it's written to.
+    async fn backup(&self, path: PathBuf) -> Result<()> {
+        self.conn
+            .call(move |conn| {
+                conn.backup(DatabaseName::Main, &path, None)?;
+                Ok(())
             })
             .await?;
         Ok(())
     }
+
+    async fn integrity_check(&self) -> Result<Vec<String>> {
+        let problems = self.conn.call(integrity_problems).await?;
+        Ok(problems)
+    }
 }
@@ -0,0 +1,325 @@
+use crate::memory_store::MemoryStore;
+use crate::postgres_store::PostgresStore;
+use crate::proto::error_details::FieldViolation;
+use crate::proto::record_operation::Operation;
+use crate::proto::{
+    BatchReply, Conflict, FindUniqueRecordRequest, HistoryEntry, HistoryRequest, LeaseRenewal,
+    RecordOperation, RecordReply, RecordsPage, RecordsQueryRequest, UpsertRecordRequest,
+};
+use crate::record_repository::RecordRepository;
+use crate::util::{migrate_database, open_database};
+use crate::{ServerConfig, StorageBackend};
+use anyhow::Context;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hickory_server::proto::rr::RecordType;
+use std::fmt;
+use std::future::Future;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use thiserror::Error;
+use time::{Duration, OffsetDateTime};
+use tracing::warn;
+
+type Result<T, E = RecordError> = std::result::Result<T, E>;
+
+/// Lease of records published without one, in seconds.
+pub static DEFAULT_LEASE: u32 = 7 * 60;
+/// Number of rejected upserts remembered.
+pub(crate) static MAX_CONFLICTS: usize = 1000;
+/// Number of changes returned from the history when the request doesn't set a limit.
+pub(crate) static DEFAULT_HISTORY_LIMIT: u32 = 100;
+
+tokio::task_local! {
+    static ACTOR: String;
+}
+
+/// Runs `f` with the changes it makes recorded in the history as made by `actor`, e.g. the
+/// address of the API client.
+pub async fn acting_as<F: Future>(actor: String, f: F) -> F::Output {
+    ACTOR.scope(actor, f).await
+}
+
+pub(crate) fn current_actor() -> String {
+    ACTOR.try_with(|actor| actor.clone()).unwrap_or_default()
+}
+
+/// An upsert or delete was rejected because the RRset belongs to another publisher.
+#[derive(Debug, Clone)]
+pub struct OwnerConflict {
+    pub name: String,
+    pub r#type: String,
+    pub view: String,
+    pub owner: String,
+    pub challenger: String,
+}
+
+impl fmt::Display for OwnerConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} is owned by {:?}, not {:?}",
+            self.name, self.r#type, self.owner, self.challenger
+        )
+    }
+}
+
+impl std::error::Error for OwnerConflict {}
+
+/// Why a store operation failed.
+#[derive(Debug, Error)]
+pub enum RecordError {
+    /// The request itself is wrong, retrying it won't help.
+    #[error("Invalid request: {}", render_violations(.0))]
+    Invalid(Vec<FieldViolation>),
+    #[error("Record not found")]
+    NotFound,
+    /// Another writer held the store, retrying can succeed.
+    #[error("Conflicting write: {0}")]
+    Conflict(Box<dyn std::error::Error + Send + Sync>),
+    #[error(transparent)]
+    Permission(#[from] OwnerConflict),
+    #[error("Storage error: {0}")]
+    Storage(Box<dyn std::error::Error + Send + Sync>),
+    #[error("The configured storage doesn't support {0}")]
+    Unsupported(&'static str),
+}
+
+fn render_violations(violations: &[FieldViolation]) -> String {
+    violations
+        .iter()
+        .map(|violation| format!("{} {}", violation.field, violation.description))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// Checks that an upsert names an RRset and has values for it, prefixing the fields with
+/// `prefix` when it's part of a larger request.
+///
+/// This is only the shape every store needs, zones and protected names are checked by
+/// [`crate::validation::validate_upsert`] before requests reach the store.
+pub(crate) fn validate_rrset_shape(
+    request: &UpsertRecordRequest,
+    prefix: &str,
+) -> Vec<FieldViolation> {
+    let mut violations = vec![];
+    if request.name.is_empty() {
+        violations.push(FieldViolation::new(
+            format!("{}name", prefix),
+            "must not be empty",
+        ));
+    }
+    // Types are stored as they're sent, so they have to be in the canonical upper case.
+    if request.r#type.chars().any(|c| c.is_ascii_lowercase())
+        || RecordType::from_str(&request.r#type).is_err()
+    {
+        violations.push(FieldViolation::new(
+            format!("{}type", prefix),
+            format!("{:?} is not a record type", request.r#type),
+        ));
+    }
+    if request.value.is_empty() && request.values.is_empty() {
+        violations.push(FieldViolation::new(
+            format!("{}values", prefix),
+            "must not be empty",
+        ));
+    }
+    violations
+}
+
+/// Checks that every operation of a batch is an upsert or delete, and that the upserts have
+/// the shape of an RRset.
+pub(crate) fn validate_batch_shape(operations: &[RecordOperation]) -> Vec<FieldViolation> {
+    let mut violations = vec![];
+    for (i, operation) in operations.iter().enumerate() {
+        match &operation.operation {
+            Some(Operation::Upsert(request)) => violations.extend(validate_rrset_shape(
+                request,
+                &format!("operations[{}].upsert.", i),
+            )),
+            Some(Operation::Delete(_)) => {}
+            None => violations.push(FieldViolation::new(
+                format!("operations[{}]", i),
+                "must be an upsert or delete",
+            )),
+        }
+    }
+    violations
+}
+
+/// The RRset an upsert replaces, with the publisher making it.
+pub(crate) fn upsert_key(request: &UpsertRecordRequest) -> FindUniqueRecordRequest {
+    FindUniqueRecordRequest {
+        name: request.name.clone(),
+        r#type: request.r#type.clone(),
+        view: request.view.clone(),
+        owner: request.owner.clone(),
+        force: request.force,
+    }
+}
+
+/// The RRset an operation applies to.
+pub(crate) fn operation_key(operation: &RecordOperation) -> Option<FindUniqueRecordRequest> {
+    match &operation.operation {
+        Some(Operation::Upsert(request)) => Some(upsert_key(request)),
+        Some(Operation::Delete(request)) => Some(request.clone()),
+        None => None,
+    }
+}
+
+/// Whether a record's lease is still running. Static records aren't refreshed by clients, so they
+/// never expire.
+pub(crate) fn is_healthy(static_: bool, updated_at: OffsetDateTime, lease: u32) -> bool {
+    static_ || OffsetDateTime::now_utc() - updated_at <= Duration::seconds(lease as i64)
+}
+
+/// Checks whether an upsert or delete would change an RRset that another publisher still holds.
+///
+/// Unowned RRsets can be changed by anyone, owned ones only by their owner until their lease
+/// expires, unless the change is forced.
+pub(crate) fn check_owner(
+    request: &FindUniqueRecordRequest,
+    owner: String,
+    healthy: bool,
+) -> Option<OwnerConflict> {
+    if owner.is_empty() || owner == request.owner || !healthy {
+        return None;
+    }
+    if request.force {
+        warn!(
+            "{} {} of {:?} forcibly changed by {:?}",
+            request.name, request.r#type, owner, request.owner
+        );
+        return None;
+    }
+    Some(OwnerConflict {
+        name: request.name.clone(),
+        r#type: request.r#type.clone(),
+        view: request.view.clone(),
+        owner,
+        challenger: request.owner.clone(),
+    })
+}
+
+pub(crate) fn encode_page_token(key: &[String]) -> String {
+    URL_SAFE_NO_PAD.encode(serde_json::to_vec(key).unwrap_or_default())
+}
+
+/// Decodes the sort key a page continues after, which has to have `len` columns.
+pub(crate) fn decode_page_token(token: &str, len: usize) -> Result<Vec<String>> {
+    URL_SAFE_NO_PAD
+        .decode(token)
+        .ok()
+        .and_then(|json| serde_json::from_slice::<Vec<String>>(&json).ok())
+        .filter(|key| key.len() == len)
+        .ok_or_else(|| {
+            RecordError::Invalid(vec![FieldViolation::new(
+                "page_token",
+                "isn't a token for this query",
+            )])
+        })
+}
+
+/// Where the records are stored.
+///
+/// Names and types are expected to be normalized already, see [`crate::validation`]. Changes
+/// are recorded in the history as made by the actor set with [`acting_as`].
+#[async_trait::async_trait]
+pub trait RecordStore: fmt::Debug + Send + Sync {
+    async fn find_unique(&self, request: FindUniqueRecordRequest) -> Result<RecordReply>;
+
+    /// Finds the RRset to answer a query with from the given view.
+    ///
+    /// Records tagged with the view take precedence over untagged records, which are visible in
+    /// every view. RRsets whose lease expired aren't served.
+    async fn find_for_view(
+        &self,
+        name: String,
+        r#type: String,
+        view: String,
+    ) -> Result<RecordReply>;
+
+    /// Whether any RRset served to the view has the name or a name below it, i.e. whether the
+    /// name exists in the DNS.
+    async fn has_name(&self, name: String, view: String) -> Result<bool>;
+
+    /// Replaces the RRset with the values in the request.
+    ///
+    /// Fails with [`RecordError::Permission`] when the RRset belongs to another publisher.
+    async fn upsert(&self, request: UpsertRecordRequest) -> Result<RecordReply>;
+
+    /// Applies the operations atomically, or none of them if the revision precondition of any
+    /// operation fails.
+    ///
+    /// Fails with [`RecordError::Permission`] when any operation would change another publisher's
+    /// RRset.
+    async fn batch(&self, operations: Vec<RecordOperation>) -> Result<BatchReply>;
+
+    /// Renews the leases of RRsets without resending them, as long as they're still at the
+    /// revision the client last published and owned by the client.
+    ///
+    /// Returns a result for each renewal, `None` when the RRset changed or doesn't exist anymore so
+    /// the client has to upsert it.
+    async fn keep_alive(&self, renewals: Vec<LeaseRenewal>) -> Result<Vec<Option<RecordReply>>>;
+
+    async fn list(&self) -> Result<Vec<RecordReply>> {
+        Ok(self.query(Default::default()).await?.records)
+    }
+
+    /// Finds the RRsets matching a query, in pages of `page_size` RRsets if it's set.
+    ///
+    /// Pages continue after the sort key of the previous page's last RRset, so RRsets changing
+    /// between pages don't shift the following ones.
+    async fn query(&self, query: RecordsQueryRequest) -> Result<RecordsPage>;
+
+    /// Lists the upserts and deletes rejected because of an owner conflict, most recent first.
+    async fn conflicts(&self) -> Result<Vec<Conflict>>;
+
+    /// Lists the changes to the records matching the request, most recent first.
+    async fn history(&self, request: HistoryRequest) -> Result<Vec<HistoryEntry>>;
+
+    /// Records the RRsets whose lease ran out since they were last published in the history.
+    ///
+    /// Leases run out without anything being written, so this has to be called periodically.
+    /// Returns the number of expiries recorded.
+    async fn record_expiries(&self) -> Result<usize>;
+
+    /// Forgets the changes made before `cutoff`. Returns the number of changes forgotten.
+    async fn prune_history(&self, cutoff: OffsetDateTime) -> Result<usize>;
+
+    /// Deletes the RRset, if it exists.
+    ///
+    /// Fails with [`RecordError::Permission`] when the RRset belongs to another publisher.
+    async fn delete(&self, request: FindUniqueRecordRequest) -> Result<()>;
+
+    /// Writes a consistent snapshot of the store to `path` while it's in use.
+    async fn backup(&self, _path: PathBuf) -> Result<()> {
+        Err(RecordError::Unsupported("backups"))
+    }
+
+    /// Checks the stored data for corruption, returning the problems found.
+    async fn integrity_check(&self) -> Result<Vec<String>> {
+        Err(RecordError::Unsupported("integrity checks"))
+    }
+}
+
+/// Opens the store configured with `storage`, migrating the SQLite or PostgreSQL database to the
+/// latest schema.
+pub async fn open_store(cfg: &ServerConfig) -> anyhow::Result<Arc<dyn RecordStore>> {
+    match cfg.storage {
+        StorageBackend::Sqlite => {
+            let conn = Arc::new(open_database(&cfg.data_dir, &cfg.db_file).await?);
+            migrate_database(conn.clone()).await?;
+            Ok(Arc::new(RecordRepository { conn }))
+        }
+        StorageBackend::Memory => Ok(Arc::new(MemoryStore::default())),
+        StorageBackend::Postgres => {
+            let url = cfg
+                .postgres_url
+                .as_deref()
+                .context("storage: postgres needs a postgres_url")?;
+            Ok(Arc::new(PostgresStore::connect(url).await?))
+        }
+    }
+}
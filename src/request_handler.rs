@@ -0,0 +1,171 @@
+use crate::rate_limit::{RateLimitAction, ResponseRateLimiter};
+use crate::util::canonical_ip;
+use hickory_server::authority::{Catalog, MessageResponse, MessageResponseBuilder};
+use hickory_server::proto::op::{Header, ResponseCode};
+use hickory_server::proto::rr::Record;
+use hickory_server::server::{Protocol, Request, RequestHandler, ResponseHandler, ResponseInfo};
+use ipnet::IpNet;
+use std::cell::RefCell;
+use std::io;
+use std::net::IpAddr;
+use std::sync::{Arc, RwLock};
+use tracing::{debug, error};
+
+tokio::task_local! {
+    static RESPONSE_OVERRIDES: RefCell<ResponseOverrides>;
+}
+
+/// Changes an authority wants applied to the response header.
+///
+/// The `Catalog` builds the response itself, so authorities record these while handling a request
+/// and they're applied just before the response is sent.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ResponseOverrides {
+    pub response_code: Option<ResponseCode>,
+    pub authentic_data: bool,
+}
+
+/// Updates the overrides for the request currently being handled, if any.
+pub fn override_response(f: impl FnOnce(&mut ResponseOverrides)) {
+    let _ = RESPONSE_OVERRIDES.try_with(|overrides| f(&mut overrides.borrow_mut()));
+}
+
+/// A set of zones served to the clients within `match_clients` that query a server address
+/// within `match_destinations`. An empty list matches everything, as long as the other isn't
+/// empty too.
+pub struct View {
+    pub name: String,
+    pub match_clients: Vec<IpNet>,
+    pub match_destinations: Vec<IpNet>,
+    pub catalog: Catalog,
+}
+
+impl View {
+    fn matches(&self, src: IpAddr, dst: IpAddr) -> bool {
+        let matches = |nets: &[IpNet], ip_addr| {
+            nets.is_empty() || nets.iter().any(|net| net.contains(&ip_addr))
+        };
+        !(self.match_clients.is_empty() && self.match_destinations.is_empty())
+            && matches(&self.match_clients, src)
+            && matches(&self.match_destinations, dst)
+    }
+}
+
+/// The zones served to each view, rebuilt from scratch when the config is reloaded.
+pub struct Catalogs {
+    /// Zones for clients that don't match any view.
+    pub catalog: Catalog,
+    pub views: Vec<View>,
+}
+
+impl Catalogs {
+    fn select_catalog(&self, src: IpAddr, dst: IpAddr) -> &Catalog {
+        let src = canonical_ip(src);
+        let dst = canonical_ip(dst);
+        self.views
+            .iter()
+            .find(|view| view.matches(src, dst))
+            .map_or(&self.catalog, |view| {
+                debug!("Using view {:?} for {:?}", view.name, src);
+                &view.catalog
+            })
+    }
+}
+
+pub struct SwanRequestHandler {
+    /// Swapped as a whole on reload, requests in flight finish with the catalogs they started with.
+    pub catalogs: Arc<RwLock<Arc<Catalogs>>>,
+    pub rate_limiter: Option<Arc<ResponseRateLimiter>>,
+    /// Address the handler's listeners are bound to, which views match destinations against.
+    pub local_ip: IpAddr,
+}
+
+#[async_trait::async_trait]
+impl RequestHandler for SwanRequestHandler {
+    async fn handle_request<R: ResponseHandler>(
+        &self,
+        request: &Request,
+        mut response_handle: R,
+    ) -> ResponseInfo {
+        // TCP clients can't spoof their source address, so only UDP is rate limited.
+        if let (Some(rate_limiter), Protocol::Udp) = (&self.rate_limiter, request.protocol()) {
+            match rate_limiter.check(request.src().ip()) {
+                RateLimitAction::Allow => {}
+                RateLimitAction::Drop => {
+                    debug!("Rate limited {:?}, dropping response", request.src());
+                    let mut header = Header::response_from_request(request.header());
+                    header.set_response_code(ResponseCode::Refused);
+                    return ResponseInfo::from(header);
+                }
+                RateLimitAction::Slip => {
+                    debug!(
+                        "Rate limited {:?}, sending truncated response",
+                        request.src()
+                    );
+                    let mut header = Header::response_from_request(request.header());
+                    header.set_truncated(true);
+                    let response = MessageResponseBuilder::from_message_request(request)
+                        .build_no_records(header);
+                    return match response_handle.send_response(response).await {
+                        Ok(response_info) => response_info,
+                        Err(err) => {
+                            error!("Error sending truncated response: {}", err);
+                            ResponseInfo::from(header)
+                        }
+                    };
+                }
+            }
+        }
+
+        let catalogs = self
+            .catalogs
+            .read()
+            .expect("catalogs lock poisoned")
+            .clone();
+        RESPONSE_OVERRIDES
+            .scope(
+                RefCell::new(ResponseOverrides::default()),
+                catalogs
+                    .select_catalog(request.src().ip(), self.local_ip)
+                    .handle_request(
+                        request,
+                        OverridingResponseHandle {
+                            inner: response_handle,
+                        },
+                    ),
+            )
+            .await
+    }
+}
+
+#[derive(Clone)]
+struct OverridingResponseHandle<R: ResponseHandler> {
+    inner: R,
+}
+
+#[async_trait::async_trait]
+impl<R: ResponseHandler> ResponseHandler for OverridingResponseHandle<R> {
+    async fn send_response<'a>(
+        &mut self,
+        mut response: MessageResponse<
+            '_,
+            'a,
+            impl Iterator<Item = &'a Record> + Send + 'a,
+            impl Iterator<Item = &'a Record> + Send + 'a,
+            impl Iterator<Item = &'a Record> + Send + 'a,
+            impl Iterator<Item = &'a Record> + Send + 'a,
+        >,
+    ) -> io::Result<ResponseInfo> {
+        let overrides = RESPONSE_OVERRIDES
+            .try_with(|overrides| *overrides.borrow())
+            .unwrap_or_default();
+        let header = response.header_mut();
+        if let Some(response_code) = overrides.response_code {
+            header.set_response_code(response_code);
+        }
+        if overrides.authentic_data {
+            header.set_authentic_data(true);
+        }
+        self.inner.send_response(response).await
+    }
+}
@@ -0,0 +1,155 @@
+use crate::backup::create_backup;
+use crate::proto::admin_server::Admin;
+use crate::proto::{
+    BackupReply, BackupRequest, ConflictsReply, ConflictsRequest, HistoryReply, HistoryRequest,
+    InfoReply, InfoRequest, IntegrityCheckReply, IntegrityCheckRequest, RecordReply, ZoneStatus,
+    ZonesReply, ZonesRequest,
+};
+use crate::record_store::{RecordError, RecordStore};
+use crate::validation::validate_history;
+use crate::{ServerConfig, ZoneConfig};
+use hickory_server::proto::rr::LowerName;
+use hickory_server::resolver::Name;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::sync::watch;
+use tonic::{Request, Response, Status};
+use tracing::info;
+
+#[derive(Debug)]
+pub struct MyAdmin {
+    pub repo: Arc<dyn RecordStore>,
+    pub configs: watch::Receiver<Arc<ServerConfig>>,
+    pub started_at: i64,
+}
+
+/// Summarizes the records served for each zone of each view.
+///
+/// A record counts towards the most specific zone containing it, in its own view or in every
+/// view when it was published to all of them.
+pub fn zone_statuses(cfg: &ServerConfig, records: &[RecordReply]) -> Vec<ZoneStatus> {
+    let views = std::iter::once(("", &cfg.zones)).chain(
+        cfg.views
+            .iter()
+            .map(|view| (view.name.as_str(), &view.zones)),
+    );
+    let mut statuses = vec![];
+    for (view, zones) in views {
+        let zone_names: Vec<Option<LowerName>> = zones
+            .iter()
+            .map(|zone| Name::from_str(&zone.name).ok().map(LowerName::from))
+            .collect();
+        let mut view_statuses: Vec<ZoneStatus> = zones
+            .iter()
+            .map(|zone| zone_status(cfg, view, zone))
+            .collect();
+        for record in records
+            .iter()
+            .filter(|record| record.view.is_empty() || record.view == view)
+        {
+            let Ok(name) = Name::from_str(&record.name) else {
+                continue;
+            };
+            let name = LowerName::from(name);
+            let zone = zone_names
+                .iter()
+                .enumerate()
+                .filter_map(|(i, zone_name)| zone_name.as_ref().map(|zone_name| (i, zone_name)))
+                .filter(|(_, zone_name)| zone_name.zone_of(&name))
+                .max_by_key(|(_, zone_name)| zone_name.num_labels());
+            if let Some((i, _)) = zone {
+                view_statuses[i].records += 1;
+                if record.healthy {
+                    view_statuses[i].healthy_records += 1;
+                }
+            }
+        }
+        statuses.extend(view_statuses);
+    }
+    statuses
+}
+
+fn zone_status(cfg: &ServerConfig, view: &str, zone: &ZoneConfig) -> ZoneStatus {
+    ZoneStatus {
+        name: zone.name.clone(),
+        view: view.to_string(),
+        static_records: zone.records.len() as u32,
+        records: 0,
+        healthy_records: 0,
+        dnssec_validate: zone.dnssec_validate.unwrap_or(cfg.dnssec.validate),
+    }
+}
+
+#[tonic::async_trait]
+impl Admin for MyAdmin {
+    async fn info(&self, _request: Request<InfoRequest>) -> Result<Response<InfoReply>, Status> {
+        let records = self.repo.list().await?;
+        let cfg = self.configs.borrow().clone();
+        let reply = InfoReply {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            started_at: self.started_at,
+            dns_port: cfg.dns_port as u32,
+            api_port: cfg.api_port as u32,
+            nameservers: cfg.nameservers.clone(),
+            views: cfg.views.iter().map(|view| view.name.clone()).collect(),
+            records: records.len() as u32,
+            healthy_records: records.iter().filter(|record| record.healthy).count() as u32,
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn zones(&self, _request: Request<ZonesRequest>) -> Result<Response<ZonesReply>, Status> {
+        let records = self.repo.list().await?;
+        let zones = zone_statuses(&self.configs.borrow(), &records);
+        Ok(Response::new(ZonesReply { zones }))
+    }
+
+    async fn conflicts(
+        &self,
+        _request: Request<ConflictsRequest>,
+    ) -> Result<Response<ConflictsReply>, Status> {
+        let conflicts = self.repo.conflicts().await?;
+        Ok(Response::new(ConflictsReply { conflicts }))
+    }
+
+    async fn history(
+        &self,
+        request: Request<HistoryRequest>,
+    ) -> Result<Response<HistoryReply>, Status> {
+        let mut request = request.into_inner();
+        let violations = validate_history(&mut request);
+        if !violations.is_empty() {
+            return Err(RecordError::Invalid(violations).into());
+        }
+        let entries = self.repo.history(request).await?;
+        Ok(Response::new(HistoryReply { entries }))
+    }
+
+    async fn backup(
+        &self,
+        _request: Request<BackupRequest>,
+    ) -> Result<Response<BackupReply>, Status> {
+        let cfg = self.configs.borrow().clone();
+        let path = create_backup(self.repo.as_ref(), &cfg)
+            .await
+            .map_err(|err| match err.downcast::<RecordError>() {
+                Ok(err) => Status::from(err),
+                Err(err) => Status::internal(err.to_string()),
+            })?;
+        info!("Backed up the database to {:?}", path);
+        Ok(Response::new(BackupReply {
+            path: path.to_string_lossy().to_string(),
+        }))
+    }
+
+    async fn integrity_check(
+        &self,
+        _request: Request<IntegrityCheckRequest>,
+    ) -> Result<Response<IntegrityCheckReply>, Status> {
+        let problems = self.repo.integrity_check().await?;
+        Ok(Response::new(IntegrityCheckReply {
+            ok: problems.is_empty(),
+            problems,
+        }))
+    }
+}
@@ -1,29 +1,54 @@
+mod admin;
 mod ping;
 mod records;
 
+use crate::proto::admin_server::AdminServer;
 use crate::proto::ping_server::PingServer;
 use crate::proto::records_server::RecordsServer;
-use crate::record_repository::RecordRepository;
+use crate::record_store::RecordStore;
+use crate::ServerConfig;
+pub use admin::*;
 use anyhow::Result;
 pub use ping::*;
 pub use records::*;
 use std::net::SocketAddr;
 use std::sync::Arc;
+use tokio::sync::watch;
 use tonic::transport::Server;
 use tracing::info;
 
 pub struct RpcServer {
     pub addr: SocketAddr,
-    pub repo: Arc<RecordRepository>,
+    pub repo: Arc<dyn RecordStore>,
+    pub cfg: Arc<ServerConfig>,
 }
 
 impl RpcServer {
     pub async fn run(&self) -> Result<()> {
+        let (_, configs) = watch::channel(self.cfg.clone());
+        self.serve(configs).await
+    }
+
+    /// Runs the server, validating records against the zones of the latest config sent.
+    pub async fn run_with_reloads(
+        &self,
+        configs: watch::Receiver<Arc<ServerConfig>>,
+    ) -> Result<()> {
+        self.serve(configs).await
+    }
+
+    async fn serve(&self, configs: watch::Receiver<Arc<ServerConfig>>) -> Result<()> {
         info!("RPC server listening on: {:?}", self.addr);
         Server::builder()
             .add_service(PingServer::new(MyPing::new()))
+            .add_service(AdminServer::new(MyAdmin {
+                repo: self.repo.clone(),
+                configs: configs.clone(),
+                started_at: chrono::Utc::now().timestamp(),
+            }))
             .add_service(RecordsServer::new(MyRecords {
                 repo: self.repo.clone(),
+                configs,
             }))
             .serve(self.addr)
             .await?;
@@ -1,17 +1,61 @@
+use crate::proto::error_details::{self, FieldViolation};
+use crate::proto::record_operation::Operation;
 use crate::proto::records_server::Records;
 use crate::proto::{
-    EmptyReply, FindUniqueRecordRequest, RecordReply, RecordsQueryRequest, UpsertRecordRequest,
+    BatchReply, BatchRequest, EmptyReply, ExportReply, ExportRequest, FindUniqueRecordRequest,
+    ImportReply, ImportRequest, KeepAliveReply, KeepAliveRequest, RecordReply, RecordsPage,
+    RecordsQueryRequest, RenewalResult, UpsertRecordRequest,
 };
-use crate::record_repository::RecordRepository;
+use crate::record_store::{acting_as, RecordError, RecordStore};
+use crate::util::canonical_ip;
+use crate::validation::{normalize_renewal, validate_find, validate_query, validate_upsert};
+use crate::zone_file::{export_zone_file, import_zone_file};
+use crate::ServerConfig;
 use anyhow::Result;
 use std::sync::Arc;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, watch};
 use tokio_stream::wrappers::ReceiverStream;
 use tonic::{Request, Response, Status};
+use tracing::error;
+
+impl From<RecordError> for Status {
+    fn from(err: RecordError) -> Self {
+        match err {
+            RecordError::Invalid(violations) => {
+                error_details::invalid_argument("Invalid request", violations)
+            }
+            RecordError::NotFound => Status::not_found(err.to_string()),
+            RecordError::Conflict(_) => Status::aborted(err.to_string()),
+            RecordError::Permission(_) => Status::permission_denied(err.to_string()),
+            RecordError::Unsupported(_) => Status::unimplemented(err.to_string()),
+            RecordError::Storage(_) => {
+                error!("{}", err);
+                Status::internal(err.to_string())
+            }
+        }
+    }
+}
+
+fn invalid_unless_empty(violations: Vec<FieldViolation>) -> Result<(), RecordError> {
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(RecordError::Invalid(violations))
+    }
+}
+
+/// Who a request's changes are recorded as made by in the history, the client's address.
+fn actor<T>(request: &Request<T>) -> String {
+    request
+        .remote_addr()
+        .map(|addr| canonical_ip(addr.ip()).to_string())
+        .unwrap_or_default()
+}
 
 #[derive(Debug)]
 pub struct MyRecords {
-    pub repo: Arc<RecordRepository>,
+    pub repo: Arc<dyn RecordStore>,
+    pub configs: watch::Receiver<Arc<ServerConfig>>,
 }
 
 #[tonic::async_trait]
@@ -20,17 +64,21 @@ impl Records for MyRecords {
         &self,
         request: Request<FindUniqueRecordRequest>,
     ) -> std::result::Result<Response<RecordReply>, Status> {
-        match self.repo.find_unique(request.into_inner()).await {
-            Ok(record) => Ok(Response::new(record)),
-            Err(_) => Err(Status::not_found("record not found")),
-        }
+        let mut request = request.into_inner();
+        invalid_unless_empty(validate_find(&mut request, ""))?;
+        let record = self.repo.find_unique(request).await?;
+        Ok(Response::new(record))
     }
 
     async fn upsert(
         &self,
         request: Request<UpsertRecordRequest>,
     ) -> Result<Response<RecordReply>, Status> {
-        let record = self.repo.upsert(request.into_inner()).await.unwrap();
+        let actor = actor(&request);
+        let mut request = request.into_inner();
+        let cfg = self.configs.borrow().clone();
+        invalid_unless_empty(validate_upsert(&cfg, &mut request, ""))?;
+        let record = acting_as(actor, self.repo.upsert(request)).await?;
         Ok(Response::new(record))
     }
 
@@ -38,24 +86,115 @@ impl Records for MyRecords {
 
     async fn list(
         &self,
-        _request: Request<RecordsQueryRequest>,
+        request: Request<RecordsQueryRequest>,
     ) -> Result<Response<Self::ListStream>, Status> {
+        let mut query = request.into_inner();
+        invalid_unless_empty(validate_query(&mut query))?;
+        let page = self.repo.query(query).await?;
         let (tx, rx) = mpsc::channel(4);
-        let repo = self.repo.clone();
         tokio::spawn(async move {
-            let records = repo.list().await.unwrap();
-            for record in records {
-                tx.send(Ok(record)).await.unwrap();
+            for record in page.records {
+                // The client went away.
+                if tx.send(Ok(record)).await.is_err() {
+                    break;
+                }
             }
         });
         Ok(Response::new(ReceiverStream::new(rx)))
     }
 
+    async fn list_page(
+        &self,
+        request: Request<RecordsQueryRequest>,
+    ) -> Result<Response<RecordsPage>, Status> {
+        let mut query = request.into_inner();
+        invalid_unless_empty(validate_query(&mut query))?;
+        let page = self.repo.query(query).await?;
+        Ok(Response::new(page))
+    }
+
     async fn delete(
         &self,
         request: Request<FindUniqueRecordRequest>,
     ) -> Result<Response<EmptyReply>, Status> {
-        self.repo.delete(request.into_inner()).await.unwrap();
+        let actor = actor(&request);
+        let mut request = request.into_inner();
+        invalid_unless_empty(validate_find(&mut request, ""))?;
+        acting_as(actor, self.repo.delete(request)).await?;
         Ok(Response::new(EmptyReply {}))
     }
+
+    async fn import(
+        &self,
+        request: Request<ImportRequest>,
+    ) -> Result<Response<ImportReply>, Status> {
+        let actor = actor(&request);
+        let cfg = self.configs.borrow().clone();
+        match acting_as(
+            actor,
+            import_zone_file(&cfg, self.repo.as_ref(), request.into_inner()),
+        )
+        .await
+        {
+            Ok(reply) => Ok(Response::new(reply)),
+            // Anything but the repository failing is a problem with the zone file.
+            Err(err) => Err(match err.downcast::<RecordError>() {
+                Ok(err) => err.into(),
+                Err(err) => Status::invalid_argument(err.to_string()),
+            }),
+        }
+    }
+
+    async fn export(
+        &self,
+        request: Request<ExportRequest>,
+    ) -> Result<Response<ExportReply>, Status> {
+        let cfg = self.configs.borrow().clone();
+        match export_zone_file(&cfg, self.repo.as_ref(), request.into_inner()).await {
+            Ok(reply) => Ok(Response::new(reply)),
+            Err(err) => Err(Status::invalid_argument(err.to_string())),
+        }
+    }
+
+    async fn keep_alive(
+        &self,
+        request: Request<KeepAliveRequest>,
+    ) -> Result<Response<KeepAliveReply>, Status> {
+        let mut renewals = request.into_inner().renewals;
+        renewals.iter_mut().for_each(normalize_renewal);
+        let records = self.repo.keep_alive(renewals).await?;
+        Ok(Response::new(KeepAliveReply {
+            results: records
+                .into_iter()
+                .map(|record| RenewalResult {
+                    current: record.is_some(),
+                    record,
+                })
+                .collect(),
+        }))
+    }
+
+    async fn batch(&self, request: Request<BatchRequest>) -> Result<Response<BatchReply>, Status> {
+        let actor = actor(&request);
+        let mut operations = request.into_inner().operations;
+        let cfg = self.configs.borrow().clone();
+        let mut violations = vec![];
+        for (i, operation) in operations.iter_mut().enumerate() {
+            match &mut operation.operation {
+                Some(Operation::Upsert(request)) => violations.extend(validate_upsert(
+                    &cfg,
+                    request,
+                    &format!("operations[{}].upsert.", i),
+                )),
+                Some(Operation::Delete(request)) => violations.extend(validate_find(
+                    request,
+                    &format!("operations[{}].delete.", i),
+                )),
+                None => {}
+            }
+        }
+        invalid_unless_empty(violations)?;
+        let reply = acting_as(actor, self.repo.batch(operations)).await?;
+        Ok(Response::new(reply))
+    }
 }
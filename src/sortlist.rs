@@ -0,0 +1,48 @@
+use crate::util::{canonical_ip, client_network};
+use crate::SortlistConfig;
+use hickory_server::proto::rr::{RData, Record};
+use hickory_server::resolver::lookup::Lookup;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+/// Orders address records so the ones closest to the client come first, like BIND's sortlist.
+#[derive(Debug, Clone)]
+pub struct Sortlist {
+    cfg: SortlistConfig,
+}
+
+impl Sortlist {
+    pub fn new(cfg: SortlistConfig) -> Self {
+        Self { cfg }
+    }
+
+    /// Moves the records within the client's network to the front, keeping the order otherwise.
+    pub fn sort(&self, records: &mut [Record], client: IpAddr) {
+        let network = client_network(
+            canonical_ip(client),
+            self.cfg.ipv4_prefix_length,
+            self.cfg.ipv6_prefix_length,
+        );
+        records.sort_by_key(|record| {
+            let address = match record.data() {
+                Some(RData::A(a)) => IpAddr::V4(a.0),
+                Some(RData::AAAA(aaaa)) => IpAddr::V6(aaaa.0),
+                _ => return true,
+            };
+            !network.contains(&address)
+        });
+    }
+
+    pub fn sort_lookup(&self, lookup: Lookup, client: IpAddr) -> Lookup {
+        if lookup.records().len() < 2 {
+            return lookup;
+        }
+        let mut records = lookup.records().to_vec();
+        self.sort(&mut records, client);
+        Lookup::new_with_deadline(
+            lookup.query().clone(),
+            Arc::from(records),
+            lookup.valid_until(),
+        )
+    }
+}
@@ -1,3 +1,8 @@
+use crate::acl::Acl;
+use crate::config::LookupTier;
+use crate::forwarder::Forwarder;
+use crate::request_handler::override_response;
+use crate::sortlist::Sortlist;
 use crate::sqlite_authority::SqliteAuthority;
 use hickory_server::authority::{
     Authority, LookupError, LookupObject, LookupOptions, LookupRecords, MessageRequest,
@@ -9,13 +14,44 @@ use hickory_server::resolver::lookup::Lookup;
 use hickory_server::server::RequestInfo;
 use hickory_server::store::forwarder::ForwardAuthority;
 use hickory_server::store::in_memory::InMemoryAuthority;
+use std::collections::HashSet;
 use std::io;
+use std::net::IpAddr;
+use tracing::debug;
 
 pub struct SplitAuthority {
     pub origin: LowerName,
     pub in_memory_authority: InMemoryAuthority,
     pub sqlite_authority: SqliteAuthority,
     pub forward_authority: ForwardAuthority,
+    /// Used instead of `forward_authority` when queries need DNSSEC validation or client subnets.
+    pub forwarder: Option<Forwarder>,
+    pub query_acl: Acl,
+    pub recursion_acl: Acl,
+    pub sortlist: Option<Sortlist>,
+    /// Tiers asked for an answer, in order. Tiers left out are never asked.
+    pub lookup_order: Vec<LookupTier>,
+    /// Whether dynamic RRsets are served when there's a static RRset of the same name and type.
+    pub allow_shadowing: bool,
+    /// Names of the static records and the names above them in the zone, see [`static_names`].
+    pub static_names: HashSet<LowerName>,
+}
+
+/// Collects the names of the authority's records and the names above them up to its origin, i.e.
+/// every name that exists in it.
+pub async fn static_names(authority: &InMemoryAuthority) -> HashSet<LowerName> {
+    let mut names = HashSet::new();
+    for key in authority.records().await.keys() {
+        let mut name = key.name.clone();
+        // Once a name is known, so are the names above it.
+        while authority.origin().zone_of(&name) && names.insert(name.clone()) {
+            if name.is_root() {
+                break;
+            }
+            name = name.base_name();
+        }
+    }
+    names
 }
 
 pub struct SplitLookup {
@@ -25,6 +61,147 @@ pub struct SplitLookup {
 
 impl SplitLookup {}
 
+impl SplitAuthority {
+    /// Asks the tiers for an answer in order, the first one with the RRset answers.
+    ///
+    /// Names that exist in the static or dynamic tier belong to the zone, so they're never
+    /// forwarded. When no tier has the RRset, the answer is NODATA if the name exists with other
+    /// types and NXDOMAIN otherwise.
+    async fn resolve(
+        &self,
+        name: &LowerName,
+        rtype: RecordType,
+        lookup_options: LookupOptions,
+        client: Option<IpAddr>,
+    ) -> Result<SplitLookup, LookupError> {
+        let mut name_exists = false;
+        for tier in self.lookup_order.iter() {
+            match tier {
+                LookupTier::Static => {
+                    match self
+                        .in_memory_authority
+                        .lookup(name, rtype, lookup_options)
+                        .await
+                    {
+                        // `ANY` queries are answered even without records.
+                        Ok(l) if !l.is_empty() => {
+                            return Ok(SplitLookup {
+                                auth_lookup: Some(l.unwrap_records()),
+                                lookup: None,
+                            })
+                        }
+                        Ok(_) => {}
+                        Err(LookupError::NameExists) => name_exists = true,
+                        Err(err) if err.is_nx_domain() => {}
+                        Err(err) => return Err(err),
+                    }
+                }
+                LookupTier::Dynamic => {
+                    if !self.allow_shadowing
+                        && self.has_static_rrset(name, rtype, lookup_options).await
+                    {
+                        debug!("Not looking up dynamic {} records for {}", rtype, name);
+                        continue;
+                    }
+                    match self
+                        .sqlite_authority
+                        .lookup(name, rtype, lookup_options)
+                        .await
+                    {
+                        Ok(l) => {
+                            return Ok(SplitLookup {
+                                auth_lookup: None,
+                                lookup: Some(match (&self.sortlist, client) {
+                                    (Some(sortlist), Some(client)) => {
+                                        sortlist.sort_lookup(l, client)
+                                    }
+                                    _ => l,
+                                }),
+                            })
+                        }
+                        Err(err) if err.is_nx_domain() => {
+                            name_exists =
+                                name_exists || self.sqlite_authority.has_name(name).await?;
+                        }
+                        Err(err) => return Err(err),
+                    }
+                }
+                LookupTier::Forward => {
+                    if name_exists || self.has_local_name(name).await? {
+                        debug!("Not forwarding query for {}, the zone has the name", name);
+                        continue;
+                    }
+                    return self.forward(name, rtype, lookup_options, client).await;
+                }
+            }
+        }
+        if name_exists || self.has_local_name(name).await? {
+            Err(LookupError::NameExists)
+        } else {
+            Err(LookupError::from(ResponseCode::NXDomain))
+        }
+    }
+
+    /// Whether the static records answer the query, which dynamic records then can't shadow.
+    async fn has_static_rrset(
+        &self,
+        name: &LowerName,
+        rtype: RecordType,
+        lookup_options: LookupOptions,
+    ) -> bool {
+        matches!(
+            self.in_memory_authority
+                .lookup(name, rtype, lookup_options)
+                .await,
+            Ok(l) if !l.is_empty()
+        )
+    }
+
+    /// Whether the name exists in any of the zone's tiers that are asked.
+    async fn has_local_name(&self, name: &LowerName) -> Result<bool, LookupError> {
+        if self.lookup_order.contains(&LookupTier::Static) && self.static_names.contains(name) {
+            return Ok(true);
+        }
+        if self.lookup_order.contains(&LookupTier::Dynamic) {
+            return self.sqlite_authority.has_name(name).await;
+        }
+        Ok(false)
+    }
+
+    async fn forward(
+        &self,
+        name: &LowerName,
+        rtype: RecordType,
+        lookup_options: LookupOptions,
+        client: Option<IpAddr>,
+    ) -> Result<SplitLookup, LookupError> {
+        let allow_recursion = match client {
+            Some(client) => self.recursion_acl.permits(client),
+            None => true,
+        };
+        if !allow_recursion {
+            override_response(|o| o.response_code = Some(ResponseCode::Refused));
+            return Err(LookupError::ResponseCode(ResponseCode::Refused));
+        }
+        if let Some(forwarder) = &self.forwarder {
+            return forwarder
+                .lookup(name, rtype, client)
+                .await
+                .map(|l| SplitLookup {
+                    auth_lookup: None,
+                    lookup: Some(l),
+                });
+        }
+        self.forward_authority
+            .lookup(name, rtype, lookup_options)
+            .await
+            .map(|l| SplitLookup {
+                auth_lookup: None,
+                lookup: Some(l.0),
+            })
+    }
+}
+
 impl LookupObject for SplitLookup {
     fn is_empty(&self) -> bool {
         if let Some(auth_lookup) = &self.auth_lookup {
@@ -77,34 +254,7 @@ impl Authority for SplitAuthority {
         rtype: RecordType,
         lookup_options: LookupOptions,
     ) -> Result<Self::Lookup, LookupError> {
-        let in_memory_resolve = self
-            .in_memory_authority
-            .lookup(name, rtype, lookup_options)
-            .await;
-        if in_memory_resolve.is_ok() {
-            return in_memory_resolve.map(|l| SplitLookup {
-                auth_lookup: Some(l.unwrap_records()),
-                lookup: None,
-            });
-        }
-        let db_resolve = self
-            .sqlite_authority
-            .lookup(name, rtype, lookup_options)
-            .await;
-        if db_resolve.is_ok() {
-            return db_resolve.map(|l| SplitLookup {
-                auth_lookup: None,
-                lookup: Some(l),
-            });
-        }
-        let forward_resolve = self
-            .forward_authority
-            .lookup(name, rtype, lookup_options)
-            .await;
-        return forward_resolve.map(|l| SplitLookup {
-            auth_lookup: None,
-            lookup: Some(l.0),
-        });
+        self.resolve(name, rtype, lookup_options, None).await
     }
 
     async fn search(
@@ -112,10 +262,17 @@ impl Authority for SplitAuthority {
         request: RequestInfo<'_>,
         lookup_options: LookupOptions,
     ) -> Result<Self::Lookup, LookupError> {
-        self.lookup(
+        let src = request.src.ip();
+        if !self.query_acl.permits(src) {
+            debug!("Refusing query for {} from {:?}", self.origin, src);
+            override_response(|o| o.response_code = Some(ResponseCode::Refused));
+            return Err(LookupError::ResponseCode(ResponseCode::Refused));
+        }
+        self.resolve(
             request.query.name(),
             request.query.query_type(),
             lookup_options,
+            Some(src),
         )
         .await
     }
@@ -1,11 +1,10 @@
-use crate::proto::FindUniqueRecordRequest;
-use crate::record_repository::RecordRepository;
-use crate::util::create_record_data;
+use crate::record_store::{RecordError, RecordStore};
 use hickory_server::authority::{
     Authority, LookupError, LookupOptions, MessageRequest, UpdateResult, ZoneType,
 };
 use hickory_server::proto::op::{Query, ResponseCode};
-use hickory_server::proto::rr::{DNSClass, LowerName, Record, RecordType};
+use hickory_server::proto::rr::{DNSClass, LowerName, RData, Record, RecordType};
+use hickory_server::proto::serialize::txt::RDataParser;
 use hickory_server::resolver::lookup::Lookup;
 use hickory_server::resolver::{IntoName, Name};
 use hickory_server::server::RequestInfo;
@@ -15,11 +14,28 @@ use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Instant;
 use time::Duration;
+use tracing::error;
 
 pub struct SqliteAuthority {
     pub origin: LowerName,
     pub zone_type: ZoneType,
-    pub repo: Arc<RecordRepository>,
+    pub repo: Arc<dyn RecordStore>,
+    pub view: String,
+}
+
+impl SqliteAuthority {
+    /// Whether the name exists in the view, with any type or as a parent of other names.
+    pub async fn has_name(&self, name: &LowerName) -> Result<bool, LookupError> {
+        let name = name.to_string();
+        let name = name.strip_suffix('.').unwrap_or(&name).to_string();
+        self.repo
+            .has_name(name.clone(), self.view.clone())
+            .await
+            .map_err(|err| {
+                error!("Error looking up {}: {}", name, err);
+                LookupError::ResponseCode(ResponseCode::ServFail)
+            })
+    }
 }
 
 #[async_trait::async_trait]
@@ -50,32 +66,43 @@ impl Authority for SqliteAuthority {
     ) -> Result<Self::Lookup, LookupError> {
         let mut name_param = name.to_string();
         name_param = name_param.strip_suffix(".").unwrap().to_string();
-        let records_result = self
+        let db_record = match self
             .repo
-            .find_unique(FindUniqueRecordRequest {
-                name: name_param,
-                r#type: rtype.to_string(),
-            })
-            .await;
-
-        if records_result.is_err() {
-            return Err(LookupError::ResponseCode(ResponseCode::NXDomain));
+            .find_for_view(name_param, rtype.to_string(), self.view.clone())
+            .await
+        {
+            Ok(db_record) => db_record,
+            Err(RecordError::NotFound) => {
+                return Err(LookupError::ResponseCode(ResponseCode::NXDomain))
+            }
+            Err(err) => {
+                error!("Error looking up {} {}: {}", name, rtype, err);
+                return Err(LookupError::ResponseCode(ResponseCode::ServFail));
+            }
+        };
+        let rr_type = RecordType::from_str(db_record.r#type.as_str()).unwrap();
+        let mut dns_records = vec![];
+        for value in db_record.values.iter() {
+            let rdata = RData::try_from_str(rr_type, value.as_str()).map_err(|err| {
+                LookupError::from(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid data for {} {}: {}", db_record.name, rr_type, err),
+                ))
+            })?;
+            let mut dns_record = Record::new();
+            dns_record
+                .set_name(Name::from_str(db_record.name.as_str()).unwrap())
+                .set_rr_type(rr_type)
+                .set_dns_class(DNSClass::IN)
+                .set_ttl(db_record.ttl)
+                .set_data(Some(rdata));
+            dns_records.push(dns_record);
         }
-
-        // TODO: Support more than one record?
-        let db_record = records_result.unwrap();
-        let mut dns_record = Record::new();
-        dns_record
-            .set_name(Name::from_str(db_record.name.as_str()).unwrap())
-            .set_rr_type(RecordType::from_str(db_record.r#type.as_str()).unwrap())
-            .set_dns_class(DNSClass::IN)
-            .set_ttl(db_record.ttl)
-            .set_data(create_record_data(db_record.data.as_str()).unwrap());
         let query = Query::query(name.into_name().unwrap(), rtype);
         let ttl = Instant::now().add(Duration::seconds(30));
         Ok(Lookup::new_with_deadline(
             query,
-            Arc::new([dns_record.clone()]),
+            Arc::from(dns_records),
             ttl,
         ))
     }
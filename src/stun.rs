@@ -0,0 +1,128 @@
+use crate::util::lookup_server;
+use anyhow::{anyhow, Result};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tracing::debug;
+
+static MAGIC_COOKIE: u32 = 0x2112_A442;
+static BINDING_REQUEST: u16 = 0x0001;
+static BINDING_RESPONSE: u16 = 0x0101;
+static MAPPED_ADDRESS: u16 = 0x0001;
+static XOR_MAPPED_ADDRESS: u16 = 0x0020;
+static STUN_PORT: u16 = 3478;
+static ATTEMPTS: usize = 3;
+static ATTEMPT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Builds an RFC 5389 Binding request.
+pub fn binding_request(transaction_id: &[u8; 12]) -> Vec<u8> {
+    let mut request = Vec::with_capacity(20);
+    request.extend_from_slice(&BINDING_REQUEST.to_be_bytes());
+    request.extend_from_slice(&0u16.to_be_bytes());
+    request.extend_from_slice(&MAGIC_COOKIE.to_be_bytes());
+    request.extend_from_slice(transaction_id);
+    request
+}
+
+/// Parses the reflexive address out of a Binding response.
+///
+/// XOR-MAPPED-ADDRESS is preferred, MAPPED-ADDRESS is only used by old RFC 3489 servers.
+pub fn parse_binding_response(response: &[u8], transaction_id: &[u8; 12]) -> Result<IpAddr> {
+    if response.len() < 20 {
+        return Err(anyhow!("STUN response is too short"));
+    }
+    let message_type = u16::from_be_bytes([response[0], response[1]]);
+    let length = u16::from_be_bytes([response[2], response[3]]) as usize;
+    if message_type != BINDING_RESPONSE {
+        return Err(anyhow!(
+            "Unexpected STUN message type {:#06x}",
+            message_type
+        ));
+    }
+    if response[8..20] != transaction_id[..] {
+        return Err(anyhow!("STUN response is for another transaction"));
+    }
+    let attributes = response
+        .get(20..20 + length)
+        .ok_or_else(|| anyhow!("STUN response is truncated"))?;
+
+    let mut mapped = None;
+    let mut offset = 0;
+    while offset + 4 <= attributes.len() {
+        let r#type = u16::from_be_bytes([attributes[offset], attributes[offset + 1]]);
+        let length = u16::from_be_bytes([attributes[offset + 2], attributes[offset + 3]]) as usize;
+        let value = attributes
+            .get(offset + 4..offset + 4 + length)
+            .ok_or_else(|| anyhow!("STUN attribute is truncated"))?;
+        if r#type == XOR_MAPPED_ADDRESS {
+            return parse_address(value, Some(transaction_id));
+        }
+        if r#type == MAPPED_ADDRESS {
+            mapped = Some(parse_address(value, None)?);
+        }
+        // Attributes are padded to 4 bytes.
+        offset += 4 + ((length + 3) & !3);
+    }
+    mapped.ok_or_else(|| anyhow!("STUN response has no mapped address"))
+}
+
+fn parse_address(value: &[u8], xor_transaction_id: Option<&[u8; 12]>) -> Result<IpAddr> {
+    // 0x00, family, port, address
+    let family = *value
+        .get(1)
+        .ok_or_else(|| anyhow!("STUN address is truncated"))?;
+    let mut mask = MAGIC_COOKIE.to_be_bytes().to_vec();
+    match xor_transaction_id {
+        Some(transaction_id) => mask.extend_from_slice(transaction_id),
+        None => mask = vec![0; 16],
+    }
+    let address_len = match family {
+        0x01 => 4,
+        0x02 => 16,
+        _ => return Err(anyhow!("Unknown STUN address family {:#04x}", family)),
+    };
+    let address: Vec<u8> = value
+        .get(4..4 + address_len)
+        .ok_or_else(|| anyhow!("STUN address is truncated"))?
+        .iter()
+        .zip(mask.iter())
+        .map(|(byte, mask)| byte ^ mask)
+        .collect();
+    Ok(match family {
+        0x01 => IpAddr::V4(Ipv4Addr::new(
+            address[0], address[1], address[2], address[3],
+        )),
+        _ => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&address);
+            IpAddr::V6(Ipv6Addr::from(octets))
+        }
+    })
+}
+
+/// Asks a STUN server for the address our requests come from.
+pub async fn query_stun_server(server: &str) -> Result<IpAddr> {
+    let server_addr = lookup_server(server, STUN_PORT).await?;
+    let bind_addr: SocketAddr = if server_addr.is_ipv6() {
+        "[::]:0".parse()?
+    } else {
+        "0.0.0.0:0".parse()?
+    };
+    let socket = UdpSocket::bind(bind_addr).await?;
+    socket.connect(server_addr).await?;
+
+    let transaction_id: [u8; 12] = rand::random();
+    let request = binding_request(&transaction_id);
+    let mut buf = [0u8; 1024];
+    for attempt in 1..=ATTEMPTS {
+        debug!(
+            "Sending STUN request to {:?}, attempt {}",
+            server_addr, attempt
+        );
+        socket.send(&request).await?;
+        if let Ok(len) = tokio::time::timeout(ATTEMPT_TIMEOUT, socket.recv(&mut buf)).await {
+            return parse_binding_response(&buf[..len?], &transaction_id);
+        }
+    }
+    Err(anyhow!("STUN server {:?} didn't respond", server))
+}
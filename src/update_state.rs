@@ -0,0 +1,116 @@
+use anyhow::Result;
+use platform_dirs::AppDirs;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+
+/// An RRset as `swandns-update` last published it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PublishedRecord {
+    pub server_url: String,
+    pub name: String,
+    pub r#type: String,
+    pub view: String,
+    pub values: Vec<String>,
+    pub ttl: u32,
+    /// Lease requested from the server, 0 for its default.
+    #[serde(default)]
+    pub lease: u32,
+    pub revision: u64,
+    /// Unix timestamp of the last full upsert.
+    pub published_at: i64,
+}
+
+impl PublishedRecord {
+    fn is(&self, server_url: &str, name: &str, r#type: &str, view: &str) -> bool {
+        self.server_url == server_url
+            && self.name == name
+            && self.r#type == r#type
+            && self.view == view
+    }
+}
+
+/// What was last published, so unchanged records only have to be kept alive.
+///
+/// The state is only a cache. A lost or stale entry just means a full upsert.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct UpdateState {
+    pub records: Vec<PublishedRecord>,
+}
+
+/// Held while the state file is read and written back, so concurrent updates can't drop each
+/// other's changes.
+static STATE_LOCK: Mutex<()> = Mutex::const_new(());
+
+pub fn default_state_file() -> PathBuf {
+    AppDirs::new(Some("swandns"), false)
+        .map_or(env::temp_dir(), |app_dirs| app_dirs.data_dir)
+        .join("update-state.json")
+}
+
+impl UpdateState {
+    /// Loads the state, starting over when the file is missing or unreadable.
+    pub async fn load(path: &Path) -> Self {
+        let contents = match fs::read(path).await {
+            Ok(contents) => contents,
+            Err(err) => {
+                debug!("No update state at {:?}: {}", path, err);
+                return Default::default();
+            }
+        };
+        serde_json::from_slice(&contents).unwrap_or_else(|err| {
+            warn!("Ignoring invalid update state at {:?}: {}", path, err);
+            Default::default()
+        })
+    }
+
+    /// Saves the state, replacing the file atomically so a crash can't leave it half written.
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, serde_json::to_vec_pretty(self)?).await?;
+        fs::rename(&tmp_path, path).await?;
+        Ok(())
+    }
+
+    /// Applies `change` to the saved state, reloading it under a lock so the changes are merged
+    /// with those of other records updated at the same time.
+    pub async fn update(path: &Path, change: impl FnOnce(&mut Self)) -> Result<()> {
+        let _guard = STATE_LOCK.lock().await;
+        let mut state = Self::load(path).await;
+        change(&mut state);
+        state.save(path).await
+    }
+
+    pub fn get(
+        &self,
+        server_url: &str,
+        name: &str,
+        r#type: &str,
+        view: &str,
+    ) -> Option<&PublishedRecord> {
+        self.records
+            .iter()
+            .find(|record| record.is(server_url, name, r#type, view))
+    }
+
+    pub fn insert(&mut self, record: PublishedRecord) {
+        self.remove(
+            &record.server_url,
+            &record.name,
+            &record.r#type,
+            &record.view,
+        );
+        self.records.push(record);
+    }
+
+    pub fn remove(&mut self, server_url: &str, name: &str, r#type: &str, view: &str) {
+        self.records
+            .retain(|record| !record.is(server_url, name, r#type, view));
+    }
+}
@@ -1,14 +1,17 @@
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use hickory_server::proto::rr::rdata::{A, AAAA};
 use hickory_server::proto::rr::{RData, RecordType};
 use hickory_server::resolver::Name;
+use ipnet::IpNet;
 use local_ip_address::{list_afinet_netifas, local_ip, local_ipv6};
 use rusqlite_migration::{Migrations, M};
+use std::fmt;
 use std::net::{IpAddr, SocketAddr};
 use std::path::PathBuf;
 use std::str::FromStr;
 use std::sync::Arc;
 use tokio::fs;
+use tokio::net::lookup_host;
 use tokio_rusqlite::Connection;
 use tracing::{debug, info, Level};
 use tracing_subscriber::FmtSubscriber;
@@ -48,6 +51,72 @@ pub fn get_iface_addr(iface: Option<String>, protocol: Option<String>) -> Result
     return Err(anyhow!("Interface not found"));
 }
 
+/// Lists every address of an interface for `ipv4`, `ipv6` or `both` protocols.
+///
+/// Without an interface this is the address of the system's default interface for each protocol.
+pub fn get_iface_addrs(iface: Option<String>, protocol: Option<String>) -> Result<Vec<IpAddr>> {
+    let desired_protocol = protocol.unwrap_or("ipv4".to_string());
+    let protocols: &[&str] = if desired_protocol.eq_ignore_ascii_case("both") {
+        &["ipv4", "ipv6"]
+    } else if desired_protocol.eq_ignore_ascii_case("ipv4") {
+        &["ipv4"]
+    } else if desired_protocol.eq_ignore_ascii_case("ipv6") {
+        &["ipv6"]
+    } else {
+        return Err(anyhow!("Unknown protocol {:?}", desired_protocol));
+    };
+
+    let Some(iface) = iface else {
+        let ip_addrs: Vec<IpAddr> = protocols
+            .iter()
+            .filter_map(|protocol| {
+                let ip_addr = if *protocol == "ipv4" {
+                    local_ip()
+                } else {
+                    local_ipv6()
+                };
+                // Dual-stack records are published with whatever protocols the host has.
+                ip_addr
+                    .map_err(|err| debug!("No {} address found: {}", protocol, err))
+                    .ok()
+            })
+            .collect();
+        return if ip_addrs.is_empty() {
+            Err(anyhow!("No {} address found", desired_protocol))
+        } else {
+            Ok(ip_addrs)
+        };
+    };
+
+    let ip_addrs: Vec<IpAddr> = list_afinet_netifas()?
+        .into_iter()
+        .filter(|(system_iface, system_ip)| {
+            let system_protocol = if system_ip.is_ipv6() { "ipv6" } else { "ipv4" };
+            system_iface.eq_ignore_ascii_case(iface.as_str())
+                && protocols.contains(&system_protocol)
+        })
+        .map(|(_, system_ip)| system_ip)
+        .collect();
+    debug!("Found ips {:?} for interface {:?}", ip_addrs, iface);
+    if ip_addrs.is_empty() {
+        return Err(anyhow!("Interface not found"));
+    }
+    Ok(ip_addrs)
+}
+
+/// Resolves a server given as `host`, `host:port` or an address, using `default_port` when none is given.
+pub async fn lookup_server(server: &str, default_port: u16) -> Result<SocketAddr> {
+    if let Ok(ip_addr) = IpAddr::from_str(server) {
+        return Ok(SocketAddr::new(ip_addr, default_port));
+    }
+    let socket_addr = if server.contains(':') {
+        lookup_host(server).await?.next()
+    } else {
+        lookup_host((server, default_port)).await?.next()
+    };
+    socket_addr.ok_or_else(|| anyhow!("Server {:?} not found", server))
+}
+
 pub fn get_socket_addr(
     iface: Option<String>,
     port: u16,
@@ -83,9 +152,15 @@ pub async fn open_database(data_dir: &PathBuf, db_filename: &PathBuf) -> Result<
     Ok(conn)
 }
 
-pub async fn migrate_database(conn: Arc<Connection>) -> Result<()> {
-    let migrations = Migrations::new(vec![M::up(
-        r#"
+/// The schema migrations, the database's `user_version` is the number applied.
+///
+/// Migrations are only ever appended, a released one must not change. Each one has a `down` so
+/// the schema can be rolled back to an older version. Columns are added with `ALTER TABLE` when
+/// they have a default, other changes rebuild the table and copy its rows over.
+fn migration_steps() -> Vec<M<'static>> {
+    vec![
+        M::up(
+            r#"
             CREATE TABLE records(
                 name VARCHAR(256) NOT NULL,
                 type VARCHAR(16) NOT NULL,
@@ -96,15 +171,373 @@ pub async fn migrate_database(conn: Arc<Connection>) -> Result<()> {
                 PRIMARY KEY (name, type)
             );
         "#,
-    )
-    .down("DROP TABLE records;")]);
-    conn.call(move |mut conn| {
-        info!("Migrating database to latest");
-        conn.pragma_update(None, "journal_mode", &"WAL").unwrap();
-        migrations.to_latest(&mut conn).unwrap();
+        )
+        .down("DROP TABLE records;"),
+        M::up(
+            r#"
+            CREATE TABLE records_new(
+                name VARCHAR(256) NOT NULL,
+                type VARCHAR(16) NOT NULL,
+                view VARCHAR(64) NOT NULL DEFAULT '',
+                data VARCHAR(512),
+                ttl INTEGER,
+                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (name, type, view)
+            );
+            INSERT INTO records_new (name, type, data, ttl, created_at, updated_at)
+            SELECT name, type, data, ttl, created_at, updated_at FROM records;
+            DROP TABLE records;
+            ALTER TABLE records_new RENAME TO records;
+        "#,
+        )
+        .down(
+            r#"
+            CREATE TABLE records_old(
+                name VARCHAR(256) NOT NULL,
+                type VARCHAR(16) NOT NULL,
+                data VARCHAR(512),
+                ttl INTEGER,
+                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (name, type)
+            );
+            INSERT INTO records_old (name, type, data, ttl, created_at, updated_at)
+            SELECT name, type, data, ttl, created_at, updated_at FROM records WHERE view = '';
+            DROP TABLE records;
+            ALTER TABLE records_old RENAME TO records;
+        "#,
+        ),
+        M::up("ALTER TABLE records ADD COLUMN static BOOLEAN NOT NULL DEFAULT 0;")
+            .down("ALTER TABLE records DROP COLUMN static;"),
+        M::up(
+            r#"
+            CREATE TABLE records_new(
+                name VARCHAR(256) NOT NULL,
+                type VARCHAR(16) NOT NULL,
+                view VARCHAR(64) NOT NULL DEFAULT '',
+                data VARCHAR(512) NOT NULL,
+                ttl INTEGER,
+                static BOOLEAN NOT NULL DEFAULT 0,
+                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (name, type, view, data)
+            );
+            INSERT INTO records_new (name, type, view, data, ttl, static, created_at, updated_at)
+            SELECT name, type, view, data, ttl, static, created_at, updated_at FROM records;
+            DROP TABLE records;
+            ALTER TABLE records_new RENAME TO records;
+        "#,
+        )
+        .down(
+            r#"
+            CREATE TABLE records_old(
+                name VARCHAR(256) NOT NULL,
+                type VARCHAR(16) NOT NULL,
+                view VARCHAR(64) NOT NULL DEFAULT '',
+                data VARCHAR(512),
+                ttl INTEGER,
+                static BOOLEAN NOT NULL DEFAULT 0,
+                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (name, type, view)
+            );
+            INSERT OR IGNORE INTO records_old (name, type, view, data, ttl, static, created_at, updated_at)
+            SELECT name, type, view, data, ttl, static, created_at, updated_at FROM records ORDER BY rowid;
+            DROP TABLE records;
+            ALTER TABLE records_old RENAME TO records;
+        "#,
+        ),
+        M::up("ALTER TABLE records ADD COLUMN revision INTEGER NOT NULL DEFAULT 1;")
+            .down("ALTER TABLE records DROP COLUMN revision;"),
+        M::up("ALTER TABLE records ADD COLUMN lease INTEGER NOT NULL DEFAULT 420;")
+            .down("ALTER TABLE records DROP COLUMN lease;"),
+        M::up(
+            r#"
+            ALTER TABLE records ADD COLUMN owner VARCHAR(256) NOT NULL DEFAULT '';
+            CREATE TABLE conflicts(
+                name VARCHAR(256) NOT NULL,
+                type VARCHAR(16) NOT NULL,
+                view VARCHAR(64) NOT NULL DEFAULT '',
+                owner VARCHAR(256) NOT NULL,
+                challenger VARCHAR(256) NOT NULL,
+                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+            );
+        "#,
+        )
+        .down(
+            r#"
+            DROP TABLE conflicts;
+            ALTER TABLE records DROP COLUMN owner;
+        "#,
+        ),
+        M::up(
+            r#"
+            CREATE INDEX records_updated_at ON records (updated_at);
+            CREATE INDEX records_type ON records (type);
+            CREATE INDEX records_owner ON records (owner);
+        "#,
+        )
+        .down(
+            r#"
+            DROP INDEX records_owner;
+            DROP INDEX records_type;
+            DROP INDEX records_updated_at;
+        "#,
+        ),
+        M::up(
+            r#"
+            CREATE TABLE history(
+                name VARCHAR(256) NOT NULL,
+                type VARCHAR(16) NOT NULL,
+                view VARCHAR(64) NOT NULL DEFAULT '',
+                action VARCHAR(16) NOT NULL,
+                old_values TEXT NOT NULL DEFAULT '[]',
+                new_values TEXT NOT NULL DEFAULT '[]',
+                ttl INTEGER,
+                revision INTEGER NOT NULL DEFAULT 0,
+                owner VARCHAR(256) NOT NULL DEFAULT '',
+                actor VARCHAR(256) NOT NULL DEFAULT '',
+                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+            );
+            CREATE INDEX history_created_at ON history (created_at);
+            CREATE INDEX history_name ON history (name, type, view);
+        "#,
+        )
+        .down("DROP TABLE history;"),
+        M::up(
+            r#"
+            CREATE TABLE records_new(
+                name VARCHAR(256) NOT NULL,
+                type VARCHAR(16) NOT NULL,
+                view VARCHAR(64) NOT NULL DEFAULT '',
+                data VARCHAR(512) NOT NULL,
+                ttl INTEGER,
+                static BOOLEAN NOT NULL DEFAULT 0,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                revision INTEGER NOT NULL DEFAULT 1,
+                lease INTEGER NOT NULL DEFAULT 420,
+                owner VARCHAR(256) NOT NULL DEFAULT '',
+                PRIMARY KEY (name, type, view, data)
+            );
+            INSERT INTO records_new (name, type, view, data, ttl, static, created_at, updated_at, revision, lease, owner)
+            SELECT name, type, view, data, ttl, static,
+                   COALESCE(created_at, CURRENT_TIMESTAMP), COALESCE(updated_at, CURRENT_TIMESTAMP),
+                   revision, lease, owner
+            FROM records;
+            DROP TABLE records;
+            ALTER TABLE records_new RENAME TO records;
+            CREATE INDEX records_updated_at ON records (updated_at);
+            CREATE INDEX records_type ON records (type);
+            CREATE INDEX records_owner ON records (owner);
+
+            CREATE TABLE conflicts_new(
+                name VARCHAR(256) NOT NULL,
+                type VARCHAR(16) NOT NULL,
+                view VARCHAR(64) NOT NULL DEFAULT '',
+                owner VARCHAR(256) NOT NULL,
+                challenger VARCHAR(256) NOT NULL,
+                created_at TEXT NOT NULL
+            );
+            INSERT INTO conflicts_new (name, type, view, owner, challenger, created_at)
+            SELECT name, type, view, owner, challenger, COALESCE(created_at, CURRENT_TIMESTAMP)
+            FROM conflicts ORDER BY rowid;
+            DROP TABLE conflicts;
+            ALTER TABLE conflicts_new RENAME TO conflicts;
+
+            CREATE TABLE history_new(
+                name VARCHAR(256) NOT NULL,
+                type VARCHAR(16) NOT NULL,
+                view VARCHAR(64) NOT NULL DEFAULT '',
+                action VARCHAR(16) NOT NULL,
+                old_values TEXT NOT NULL DEFAULT '[]',
+                new_values TEXT NOT NULL DEFAULT '[]',
+                ttl INTEGER,
+                revision INTEGER NOT NULL DEFAULT 0,
+                owner VARCHAR(256) NOT NULL DEFAULT '',
+                actor VARCHAR(256) NOT NULL DEFAULT '',
+                created_at TEXT NOT NULL
+            );
+            INSERT INTO history_new (name, type, view, action, old_values, new_values, ttl, revision, owner, actor, created_at)
+            SELECT name, type, view, action, old_values, new_values, ttl, revision, owner, actor,
+                   COALESCE(created_at, CURRENT_TIMESTAMP)
+            FROM history ORDER BY rowid;
+            DROP TABLE history;
+            ALTER TABLE history_new RENAME TO history;
+            CREATE INDEX history_created_at ON history (created_at);
+            CREATE INDEX history_name ON history (name, type, view);
+
+            -- Timestamps defaulted by SQLite or written by older versions sort before or after those written now,
+            -- and older ones can't be read by SQLite's date functions.
+            UPDATE records SET created_at = CASE
+                WHEN created_at GLOB '????-??-?? ??:??:??' THEN created_at || '.0+00:00'
+                WHEN created_at GLOB '????-??-?? ??:??:??:* +??:??'
+                    THEN substr(created_at, 1, 19) || '.' || substr(created_at, 21, instr(created_at, ' +') - 21)
+                        || substr(created_at, instr(created_at, ' +') + 1)
+                ELSE created_at
+            END,
+                               updated_at = CASE
+                WHEN updated_at GLOB '????-??-?? ??:??:??' THEN updated_at || '.0+00:00'
+                WHEN updated_at GLOB '????-??-?? ??:??:??:* +??:??'
+                    THEN substr(updated_at, 1, 19) || '.' || substr(updated_at, 21, instr(updated_at, ' +') - 21)
+                        || substr(updated_at, instr(updated_at, ' +') + 1)
+                ELSE updated_at
+            END;
+            UPDATE conflicts SET created_at = CASE
+                WHEN created_at GLOB '????-??-?? ??:??:??' THEN created_at || '.0+00:00'
+                WHEN created_at GLOB '????-??-?? ??:??:??:* +??:??'
+                    THEN substr(created_at, 1, 19) || '.' || substr(created_at, 21, instr(created_at, ' +') - 21)
+                        || substr(created_at, instr(created_at, ' +') + 1)
+                ELSE created_at
+            END;
+            UPDATE history SET created_at = CASE
+                WHEN created_at GLOB '????-??-?? ??:??:??' THEN created_at || '.0+00:00'
+                WHEN created_at GLOB '????-??-?? ??:??:??:* +??:??'
+                    THEN substr(created_at, 1, 19) || '.' || substr(created_at, 21, instr(created_at, ' +') - 21)
+                        || substr(created_at, instr(created_at, ' +') + 1)
+                ELSE created_at
+            END;
+        "#,
+        )
+        .down(
+            r#"
+            CREATE TABLE records_old(
+                name VARCHAR(256) NOT NULL,
+                type VARCHAR(16) NOT NULL,
+                view VARCHAR(64) NOT NULL DEFAULT '',
+                data VARCHAR(512) NOT NULL,
+                ttl INTEGER,
+                static BOOLEAN NOT NULL DEFAULT 0,
+                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                revision INTEGER NOT NULL DEFAULT 1,
+                lease INTEGER NOT NULL DEFAULT 420,
+                owner VARCHAR(256) NOT NULL DEFAULT '',
+                PRIMARY KEY (name, type, view, data)
+            );
+            INSERT INTO records_old SELECT * FROM records;
+            DROP TABLE records;
+            ALTER TABLE records_old RENAME TO records;
+            CREATE INDEX records_updated_at ON records (updated_at);
+            CREATE INDEX records_type ON records (type);
+            CREATE INDEX records_owner ON records (owner);
+
+            CREATE TABLE conflicts_old(
+                name VARCHAR(256) NOT NULL,
+                type VARCHAR(16) NOT NULL,
+                view VARCHAR(64) NOT NULL DEFAULT '',
+                owner VARCHAR(256) NOT NULL,
+                challenger VARCHAR(256) NOT NULL,
+                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+            );
+            INSERT INTO conflicts_old SELECT * FROM conflicts ORDER BY rowid;
+            DROP TABLE conflicts;
+            ALTER TABLE conflicts_old RENAME TO conflicts;
+
+            CREATE TABLE history_old(
+                name VARCHAR(256) NOT NULL,
+                type VARCHAR(16) NOT NULL,
+                view VARCHAR(64) NOT NULL DEFAULT '',
+                action VARCHAR(16) NOT NULL,
+                old_values TEXT NOT NULL DEFAULT '[]',
+                new_values TEXT NOT NULL DEFAULT '[]',
+                ttl INTEGER,
+                revision INTEGER NOT NULL DEFAULT 0,
+                owner VARCHAR(256) NOT NULL DEFAULT '',
+                actor VARCHAR(256) NOT NULL DEFAULT '',
+                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+            );
+            INSERT INTO history_old SELECT * FROM history ORDER BY rowid;
+            DROP TABLE history;
+            ALTER TABLE history_old RENAME TO history;
+            CREATE INDEX history_created_at ON history (created_at);
+            CREATE INDEX history_name ON history (name, type, view);
+        "#,
+        ),
+    ]
+}
+
+pub fn migrations() -> Migrations<'static> {
+    Migrations::new(migration_steps())
+}
+
+/// How the database's schema compares to the one of this version of Swan DNS.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SchemaStatus {
+    /// Number of migrations applied to the database.
+    pub current: usize,
+    /// Number of migrations this version of Swan DNS has.
+    pub latest: usize,
+}
+
+impl SchemaStatus {
+    pub fn is_current(&self) -> bool {
+        self.current == self.latest
+    }
+
+    /// Whether the database was migrated by a newer version of Swan DNS, which this one can't use.
+    pub fn is_newer(&self) -> bool {
+        self.current > self.latest
+    }
+}
+
+impl fmt::Display for SchemaStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "schema version {} of {}", self.current, self.latest)
+    }
+}
+
+pub async fn schema_status(conn: &Connection) -> Result<SchemaStatus> {
+    let current = conn
+        .call(|conn| {
+            migrations()
+                .current_version(conn)
+                .map_err(|err| tokio_rusqlite::Error::Other(Box::new(err)))
+        })
+        .await
+        .context("Couldn't read the database's schema version")?;
+    Ok(SchemaStatus {
+        current: usize::from(current),
+        latest: migration_steps().len(),
+    })
+}
+
+/// Migrates the database to the latest schema, refusing databases migrated by a newer version.
+pub async fn migrate_database(conn: Arc<Connection>) -> Result<()> {
+    let status = schema_status(&conn).await?;
+    if status.is_newer() {
+        bail!(
+            "The database is at {}, from a newer version of Swan DNS",
+            status
+        );
+    }
+    conn.call(|conn| {
+        conn.pragma_update(None, "journal_mode", "WAL")?;
         Ok(())
     })
-    .await?;
+    .await
+    .context("Couldn't enable write-ahead logging")?;
+    if status.is_current() {
+        debug!("Database is at the latest {}", status);
+        return Ok(());
+    }
+    info!(
+        "Migrating database from schema version {} to {}",
+        status.current, status.latest
+    );
+    conn.call(|conn| {
+        migrations()
+            .to_latest(conn)
+            .map_err(|err| tokio_rusqlite::Error::Other(Box::new(err)))
+    })
+    .await
+    .with_context(|| {
+        format!(
+            "Couldn't migrate the database from schema version {} to {}",
+            status.current, status.latest
+        )
+    })?;
     Ok(())
 }
 
@@ -137,6 +570,33 @@ pub fn render_record_name(key: &String, zone_name: &Name) -> Result<Name> {
     };
 }
 
+/// Unwraps IPv4 clients that dual-stack sockets report as mapped IPv6 addresses.
+pub fn canonical_ip(ip_addr: IpAddr) -> IpAddr {
+    match ip_addr {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip_addr, IpAddr::V4),
+        IpAddr::V4(_) => ip_addr,
+    }
+}
+
+/// Truncates a client address to the network it's grouped with.
+pub fn client_network(ip_addr: IpAddr, ipv4_prefix_length: u8, ipv6_prefix_length: u8) -> IpNet {
+    let prefix_len = match ip_addr {
+        IpAddr::V4(_) => ipv4_prefix_length.min(32),
+        IpAddr::V6(_) => ipv6_prefix_length.min(128),
+    };
+    // The prefix length is clamped above, so this can't fail.
+    IpNet::new(ip_addr, prefix_len).unwrap().trunc()
+}
+
+pub fn parse_cidr(value: &str) -> Result<IpNet> {
+    if let Ok(ip_net) = value.parse() {
+        Ok(ip_net)
+    } else {
+        let ip_addr: IpAddr = value.parse()?;
+        Ok(IpNet::from(ip_addr))
+    }
+}
+
 pub fn parse_ip_optional_socket(value: &str, default_port: u16) -> Result<SocketAddr> {
     if let Ok(socket_addr) = value.parse() {
         Ok(socket_addr)
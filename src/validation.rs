@@ -0,0 +1,221 @@
+use crate::proto::error_details::FieldViolation;
+use crate::proto::{
+    FindUniqueRecordRequest, HistoryRequest, LeaseRenewal, RecordsQueryRequest, UpsertRecordRequest,
+};
+use crate::util::render_record_name;
+use crate::{ServerConfig, ZoneConfig};
+use hickory_server::proto::rr::{RData, RecordType};
+use hickory_server::proto::serialize::txt::RDataParser;
+use hickory_server::resolver::Name;
+use std::str::FromStr;
+
+/// Types the server answers for its zones itself, which can't be published.
+static PROTECTED_TYPES: [RecordType; 2] = [RecordType::SOA, RecordType::NS];
+
+/// Parses a name the way it's stored, IDNA names in punycode and lower case.
+fn parse_name(name: &str) -> Result<Name, String> {
+    if name.is_empty() || name == "." {
+        return Err("must not be empty".to_string());
+    }
+    let mut parsed =
+        Name::from_utf8(name).map_err(|err| format!("{:?} is not a valid name: {}", name, err))?;
+    parsed.set_fqdn(true);
+    Ok(parsed.to_lowercase())
+}
+
+/// Normalizes a record name to how it's stored, e.g. `Bücher.Example.com.` to
+/// `xn--bcher-kva.example.com`.
+pub fn normalize_name(name: &str) -> Result<String, String> {
+    let name = parse_name(name)?;
+    Ok(name.to_ascii().trim_end_matches('.').to_string())
+}
+
+fn parse_type(r#type: &str) -> Result<RecordType, String> {
+    let record_type = RecordType::from_str(&r#type.to_uppercase())
+        .map_err(|_| format!("{:?} is not a record type", r#type))?;
+    if PROTECTED_TYPES.contains(&record_type) {
+        return Err(format!(
+            "{} records are served by the server itself",
+            record_type
+        ));
+    }
+    Ok(record_type)
+}
+
+/// The zones records published to `view` are served from, `None` if there's no such view.
+///
+/// Records published to all views are served from the zones of every view.
+fn view_zones<'a>(cfg: &'a ServerConfig, view: &str) -> Option<Vec<&'a ZoneConfig>> {
+    if view.is_empty() {
+        return Some(
+            cfg.zones
+                .iter()
+                .chain(cfg.views.iter().flat_map(|view| view.zones.iter()))
+                .collect(),
+        );
+    }
+    cfg.views
+        .iter()
+        .find(|view_config| view_config.name == view)
+        .map(|view_config| view_config.zones.iter().collect())
+}
+
+/// The most specific zone containing the name, and the zone's name.
+fn find_zone<'a>(zones: &[&'a ZoneConfig], name: &Name) -> Option<(&'a ZoneConfig, Name)> {
+    zones
+        .iter()
+        .filter_map(|zone| {
+            parse_name(&zone.name)
+                .ok()
+                .map(|zone_name| (*zone, zone_name))
+        })
+        .filter(|(_, zone_name)| zone_name.zone_of(name))
+        .max_by_key(|(_, zone_name)| zone_name.num_labels())
+}
+
+/// Whether a name can't be published, either the zone's apex or one of its `protected_names`.
+fn is_protected(zone: &ZoneConfig, zone_name: &Name, name: &Name) -> bool {
+    if !zone.allow_apex && zone_name == name {
+        return true;
+    }
+    zone.protected_names
+        .iter()
+        .filter_map(|key| render_record_name(key, zone_name).ok())
+        .any(|protected| {
+            if protected.is_wildcard() {
+                let base = protected.base_name();
+                base.zone_of(name) && base != *name
+            } else {
+                protected == *name
+            }
+        })
+}
+
+/// Normalizes an upsert's name and type, and checks it against the zones the record would be
+/// served from.
+///
+/// Fields are prefixed with `prefix` when the upsert is part of a larger request.
+pub fn validate_upsert(
+    cfg: &ServerConfig,
+    request: &mut UpsertRecordRequest,
+    prefix: &str,
+) -> Vec<FieldViolation> {
+    let mut violations = vec![];
+    let field = |name: &str| format!("{}{}", prefix, name);
+
+    let name = match parse_name(&request.name) {
+        Ok(name) => Some(name),
+        Err(err) => {
+            violations.push(FieldViolation::new(field("name"), err));
+            None
+        }
+    };
+    let record_type = match parse_type(&request.r#type) {
+        Ok(record_type) => Some(record_type),
+        Err(err) => {
+            violations.push(FieldViolation::new(field("type"), err));
+            None
+        }
+    };
+
+    if let Some(record_type) = record_type {
+        let values = if request.values.is_empty() {
+            vec![(field("value"), &request.value)]
+        } else {
+            request
+                .values
+                .iter()
+                .enumerate()
+                .map(|(i, value)| (field(&format!("values[{}]", i)), value))
+                .collect()
+        };
+        for (value_field, value) in values.into_iter().filter(|(_, value)| !value.is_empty()) {
+            if let Err(err) = RData::try_from_str(record_type, value) {
+                violations.push(FieldViolation::new(
+                    value_field,
+                    format!("{:?} is not valid {} data: {}", value, record_type, err),
+                ));
+            }
+        }
+        request.r#type = record_type.to_string();
+    }
+
+    let Some(zones) = view_zones(cfg, &request.view) else {
+        violations.push(FieldViolation::new(
+            field("view"),
+            format!("there's no view named {:?}", request.view),
+        ));
+        return violations;
+    };
+    if let Some(name) = name {
+        match find_zone(&zones, &name) {
+            Some((zone, zone_name)) if is_protected(zone, &zone_name, &name) => {
+                violations.push(FieldViolation::new(
+                    field("name"),
+                    format!("{} is protected in zone {}", name, zone.name),
+                ));
+            }
+            Some(_) => {}
+            None => violations.push(FieldViolation::new(
+                field("name"),
+                format!("{} isn't in a zone served to the view", name),
+            )),
+        }
+        request.name = name.to_ascii().trim_end_matches('.').to_string();
+    }
+    violations
+}
+
+/// Normalizes the name and type of the RRset a request refers to.
+///
+/// The zones aren't checked, so records published before a zone was removed can still be
+/// looked up and deleted.
+pub fn validate_find(request: &mut FindUniqueRecordRequest, prefix: &str) -> Vec<FieldViolation> {
+    match normalize_name(&request.name) {
+        Ok(name) => {
+            request.name = name;
+            request.r#type = request.r#type.to_uppercase();
+            vec![]
+        }
+        Err(err) => vec![FieldViolation::new(format!("{}name", prefix), err)],
+    }
+}
+
+/// Normalizes a renewal like the upsert it renews, leaving names that don't parse to not match.
+pub fn normalize_renewal(renewal: &mut LeaseRenewal) {
+    if let Ok(name) = normalize_name(&renewal.name) {
+        renewal.name = name;
+    }
+    renewal.r#type = renewal.r#type.to_uppercase();
+}
+
+/// Normalizes the filters of a query to match the stored names and types.
+pub fn validate_query(query: &mut RecordsQueryRequest) -> Vec<FieldViolation> {
+    let mut violations = vec![];
+    if !query.zone.is_empty() {
+        match normalize_name(&query.zone) {
+            Ok(zone) => query.zone = zone,
+            Err(err) => violations.push(FieldViolation::new("zone", err)),
+        }
+    }
+    // Globs can't go through IDNA, internationalized names have to be given in punycode.
+    query.name = query.name.trim_end_matches('.').to_lowercase();
+    query.r#type = query.r#type.to_uppercase();
+    violations
+}
+
+/// Normalizes the filters of a history request to match the stored names and types.
+pub fn validate_history(request: &mut HistoryRequest) -> Vec<FieldViolation> {
+    let mut violations = vec![];
+    for (field, name) in [("zone", &mut request.zone), ("name", &mut request.name)] {
+        if name.is_empty() {
+            continue;
+        }
+        match normalize_name(name) {
+            Ok(normalized) => *name = normalized,
+            Err(err) => violations.push(FieldViolation::new(field, err)),
+        }
+    }
+    request.r#type = request.r#type.to_uppercase();
+    violations
+}
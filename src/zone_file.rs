@@ -0,0 +1,288 @@
+use crate::dns_server::static_rrset;
+use crate::proto::{
+    ChangeAction, ExportReply, ExportRequest, FindUniqueRecordRequest, ImportReply, ImportRequest,
+    RecordChange, RecordReply, UpsertRecordRequest,
+};
+use crate::record_store::{RecordError, RecordStore};
+use crate::validation::validate_upsert;
+use crate::ServerConfig;
+use anyhow::{anyhow, Result};
+use hickory_server::proto::rr::{LowerName, RData, RecordType};
+use hickory_server::proto::serialize::txt::Parser;
+use hickory_server::resolver::Name;
+use std::fmt::Write;
+use std::str::FromStr;
+use tracing::debug;
+
+/// Renders a record as a master file line.
+pub fn render_record(name: &str, ttl: u32, r#type: &str, data: &str) -> String {
+    let name = if name.ends_with('.') {
+        name.to_string()
+    } else {
+        format!("{}.", name)
+    };
+    format!("{} {} IN {} {}", name, ttl, r#type, data)
+}
+
+/// Renders record data the way record values and master files are parsed.
+///
+/// TXT character-strings are quoted, otherwise the strings of `"a b" "c"` would be parsed as `a`,
+/// `b` and `c`.
+pub fn record_value(rdata: &RData) -> String {
+    match rdata {
+        RData::TXT(txt) => txt
+            .txt_data()
+            .iter()
+            .map(|data| {
+                let data = String::from_utf8_lossy(data);
+                format!("\"{}\"", data.replace('\\', "\\\\").replace('"', "\\\""))
+            })
+            .collect::<Vec<String>>()
+            .join(" "),
+        rdata => rdata.to_string(),
+    }
+}
+
+/// Renders an import change as lines of a diff, one for each value of the RRset.
+pub fn render_change(change: &RecordChange) -> String {
+    let record = change.record.clone().unwrap_or_default();
+    let prefix = match change.action() {
+        ChangeAction::Add => "+",
+        ChangeAction::Update => "~",
+        ChangeAction::Unchanged => " ",
+    };
+    let mut lines: Vec<String> = record
+        .values
+        .iter()
+        .map(|value| {
+            format!(
+                "{} {}",
+                prefix,
+                render_record(&record.name, record.ttl, &record.r#type, value)
+            )
+        })
+        .collect();
+    if change.action() == ChangeAction::Update {
+        lines.push(format!(
+            "  (was {} {})",
+            change.previous_ttl,
+            change.previous_values.join(", ")
+        ));
+    }
+    lines.join("\n")
+}
+
+/// Compares RRset values ignoring their order.
+fn same_values(a: &[String], b: &[String]) -> bool {
+    let mut a = a.to_vec();
+    let mut b = b.to_vec();
+    a.sort();
+    b.sort();
+    a == b
+}
+
+/// Imports the records of a master file into the database.
+///
+/// Records are merged into the view, existing records that aren't in the file are left alone.
+/// Every record is validated like an upsert before anything is imported, and the violations of
+/// all records are reported together. With `dry_run` the changes are only reported.
+pub async fn import_zone_file(
+    cfg: &ServerConfig,
+    repo: &dyn RecordStore,
+    request: ImportRequest,
+) -> Result<ImportReply> {
+    let origin = if request.origin.is_empty() {
+        None
+    } else {
+        Some(Name::from_str(&request.origin)?)
+    };
+    let (origin, record_sets) = Parser::new(request.zone_file.as_str(), None, origin).parse()?;
+
+    let mut upserts = vec![];
+    let mut violations = vec![];
+    for (key, record_set) in record_sets.iter() {
+        // The zone's authority records are generated by the server.
+        if key.record_type == RecordType::SOA
+            || (key.record_type == RecordType::NS && key.name == LowerName::from(&origin))
+        {
+            debug!("Skipping {} record for {}", key.record_type, key.name);
+            continue;
+        }
+        let values: Vec<String> = record_set
+            .records_without_rrsigs()
+            .filter_map(|record| record.data())
+            .map(record_value)
+            .collect();
+        if values.is_empty() {
+            continue;
+        }
+        let mut upsert = UpsertRecordRequest {
+            name: key.name.to_string(),
+            r#type: key.record_type.to_string(),
+            value: values[0].clone(),
+            ttl: record_set.ttl(),
+            view: request.view.clone(),
+            r#static: request.r#static,
+            values,
+            ..Default::default()
+        };
+        violations.extend(validate_upsert(
+            cfg,
+            &mut upsert,
+            &format!("records[{} {}].", key.name, key.record_type),
+        ));
+        upserts.push(upsert);
+    }
+    if !violations.is_empty() {
+        return Err(RecordError::Invalid(violations).into());
+    }
+
+    let mut changes = vec![];
+    for upsert in upserts {
+        let UpsertRecordRequest {
+            name,
+            r#type,
+            ttl,
+            values,
+            ..
+        } = upsert.clone();
+        let existing = match repo
+            .find_unique(FindUniqueRecordRequest {
+                name: name.clone(),
+                r#type: r#type.clone(),
+                view: request.view.clone(),
+                ..Default::default()
+            })
+            .await
+        {
+            Ok(existing) => Some(existing),
+            Err(RecordError::NotFound) => None,
+            Err(err) => return Err(err.into()),
+        };
+        let action = match &existing {
+            None => ChangeAction::Add,
+            Some(existing)
+                if !same_values(&existing.values, &values)
+                    || existing.ttl != ttl
+                    || existing.r#static != request.r#static =>
+            {
+                ChangeAction::Update
+            }
+            Some(_) => ChangeAction::Unchanged,
+        };
+
+        let reply = if request.dry_run || action == ChangeAction::Unchanged {
+            RecordReply {
+                name,
+                r#type,
+                data: values[0].clone(),
+                ttl,
+                created_at: existing.as_ref().map_or(0, |e| e.created_at),
+                updated_at: existing.as_ref().map_or(0, |e| e.updated_at),
+                healthy: true,
+                view: request.view.clone(),
+                r#static: request.r#static,
+                values,
+                revision: existing.as_ref().map_or(0, |e| e.revision),
+                lease: existing.as_ref().map_or(0, |e| e.lease),
+                owner: existing.as_ref().map_or(String::new(), |e| e.owner.clone()),
+            }
+        } else {
+            repo.upsert(upsert).await?
+        };
+        let mut change = RecordChange {
+            record: Some(reply),
+            ..Default::default()
+        };
+        change.set_action(action);
+        if let Some(existing) = existing {
+            change.previous_data = existing.data;
+            change.previous_ttl = existing.ttl;
+            change.previous_values = existing.values;
+        }
+        changes.push(change);
+    }
+    Ok(ImportReply { changes })
+}
+
+/// Exports the records served for a view as a master file.
+///
+/// This covers both the static records from the config and the records in the database.
+pub async fn export_zone_file(
+    cfg: &ServerConfig,
+    repo: &dyn RecordStore,
+    request: ExportRequest,
+) -> Result<ExportReply> {
+    let zone = if request.zone.is_empty() {
+        None
+    } else {
+        Some(LowerName::from(Name::from_str(&request.zone)?))
+    };
+    let in_zone = |name: &Name| {
+        zone.as_ref()
+            .map_or(true, |zone| zone.zone_of(&LowerName::from(name)))
+    };
+
+    let mut zone_file = String::new();
+    if request.view.is_empty() {
+        writeln!(zone_file, "; swandns records for the default view")?;
+    } else {
+        writeln!(zone_file, "; swandns records for view {:?}", request.view)?;
+    }
+
+    if request.include_static {
+        let zones = if request.view.is_empty() {
+            &cfg.zones
+        } else {
+            &cfg.views
+                .iter()
+                .find(|view| view.name == request.view)
+                .ok_or_else(|| anyhow!("Unknown view {:?}", request.view))?
+                .zones
+        };
+        writeln!(zone_file, "\n; Static records from the server config")?;
+        for zone_config in zones.iter() {
+            for record_config in zone_config.records.iter() {
+                let rrset = static_rrset(zone_config, record_config)?;
+                match rrset.first() {
+                    Some(record) if in_zone(record.name()) => {}
+                    _ => continue,
+                }
+                if let Some(comment) = &record_config.comment {
+                    writeln!(zone_file, "; {}", comment)?;
+                }
+                for record in rrset.iter() {
+                    let data = record.data().map(record_value);
+                    writeln!(
+                        zone_file,
+                        "{}",
+                        render_record(
+                            &record.name().to_string(),
+                            record.ttl(),
+                            &record.record_type().to_string(),
+                            &data.unwrap_or_default()
+                        )
+                    )?;
+                }
+            }
+        }
+    }
+
+    let mut records = repo.list().await?;
+    records.retain(|record| record.view == request.view);
+    records.sort_by(|a, b| (&a.name, &a.r#type).cmp(&(&b.name, &b.r#type)));
+    writeln!(zone_file, "\n; Records from the database")?;
+    for record in records {
+        if !in_zone(&Name::from_str(&record.name)?) {
+            continue;
+        }
+        for value in record.values.iter() {
+            writeln!(
+                zone_file,
+                "{}",
+                render_record(&record.name, record.ttl, &record.r#type, value)
+            )?;
+        }
+    }
+    Ok(ExportReply { zone_file })
+}
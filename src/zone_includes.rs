@@ -0,0 +1,170 @@
+use crate::zone_file::record_value;
+use crate::{load_config, RecordConfig, ServerConfig, ZoneConfig};
+use anyhow::{anyhow, Context, Result};
+use hickory_server::proto::rr::RecordType;
+use hickory_server::proto::serialize::txt::Parser;
+use hickory_server::resolver::Name;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tokio::fs;
+use tracing::info;
+
+/// Extensions of the record snippets read from a zone's `records_dir`.
+static SNIPPET_EXTENSIONS: [&str; 4] = ["yaml", "yml", "toml", "json"];
+
+/// A file of static records in a zone's `records_dir`.
+#[derive(Debug, Deserialize)]
+struct RecordSnippet {
+    #[serde(default)]
+    records: Vec<RecordConfig>,
+}
+
+/// Loads the server config from `path` along with the zone files and record directories it
+/// includes.
+pub async fn load_server_config(conf_name: &str, path: &Path) -> Result<ServerConfig> {
+    let mut cfg: ServerConfig = load_config(conf_name, Some(path.to_path_buf())).await?;
+    let base_dir = path.parent().unwrap_or(Path::new("."));
+    load_includes(&mut cfg, base_dir).await?;
+    Ok(cfg)
+}
+
+/// Adds the records of each zone's `zone_file` and `records_dir` to its `records`.
+///
+/// Relative paths are resolved against `base_dir`, usually the config file's directory, and
+/// replaced with the resolved ones.
+pub async fn load_includes(cfg: &mut ServerConfig, base_dir: &Path) -> Result<()> {
+    let zones = cfg
+        .zones
+        .iter_mut()
+        .chain(cfg.views.iter_mut().flat_map(|view| view.zones.iter_mut()));
+    for zone in zones {
+        load_zone_includes(zone, base_dir)
+            .await
+            .with_context(|| format!("Couldn't load the includes of zone {:?}", zone.name))?;
+    }
+    Ok(())
+}
+
+async fn load_zone_includes(zone: &mut ZoneConfig, base_dir: &Path) -> Result<()> {
+    if let Some(path) = zone.zone_file.as_mut() {
+        *path = base_dir.join(&*path);
+        info!("Loading zone {:?} from {:?}", zone.name, path);
+        let records = zone_file_records(&zone.name, path).await?;
+        zone.records.extend(records);
+    }
+    if let Some(dir) = zone.records_dir.as_mut() {
+        *dir = base_dir.join(&*dir);
+        info!("Loading records of zone {:?} from {:?}", zone.name, dir);
+        for path in snippet_files(dir).await? {
+            let text = fs::read_to_string(&path)
+                .await
+                .with_context(|| format!("Couldn't read {:?}", path))?;
+            let snippet = parse_snippet(&path, &text)
+                .with_context(|| format!("Couldn't parse {:?}", path))?;
+            zone.records.extend(snippet.records);
+        }
+    }
+    Ok(())
+}
+
+/// Parses a record snippet in the format of its extension. JSON is parsed as YAML, which it's a
+/// subset of.
+fn parse_snippet(path: &Path, text: &str) -> Result<RecordSnippet> {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("toml") => Ok(toml::from_str(text)?),
+        _ => Ok(serde_yaml::from_str(text)?),
+    }
+}
+
+/// The record snippets in `dir`, in the order of their names.
+async fn snippet_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    let mut entries = fs::read_dir(dir)
+        .await
+        .with_context(|| format!("Couldn't read {:?}", dir))?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        let is_snippet = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .filter(|extension| SNIPPET_EXTENSIONS.contains(extension))
+            .is_some();
+        if is_snippet && path.is_file() {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Reads the RRsets of an RFC 1035 master file as static records, relative names to the zone.
+///
+/// The zone's SOA record is generated by the server, so the file's is skipped.
+async fn zone_file_records(zone_name: &str, path: &Path) -> Result<Vec<RecordConfig>> {
+    let mut origin = Name::from_str(zone_name)?.to_lowercase();
+    origin.set_fqdn(true);
+    let text = fs::read_to_string(path)
+        .await
+        .with_context(|| format!("Couldn't read {:?}", path))?;
+    let (_, record_sets) = Parser::new(
+        text.as_str(),
+        Some(path.to_path_buf()),
+        Some(origin.clone()),
+    )
+    .parse()
+    .with_context(|| format!("Couldn't parse {:?}", path))?;
+
+    let mut records = vec![];
+    for (rr_key, record_set) in record_sets.iter() {
+        if rr_key.record_type == RecordType::SOA {
+            continue;
+        }
+        let values: Vec<String> = record_set
+            .records_without_rrsigs()
+            .filter_map(|record| record.data())
+            .map(record_value)
+            .collect();
+        if values.is_empty() {
+            continue;
+        }
+        let name = Name::from(rr_key.name.clone());
+        let key = relative_key(&origin, &name)
+            .ok_or_else(|| anyhow!("{:?} has {} outside of the zone", path, name))?;
+        records.push(RecordConfig {
+            key,
+            values,
+            r#type: Some(rr_key.record_type.to_string()),
+            ttl: Some(record_set.ttl()),
+            ..Default::default()
+        });
+    }
+    Ok(records)
+}
+
+/// The key of `name` in the zone, `@` for the zone itself.
+fn relative_key(origin: &Name, name: &Name) -> Option<String> {
+    if name == origin {
+        return Some("@".to_string());
+    }
+    if !origin.zone_of(name) {
+        return None;
+    }
+    // Names are compared ignoring case, like `zone_of` compares them
+    let name = name.to_string();
+    let suffix = format!(".{}", origin);
+    let key_len = name.len().checked_sub(suffix.len())?;
+    name.get(key_len..)?
+        .eq_ignore_ascii_case(&suffix)
+        .then(|| name[..key_len].to_string())
+}
+
+/// The files and directories the config includes, which are watched for changes along with it.
+pub fn included_paths(cfg: &ServerConfig) -> Vec<PathBuf> {
+    cfg.zones
+        .iter()
+        .chain(cfg.views.iter().flat_map(|view| view.zones.iter()))
+        .flat_map(|zone| zone.zone_file.iter().chain(zone.records_dir.iter()))
+        .cloned()
+        .collect()
+}
@@ -0,0 +1,76 @@
+use std::time::{Duration, Instant};
+use swandns::acl::Acl;
+use swandns::rate_limit::{RateLimitAction, ResponseRateLimiter};
+use swandns::{AclConfig, RateLimitConfig};
+
+#[test]
+fn test_acl() {
+    // Empty ACL allows everyone
+    let acl = Acl::default();
+    assert!(acl.permits("192.168.1.5".parse().unwrap()));
+
+    let acl = Acl::from_config(&AclConfig {
+        allow: vec!["192.168.1.0/24".to_string(), "fd00::/8".to_string()],
+        deny: vec!["192.168.1.13".to_string()],
+    })
+    .unwrap();
+    assert!(acl.permits("192.168.1.5".parse().unwrap()));
+    assert!(acl.permits("fd00::5".parse().unwrap()));
+    assert!(!acl.permits("10.0.0.5".parse().unwrap()));
+
+    // Deny takes precedence over allow
+    assert!(!acl.permits("192.168.1.13".parse().unwrap()));
+
+    // IPv4 clients on dual-stack sockets
+    assert!(acl.permits("::ffff:192.168.1.5".parse().unwrap()));
+
+    // Invalid networks are rejected
+    assert!(Acl::from_config(&AclConfig {
+        allow: vec!["banana".to_string()],
+        deny: vec![],
+    })
+    .is_err());
+}
+
+#[test]
+fn test_rate_limit() {
+    let rate_limiter = ResponseRateLimiter::new(RateLimitConfig {
+        responses_per_second: 2,
+        window: 1,
+        slip: 2,
+        ..Default::default()
+    });
+    let now = Instant::now();
+
+    // Within the limit
+    assert_eq!(
+        rate_limiter.check_at("192.168.1.5".parse().unwrap(), now),
+        RateLimitAction::Allow
+    );
+    assert_eq!(
+        rate_limiter.check_at("192.168.1.6".parse().unwrap(), now),
+        RateLimitAction::Allow
+    );
+
+    // Clients in the same /24 share a limit, every second limited response slips
+    assert_eq!(
+        rate_limiter.check_at("192.168.1.7".parse().unwrap(), now),
+        RateLimitAction::Drop
+    );
+    assert_eq!(
+        rate_limiter.check_at("192.168.1.7".parse().unwrap(), now),
+        RateLimitAction::Slip
+    );
+
+    // Other networks aren't affected
+    assert_eq!(
+        rate_limiter.check_at("10.0.0.5".parse().unwrap(), now),
+        RateLimitAction::Allow
+    );
+
+    // Limits reset after the window
+    assert_eq!(
+        rate_limiter.check_at("192.168.1.7".parse().unwrap(), now + Duration::from_secs(1)),
+        RateLimitAction::Allow
+    );
+}
@@ -0,0 +1,45 @@
+use std::collections::HashSet;
+use swandns::address_filter::{parse_if_inet6, AddressFilter};
+
+#[test]
+fn test_address_filter() {
+    // Nothing is excluded by default
+    let filter = AddressFilter::default();
+    let temporary = HashSet::new();
+    assert!(filter.permits("fe80::1".parse().unwrap(), &temporary));
+    assert!(filter.permits("fd00::1".parse().unwrap(), &temporary));
+
+    let temporary = HashSet::from(["2001:db8::1234".parse().unwrap()]);
+    let filter = AddressFilter::from_config(&[
+        "link-local".to_string(),
+        "ula".to_string(),
+        "temporary".to_string(),
+        "192.168.100.0/24".to_string(),
+    ])
+    .unwrap();
+    assert!(!filter.permits("fe80::1".parse().unwrap(), &temporary));
+    assert!(!filter.permits("169.254.1.1".parse().unwrap(), &temporary));
+    assert!(!filter.permits("fd00::1".parse().unwrap(), &temporary));
+    assert!(!filter.permits("2001:db8::1234".parse().unwrap(), &temporary));
+    assert!(!filter.permits("192.168.100.5".parse().unwrap(), &temporary));
+    assert!(filter.permits("2001:db8::1".parse().unwrap(), &temporary));
+    assert!(filter.permits("192.168.1.5".parse().unwrap(), &temporary));
+
+    // Invalid entries are rejected
+    assert!(AddressFilter::from_config(&["banana".to_string()]).is_err());
+}
+
+#[test]
+fn test_parse_if_inet6() {
+    let contents = "\
+20010db8000000000000000000001234 02 40 00 01     eth0
+20010db8000000000000000000000001 02 40 00 00     eth0
+fe800000000000000000000000000001 02 40 20 80     eth0
+00000000000000000000000000000001 01 80 10 80       lo
+";
+    let temporary = parse_if_inet6(contents);
+    assert_eq!(
+        temporary,
+        HashSet::from(["2001:db8::1234".parse().unwrap()])
+    );
+}
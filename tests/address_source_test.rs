@@ -0,0 +1,125 @@
+use std::net::IpAddr;
+use swandns::address_source::{consensus, parse_addresses, resolve_sources};
+use swandns::stun::{binding_request, parse_binding_response};
+use swandns::{AddressSourceConfig, SourceStrategy};
+
+#[test]
+fn test_stun_binding() {
+    let transaction_id = [7u8; 12];
+    let request = binding_request(&transaction_id);
+    assert_eq!(request.len(), 20);
+    assert_eq!(&request[..2], &[0x00, 0x01]);
+    assert_eq!(&request[4..8], &[0x21, 0x12, 0xA4, 0x42]);
+    assert_eq!(&request[8..], &transaction_id);
+
+    // 203.0.113.5:54321 as XOR-MAPPED-ADDRESS
+    let port = 54321u16 ^ 0x2112;
+    let address: Vec<u8> = [203u8, 0, 113, 5]
+        .iter()
+        .zip([0x21, 0x12, 0xA4, 0x42])
+        .map(|(byte, mask)| byte ^ mask)
+        .collect();
+    let mut response = vec![0x01, 0x01, 0x00, 0x0C, 0x21, 0x12, 0xA4, 0x42];
+    response.extend_from_slice(&transaction_id);
+    response.extend_from_slice(&[0x00, 0x20, 0x00, 0x08, 0x00, 0x01]);
+    response.extend_from_slice(&port.to_be_bytes());
+    response.extend_from_slice(&address);
+    let ip_addr = parse_binding_response(&response, &transaction_id).unwrap();
+    assert_eq!(ip_addr, "203.0.113.5".parse::<IpAddr>().unwrap());
+
+    // Responses to other requests are rejected
+    assert!(parse_binding_response(&response, &[8u8; 12]).is_err());
+    // As are truncated ones
+    assert!(parse_binding_response(&response[..24], &transaction_id).is_err());
+
+    // Old servers only send MAPPED-ADDRESS
+    let mut response = vec![0x01, 0x01, 0x00, 0x0C, 0x21, 0x12, 0xA4, 0x42];
+    response.extend_from_slice(&transaction_id);
+    response.extend_from_slice(&[0x00, 0x01, 0x00, 0x08, 0x00, 0x01, 0xD4, 0x31]);
+    response.extend_from_slice(&[198, 51, 100, 7]);
+    let ip_addr = parse_binding_response(&response, &transaction_id).unwrap();
+    assert_eq!(ip_addr, "198.51.100.7".parse::<IpAddr>().unwrap());
+}
+
+#[test]
+fn test_consensus() {
+    let a: IpAddr = "203.0.113.5".parse().unwrap();
+    let b: IpAddr = "203.0.113.6".parse().unwrap();
+    let v6: IpAddr = "2001:db8::1".parse().unwrap();
+
+    // The majority wins
+    assert_eq!(consensus(&[vec![a], vec![a], vec![b]]), vec![a]);
+    // A tie is no consensus
+    assert!(consensus(&[vec![a], vec![b]]).is_empty());
+    // Families are voted on separately
+    assert_eq!(consensus(&[vec![a, v6], vec![a], vec![a]]), vec![a, v6]);
+}
+
+#[test]
+fn test_parse_addresses() {
+    assert_eq!(
+        parse_addresses(" 203.0.113.5\n2001:db8::1\n").unwrap(),
+        vec![
+            "203.0.113.5".parse::<IpAddr>().unwrap(),
+            "2001:db8::1".parse().unwrap()
+        ]
+    );
+    assert!(parse_addresses("<html>").is_err());
+}
+
+#[tokio::test]
+async fn test_resolve_sources() {
+    let sources = vec![
+        AddressSourceConfig::Command {
+            command: vec!["false".to_string()],
+        },
+        AddressSourceConfig::Static {
+            value: "203.0.113.5 2001:db8::1".to_string(),
+        },
+        AddressSourceConfig::Command {
+            command: vec!["echo".to_string(), "203.0.113.6".to_string()],
+        },
+    ];
+
+    // The first source that works is used, filtered by protocol
+    let ip_addrs = resolve_sources(&sources, SourceStrategy::Fallback, None, None)
+        .await
+        .unwrap();
+    assert_eq!(ip_addrs, vec!["203.0.113.5".parse::<IpAddr>().unwrap()]);
+    let ip_addrs = resolve_sources(
+        &sources,
+        SourceStrategy::Fallback,
+        None,
+        Some("ipv6".to_string()),
+    )
+    .await
+    .unwrap();
+    assert_eq!(ip_addrs, vec!["2001:db8::1".parse::<IpAddr>().unwrap()]);
+
+    // The two working sources disagree on IPv4
+    assert!(
+        resolve_sources(&sources, SourceStrategy::Consensus, None, None)
+            .await
+            .is_err()
+    );
+
+    let mut sources = sources;
+    sources.push(AddressSourceConfig::Static {
+        value: "203.0.113.6".to_string(),
+    });
+    let ip_addrs = resolve_sources(
+        &sources,
+        SourceStrategy::Consensus,
+        None,
+        Some("both".to_string()),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        ip_addrs,
+        vec![
+            "203.0.113.6".parse::<IpAddr>().unwrap(),
+            "2001:db8::1".parse().unwrap()
+        ]
+    );
+}
@@ -0,0 +1,115 @@
+use swandns::output::{render, Table};
+use swandns::proto::{ChangeAction, HistoryAction, HistoryEntry, RecordChange, RecordReply};
+use swandns::rpc_server::zone_statuses;
+use swandns::{OutputFormat, RecordConfig, ServerConfig, ViewConfig, ZoneConfig};
+
+fn record(name: &str, view: &str, healthy: bool) -> RecordReply {
+    RecordReply {
+        name: name.to_string(),
+        r#type: "A".to_string(),
+        data: "192.168.1.5".to_string(),
+        ttl: 30,
+        healthy,
+        view: view.to_string(),
+        values: vec!["192.168.1.5".to_string()],
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_zone_statuses() {
+    let cfg = ServerConfig {
+        zones: vec![
+            ZoneConfig {
+                name: "example.com".to_string(),
+                records: vec![RecordConfig {
+                    key: "foo".to_string(),
+                    value: "192.168.1.5".to_string(),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            ZoneConfig {
+                name: "lab.example.com".to_string(),
+                ..Default::default()
+            },
+        ],
+        views: vec![ViewConfig {
+            name: "lan".to_string(),
+            zones: vec![ZoneConfig {
+                name: "example.com".to_string(),
+                dnssec_validate: Some(true),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let records = vec![
+        record("bar.example.com", "", true),
+        record("baz.lab.example.com", "", false),
+        record("qux.example.com", "lan", true),
+        record("example.org", "", true),
+    ];
+
+    let statuses = zone_statuses(&cfg, &records);
+    assert_eq!(statuses.len(), 3);
+
+    // Records count towards the most specific zone
+    assert_eq!(statuses[0].name, "example.com");
+    assert_eq!(statuses[0].view, "");
+    assert_eq!(statuses[0].static_records, 1);
+    assert_eq!(statuses[0].records, 1);
+    assert_eq!(statuses[0].healthy_records, 1);
+    assert_eq!(statuses[1].name, "lab.example.com");
+    assert_eq!(statuses[1].records, 1);
+    assert_eq!(statuses[1].healthy_records, 0);
+
+    // Views see their own records and the ones published to every view
+    assert_eq!(statuses[2].name, "example.com");
+    assert_eq!(statuses[2].view, "lan");
+    assert_eq!(statuses[2].records, 3);
+    assert_eq!(statuses[2].healthy_records, 2);
+    assert!(statuses[2].dnssec_validate);
+}
+
+#[test]
+fn test_render() {
+    let records = vec![record("bar.example.com", "", true)];
+    let table = |records: &Vec<RecordReply>| {
+        let mut table = Table::new(&["NAME", "VALUES"]);
+        for record in records {
+            table.row(vec![record.name.clone(), record.values.join(" ")]);
+        }
+        table
+    };
+
+    assert_eq!(
+        render(OutputFormat::Table, &records, table).unwrap(),
+        "NAME             VALUES\nbar.example.com  192.168.1.5\n"
+    );
+    let json = render(OutputFormat::Json, &records, table).unwrap();
+    assert!(json.contains("\"name\": \"bar.example.com\""));
+    let yaml = render(OutputFormat::Yaml, &records, table).unwrap();
+    assert!(yaml.contains("name: bar.example.com"));
+
+    // Enums are printed by name
+    let change = RecordChange {
+        action: ChangeAction::Add.into(),
+        ..Default::default()
+    };
+    let json = render(OutputFormat::Json, &change, |_| "").unwrap();
+    assert!(json.contains("\"action\": \"ADD\""));
+    let entry = HistoryEntry {
+        action: HistoryAction::Delete.into(),
+        ..Default::default()
+    };
+    let yaml = render(OutputFormat::Yaml, &entry, |_| "").unwrap();
+    assert!(yaml.contains("action: HISTORY_ACTION_DELETE"));
+    let unknown = HistoryEntry {
+        action: 42,
+        ..Default::default()
+    };
+    let json = render(OutputFormat::Json, &unknown, |_| "").unwrap();
+    assert!(json.contains("\"action\": 42"));
+}
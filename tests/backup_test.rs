@@ -0,0 +1,99 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use swandns::backup::{backup_dir, create_backup, list_backups, restore_backup};
+use swandns::proto::{FindUniqueRecordRequest, UpsertRecordRequest};
+use swandns::record_store::{open_store, RecordError};
+use swandns::{ServerConfig, StorageBackend};
+
+fn config(name: &str) -> ServerConfig {
+    let mut cfg = ServerConfig {
+        data_dir: std::env::temp_dir().join(format!(
+            "swandns-backup-{}-{}",
+            name,
+            rand::random::<u32>()
+        )),
+        ..Default::default()
+    };
+    cfg.backup.keep = 2;
+    cfg
+}
+
+fn find() -> FindUniqueRecordRequest {
+    FindUniqueRecordRequest {
+        name: "foo.example.com".to_string(),
+        r#type: "A".to_string(),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_backup_and_restore() {
+    let cfg = config("source");
+    let store = open_store(&cfg).await.unwrap();
+    store
+        .upsert(UpsertRecordRequest {
+            name: "foo.example.com".to_string(),
+            r#type: "A".to_string(),
+            ttl: 30,
+            values: vec!["192.168.1.5".to_string()],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert!(store.integrity_check().await.unwrap().is_empty());
+
+    // Only the most recent backups are kept
+    let mut backups = vec![];
+    for _ in 0..3 {
+        backups.push(create_backup(store.as_ref(), &cfg).await.unwrap());
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    assert_eq!(
+        list_backups(&backup_dir(&cfg)).await.unwrap(),
+        backups[1..].to_vec()
+    );
+
+    // Restoring brings back the records
+    let target = config("target");
+    restore_backup(&target, &backups[2]).await.unwrap();
+    let restored = open_store(&target).await.unwrap();
+    let record = restored.find_unique(find()).await.unwrap();
+    assert_eq!(record.values, vec!["192.168.1.5"]);
+
+    // Files that aren't a database of this version are refused
+    let missing = cfg.data_dir.join("missing.db");
+    assert!(restore_backup(&target, &missing).await.is_err());
+    let empty = cfg.data_dir.join("empty.db");
+    tokio::fs::write(&empty, b"").await.unwrap();
+    assert!(restore_backup(&target, &empty).await.is_err());
+    let newer = newer_backup(&backups[2]).await;
+    let err = restore_backup(&target, &newer).await.unwrap_err();
+    assert!(format!("{:#}", err).contains("newer version"));
+    assert!(restored.find_unique(find()).await.is_ok());
+}
+
+/// A copy of the backup claiming a schema version from the future.
+async fn newer_backup(backup: &Path) -> PathBuf {
+    let newer = backup.with_extension("newer");
+    tokio::fs::copy(backup, &newer).await.unwrap();
+    let conn = tokio_rusqlite::Connection::open(&newer).await.unwrap();
+    conn.call(|conn| {
+        conn.pragma_update(None, "user_version", 1000)?;
+        Ok(())
+    })
+    .await
+    .unwrap();
+    newer
+}
+
+#[tokio::test]
+async fn test_memory_backup() {
+    let cfg = ServerConfig {
+        storage: StorageBackend::Memory,
+        ..config("memory")
+    };
+    let store = open_store(&cfg).await.unwrap();
+    let err = store.backup(cfg.data_dir.join("backup.db")).await;
+    assert!(matches!(err, Err(RecordError::Unsupported(_))));
+    assert!(create_backup(store.as_ref(), &cfg).await.is_err());
+}
@@ -0,0 +1,87 @@
+use hickory_server::proto::rr::rdata::{A, AAAA};
+use hickory_server::proto::rr::{RData, Record};
+use hickory_server::resolver::Name;
+use std::net::IpAddr;
+use swandns::forwarder::ClientSubnetPolicy;
+use swandns::sortlist::Sortlist;
+use swandns::{ClientSubnetConfig, ClientSubnetOverrideConfig, SortlistConfig};
+
+fn address_record(address: &str) -> Record {
+    let rdata = match address.parse().unwrap() {
+        IpAddr::V4(v4) => RData::A(A(v4)),
+        IpAddr::V6(v6) => RData::AAAA(AAAA(v6)),
+    };
+    Record::from_rdata(Name::from_ascii("www.example.com.").unwrap(), 30, rdata)
+}
+
+fn addresses(records: &[Record]) -> Vec<String> {
+    records
+        .iter()
+        .map(|record| record.data().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn test_client_subnet() {
+    let policy = ClientSubnetPolicy::from_config(&ClientSubnetConfig {
+        overrides: vec![ClientSubnetOverrideConfig {
+            clients: "10.20.0.0/16".to_string(),
+            subnet: "203.0.113.77/24".to_string(),
+        }],
+        ..Default::default()
+    })
+    .unwrap();
+
+    // Public clients are truncated to the prefix length
+    assert_eq!(
+        policy.subnet_for("198.51.100.42".parse().unwrap()),
+        Some("198.51.100.0/24".parse().unwrap())
+    );
+    assert_eq!(
+        policy.subnet_for("2001:db8:1234:5678::1".parse().unwrap()),
+        Some("2001:db8:1234:5600::/56".parse().unwrap())
+    );
+    assert_eq!(
+        policy.subnet_for("::ffff:198.51.100.42".parse().unwrap()),
+        Some("198.51.100.0/24".parse().unwrap())
+    );
+
+    // Private clients aren't sent unless they're overridden
+    assert_eq!(policy.subnet_for("192.168.1.5".parse().unwrap()), None);
+    assert_eq!(policy.subnet_for("fd00::5".parse().unwrap()), None);
+    assert_eq!(policy.subnet_for("127.0.0.1".parse().unwrap()), None);
+    assert_eq!(
+        policy.subnet_for("10.20.3.4".parse().unwrap()),
+        Some("203.0.113.0/24".parse().unwrap())
+    );
+}
+
+#[test]
+fn test_sortlist() {
+    let sortlist = Sortlist::new(SortlistConfig::default());
+    let mut records = vec![
+        address_record("10.0.1.5"),
+        address_record("10.0.2.5"),
+        address_record("10.0.2.6"),
+        address_record("fd00:0:0:2::5"),
+    ];
+
+    sortlist.sort(&mut records, "10.0.2.100".parse().unwrap());
+    assert_eq!(
+        addresses(&records),
+        vec!["10.0.2.5", "10.0.2.6", "10.0.1.5", "fd00:0:0:2::5"]
+    );
+
+    sortlist.sort(&mut records, "fd00:0:0:2::100".parse().unwrap());
+    assert_eq!(
+        addresses(&records),
+        vec!["fd00:0:0:2::5", "10.0.2.5", "10.0.2.6", "10.0.1.5"]
+    );
+
+    // Clients outside every network keep the original order
+    sortlist.sort(&mut records, "192.168.1.5".parse().unwrap());
+    assert_eq!(
+        addresses(&records),
+        vec!["fd00:0:0:2::5", "10.0.2.5", "10.0.2.6", "10.0.1.5"]
+    );
+}
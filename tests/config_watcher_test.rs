@@ -0,0 +1,85 @@
+use std::sync::Arc;
+use std::time::Duration;
+use swandns::config_watcher::watch_config;
+use swandns::memory_store::MemoryStore;
+use swandns::record_store::RecordStore;
+use swandns::zone_includes::load_server_config;
+use swandns::ServerConfig;
+use tokio::sync::watch;
+
+fn repo() -> Arc<dyn RecordStore> {
+    Arc::new(MemoryStore::default())
+}
+
+#[tokio::test]
+async fn test_watch_config() {
+    let path = std::env::temp_dir().join(format!("swandns-server-{}.yaml", rand::random::<u32>()));
+    std::fs::write(&path, "dns_port: 1053\n").unwrap();
+    let (sender, mut configs) = watch::channel(Arc::new(ServerConfig::default()));
+    let watcher = tokio::spawn(watch_config("server", path.clone(), repo(), sender));
+
+    // Changes to the file are picked up
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    std::fs::write(&path, "dns_port: 1053\nnameservers: [\"1.1.1.1\"]\n").unwrap();
+    tokio::time::timeout(Duration::from_secs(5), configs.changed())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(configs.borrow_and_update().nameservers, vec!["1.1.1.1"]);
+
+    // Configs that don't parse are skipped
+    std::fs::write(&path, "dns_port: [not a port\n").unwrap();
+    assert!(
+        tokio::time::timeout(Duration::from_secs(3), configs.changed())
+            .await
+            .is_err()
+    );
+    assert_eq!(configs.borrow().nameservers, vec!["1.1.1.1"]);
+
+    // Configs whose zones don't build are skipped too
+    std::fs::write(
+        &path,
+        "dns_port: 1053\nzones:\n  - name: example.com\n    records:\n      - key: www\n        value: not an address\n",
+    )
+    .unwrap();
+    assert!(
+        tokio::time::timeout(Duration::from_secs(3), configs.changed())
+            .await
+            .is_err()
+    );
+    assert_eq!(configs.borrow().nameservers, vec!["1.1.1.1"]);
+
+    watcher.abort();
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn test_watch_includes() {
+    let dir = std::env::temp_dir().join(format!("swandns-watch-{}", rand::random::<u32>()));
+    std::fs::create_dir_all(dir.join("records")).unwrap();
+    let path = dir.join("server.yaml");
+    std::fs::write(
+        &path,
+        "zones:\n  - name: example.com\n    records_dir: records\n",
+    )
+    .unwrap();
+    let cfg = load_server_config("server", &path).await.unwrap();
+    let (sender, mut configs) = watch::channel(Arc::new(cfg));
+    let watcher = tokio::spawn(watch_config("server", path.clone(), repo(), sender));
+
+    // Files added to an included directory are picked up
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    std::fs::write(
+        dir.join("records/www.yaml"),
+        "records:\n  - key: www\n    value: 192.168.1.5\n",
+    )
+    .unwrap();
+    tokio::time::timeout(Duration::from_secs(5), configs.changed())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(configs.borrow_and_update().zones[0].records[0].key, "www");
+
+    watcher.abort();
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -9,12 +9,14 @@ use std::net::SocketAddr;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
-use swandns::dns_server::DnsServer;
+use swandns::dns_server::{static_rrset, DnsServer};
 use swandns::proto::UpsertRecordRequest;
 use swandns::record_repository::RecordRepository;
+use swandns::record_store::RecordStore;
 use swandns::util::{configure_tracing, migrate_database};
-use swandns::{RecordConfig, ServerConfig, ZoneConfig};
+use swandns::{AclConfig, LookupTier, RecordConfig, ServerConfig, ViewConfig, ZoneConfig};
 use tokio::net::TcpStream as TokioTcpStream;
+use tokio::sync::watch;
 use tokio_rusqlite::Connection;
 use tracing::debug;
 
@@ -58,16 +60,20 @@ async fn test_resolve_dns() {
                     RecordConfig {
                         key: "www".to_string(),
                         value: "127.0.0.1".to_string(),
+                        ..Default::default()
                     },
                     RecordConfig {
                         key: "@".to_string(),
                         value: "127.0.0.2".to_string(),
+                        ..Default::default()
                     },
                 ],
+                ..Default::default()
             },
             ZoneConfig {
                 name: "example.org".to_string(),
                 records: vec![],
+                ..Default::default()
             },
         ],
         ..Default::default()
@@ -97,6 +103,7 @@ async fn test_resolve_dns() {
         r#type: "A".to_string(),
         value: "127.0.0.3".to_string(),
         ttl: 30,
+        ..Default::default()
     })
     .await
     .unwrap();
@@ -126,3 +133,415 @@ async fn test_resolve_dns() {
 
     dns_server_fut.abort();
 }
+
+#[tokio::test]
+async fn test_resolve_dns_views() {
+    let conn = Arc::new(Connection::open_in_memory().await.unwrap());
+    migrate_database(conn.clone()).await.unwrap();
+    let repo = Arc::new(RecordRepository { conn });
+    let cfg: Arc<ServerConfig> = Arc::new(ServerConfig {
+        dns_port: 1054,
+        nameservers: vec!["1.1.1.1".to_string()],
+        zones: vec![ZoneConfig {
+            name: "example.com".to_string(),
+            records: vec![RecordConfig {
+                key: "www".to_string(),
+                value: "10.0.0.1".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }],
+        views: vec![ViewConfig {
+            name: "lan".to_string(),
+            match_clients: vec!["127.0.0.0/8".to_string()],
+            zones: vec![ZoneConfig {
+                name: "example.com".to_string(),
+                records: vec![RecordConfig {
+                    key: "www".to_string(),
+                    value: "192.168.1.1".to_string(),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }],
+        ..Default::default()
+    });
+
+    let dns_server = Arc::new(DnsServer {
+        repo: repo.clone(),
+        cfg,
+    });
+    let socket_addr = dns_server.get_socket_addr().unwrap();
+    let dns_server_fut = tokio::spawn(async move { dns_server.run().await });
+
+    // Wait for server to start
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let mut client = create_client(socket_addr).await.unwrap();
+
+    // Static record from the matching view
+    test_query(&mut client, "www.example.com", RecordType::A, "192.168.1.1").await;
+
+    // Records tagged with the view win over untagged records
+    for (view, value) in [("", "10.0.0.3"), ("lan", "192.168.1.3")] {
+        repo.upsert(UpsertRecordRequest {
+            name: "foo.example.com".to_string(),
+            r#type: "A".to_string(),
+            value: value.to_string(),
+            ttl: 30,
+            view: view.to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    }
+    test_query(&mut client, "foo.example.com", RecordType::A, "192.168.1.3").await;
+
+    // Untagged records are visible in every view
+    repo.upsert(UpsertRecordRequest {
+        name: "bar.example.com".to_string(),
+        r#type: "A".to_string(),
+        value: "10.0.0.4".to_string(),
+        ttl: 30,
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    test_query(&mut client, "bar.example.com", RecordType::A, "10.0.0.4").await;
+
+    dns_server_fut.abort();
+}
+
+#[tokio::test]
+async fn test_resolve_dns_destination_views() {
+    let conn = Arc::new(Connection::open_in_memory().await.unwrap());
+    migrate_database(conn.clone()).await.unwrap();
+    let repo = Arc::new(RecordRepository { conn });
+    let zone = |value: &str| ZoneConfig {
+        name: "example.com".to_string(),
+        records: vec![RecordConfig {
+            key: "www".to_string(),
+            value: value.to_string(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let cfg: Arc<ServerConfig> = Arc::new(ServerConfig {
+        dns_port: 1058,
+        zones: vec![zone("10.0.0.1")],
+        views: vec![
+            ViewConfig {
+                name: "other".to_string(),
+                match_destinations: vec!["198.51.100.1/32".to_string()],
+                zones: vec![zone("10.0.0.9")],
+                ..Default::default()
+            },
+            ViewConfig {
+                name: "loopback".to_string(),
+                match_destinations: vec!["127.0.0.0/8".to_string()],
+                zones: vec![zone("192.168.1.1")],
+                ..Default::default()
+            },
+        ],
+        ..Default::default()
+    });
+
+    let dns_server = Arc::new(DnsServer { repo, cfg });
+    let dns_server_fut = tokio::spawn(async move { dns_server.run().await });
+
+    // Wait for server to start
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    // Views are selected by the address the query was sent to
+    let mut client = create_client("127.0.0.1:1058".parse().unwrap())
+        .await
+        .unwrap();
+    test_query(&mut client, "www.example.com", RecordType::A, "192.168.1.1").await;
+
+    // Other addresses of the host get the default zones
+    if let Ok(ip_addr) = local_ip_address::local_ip() {
+        let mut client = create_client(SocketAddr::new(ip_addr, 1058)).await.unwrap();
+        test_query(&mut client, "www.example.com", RecordType::A, "10.0.0.1").await;
+    }
+
+    dns_server_fut.abort();
+}
+
+#[tokio::test]
+async fn test_resolve_dns_acl() {
+    let conn = Arc::new(Connection::open_in_memory().await.unwrap());
+    migrate_database(conn.clone()).await.unwrap();
+    let repo = Arc::new(RecordRepository { conn });
+    let cfg: Arc<ServerConfig> = Arc::new(ServerConfig {
+        dns_port: 1055,
+        nameservers: vec!["1.1.1.1".to_string()],
+        recursion_acl: AclConfig {
+            deny: vec!["127.0.0.0/8".to_string()],
+            ..Default::default()
+        },
+        zones: vec![
+            ZoneConfig {
+                name: "example.com".to_string(),
+                records: vec![RecordConfig {
+                    key: "www".to_string(),
+                    value: "127.0.0.1".to_string(),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            ZoneConfig {
+                name: "example.org".to_string(),
+                query_acl: AclConfig {
+                    allow: vec!["192.168.1.0/24".to_string()],
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        ],
+        ..Default::default()
+    });
+
+    let dns_server = Arc::new(DnsServer { repo, cfg });
+    let socket_addr = dns_server.get_socket_addr().unwrap();
+    let dns_server_fut = tokio::spawn(async move { dns_server.run().await });
+
+    // Wait for server to start
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let mut client = create_client(socket_addr).await.unwrap();
+
+    // Local records are still served without recursion
+    test_query(&mut client, "www.example.com", RecordType::A, "127.0.0.1").await;
+
+    // Forwarding is refused
+    let res = client
+        .query(
+            Name::from_str("foo.example.com").unwrap(),
+            DNSClass::IN,
+            RecordType::A,
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.header().response_code(), ResponseCode::Refused);
+
+    // Zone not allowed for this client
+    let res = client
+        .query(
+            Name::from_str("www.example.org").unwrap(),
+            DNSClass::IN,
+            RecordType::A,
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.header().response_code(), ResponseCode::Refused);
+
+    dns_server_fut.abort();
+}
+
+#[tokio::test]
+async fn test_reload() {
+    let conn = Arc::new(Connection::open_in_memory().await.unwrap());
+    migrate_database(conn.clone()).await.unwrap();
+    let repo = Arc::new(RecordRepository { conn });
+    let zone = |value: &str| ZoneConfig {
+        name: "example.com".to_string(),
+        records: vec![RecordConfig {
+            key: "www".to_string(),
+            value: value.to_string(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let cfg = Arc::new(ServerConfig {
+        dns_port: 1056,
+        zones: vec![zone("127.0.0.1")],
+        ..Default::default()
+    });
+
+    let (sender, configs) = watch::channel(cfg.clone());
+    let dns_server = Arc::new(DnsServer {
+        repo,
+        cfg: cfg.clone(),
+    });
+    let socket_addr = dns_server.get_socket_addr().unwrap();
+    let dns_server_fut = tokio::spawn(async move { dns_server.run_with_reloads(configs).await });
+
+    // Wait for server to start
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let mut client = create_client(socket_addr).await.unwrap();
+    test_query(&mut client, "www.example.com", RecordType::A, "127.0.0.1").await;
+
+    // Changed static records are served without a restart
+    sender.send_replace(Arc::new(ServerConfig {
+        zones: vec![zone("127.0.0.2")],
+        ..(*cfg).clone()
+    }));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    test_query(&mut client, "www.example.com", RecordType::A, "127.0.0.2").await;
+
+    // Invalid configs are rejected and the previous zones stay up
+    sender.send_replace(Arc::new(ServerConfig {
+        zones: vec![zone("not an address")],
+        ..(*cfg).clone()
+    }));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    test_query(&mut client, "www.example.com", RecordType::A, "127.0.0.2").await;
+
+    dns_server_fut.abort();
+}
+
+#[tokio::test]
+async fn test_lookup_order() {
+    let conn = Arc::new(Connection::open_in_memory().await.unwrap());
+    migrate_database(conn.clone()).await.unwrap();
+    let repo = Arc::new(RecordRepository { conn });
+    let www = || RecordConfig {
+        key: "www".to_string(),
+        value: "127.0.0.1".to_string(),
+        ..Default::default()
+    };
+    let cfg: Arc<ServerConfig> = Arc::new(ServerConfig {
+        dns_port: 1057,
+        nameservers: vec!["1.1.1.1".to_string()],
+        zones: vec![
+            ZoneConfig {
+                name: "example.com".to_string(),
+                records: vec![www()],
+                forward: Some(false),
+                ..Default::default()
+            },
+            ZoneConfig {
+                name: "example.org".to_string(),
+                records: vec![www()],
+                lookup_order: Some(vec![LookupTier::Dynamic, LookupTier::Static]),
+                allow_shadowing: true,
+                ..Default::default()
+            },
+        ],
+        ..Default::default()
+    });
+    for (name, r#type, value) in [
+        ("www.example.com", "A", "10.0.0.1"),
+        ("www.example.com", "AAAA", "fd00::1"),
+        ("app.example.com", "A", "10.0.0.2"),
+        ("a.b.example.com", "A", "10.0.0.3"),
+        ("example.com", "A", "10.0.0.5"),
+        ("www.example.org", "A", "10.0.0.4"),
+    ] {
+        repo.upsert(UpsertRecordRequest {
+            name: name.to_string(),
+            r#type: r#type.to_string(),
+            value: value.to_string(),
+            ttl: 30,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    }
+
+    let dns_server = Arc::new(DnsServer { repo, cfg });
+    let socket_addr = dns_server.get_socket_addr().unwrap();
+    let dns_server_fut = tokio::spawn(async move { dns_server.run().await });
+
+    // Wait for server to start
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let mut client = create_client(socket_addr).await.unwrap();
+
+    // Static records aren't shadowed by dynamic ones unless the zone allows it
+    test_query(&mut client, "www.example.com", RecordType::A, "127.0.0.1").await;
+    test_query(&mut client, "app.example.com", RecordType::A, "10.0.0.2").await;
+    test_query(&mut client, "www.example.org", RecordType::A, "10.0.0.4").await;
+
+    // Only RRsets of the same type are shadowed, other types and names above static ones are
+    // served
+    test_query(&mut client, "www.example.com", RecordType::AAAA, "fd00::1").await;
+    test_query(&mut client, "example.com", RecordType::A, "10.0.0.5").await;
+
+    // Names in the zone without the type are NODATA, other names NXDOMAIN
+    for (name, query_type, response_code) in [
+        ("www.example.com", RecordType::TXT, ResponseCode::NoError),
+        ("app.example.com", RecordType::AAAA, ResponseCode::NoError),
+        ("b.example.com", RecordType::A, ResponseCode::NoError),
+        ("foo.example.com", RecordType::A, ResponseCode::NXDomain),
+    ] {
+        let res = client
+            .query(Name::from_str(name).unwrap(), DNSClass::IN, query_type)
+            .await
+            .unwrap();
+        assert_eq!(res.header().response_code(), response_code, "{}", name);
+        assert!(res.answers().is_empty());
+    }
+
+    dns_server_fut.abort();
+}
+
+#[test]
+fn test_static_rrset() {
+    let zone = ZoneConfig {
+        name: "example.com".to_string(),
+        default_ttl: Some(300),
+        ..Default::default()
+    };
+
+    // Types are inferred from addresses, TTLs default to the zone's
+    let records = static_rrset(
+        &zone,
+        &RecordConfig {
+            key: "www".to_string(),
+            values: vec!["127.0.0.1".to_string(), "::1".to_string()],
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].record_type(), RecordType::A);
+    assert_eq!(records[1].record_type(), RecordType::AAAA);
+    assert_eq!(records[1].ttl(), 300);
+
+    // Other types are given explicitly
+    let records = static_rrset(
+        &zone,
+        &RecordConfig {
+            key: "@".to_string(),
+            r#type: Some("txt".to_string()),
+            value: "v=spf1 -all".to_string(),
+            ttl: Some(3600),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(records[0].record_type(), RecordType::TXT);
+    assert_eq!(records[0].ttl(), 3600);
+
+    // Errors name the zone and key
+    let err = static_rrset(
+        &zone,
+        &RecordConfig {
+            key: "mail".to_string(),
+            value: "mx.example.com.".to_string(),
+            ..Default::default()
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Invalid record \"mail\" in zone \"example.com\": \"mx.example.com.\" is not an address, \
+         the record needs a type"
+    );
+    let err = static_rrset(
+        &zone,
+        &RecordConfig {
+            key: "mail".to_string(),
+            r#type: Some("MX".to_string()),
+            value: "mx.example.com.".to_string(),
+            ..Default::default()
+        },
+    )
+    .unwrap_err();
+    assert!(err
+        .to_string()
+        .starts_with("Invalid record \"mail\" in zone \"example.com\""));
+}
@@ -0,0 +1,267 @@
+use hickory_server::authority::LookupError;
+use hickory_server::proto::op::{Message, MessageType, Query, ResponseCode};
+use hickory_server::proto::rr::dnssec::rdata::{DNSSECRData, DNSKEY, NSEC, RRSIG, SIG};
+use hickory_server::proto::rr::dnssec::tbs::rrset_tbs_with_sig;
+use hickory_server::proto::rr::dnssec::{
+    Algorithm, DigestType, KeyFormat, KeyPair, Private, PublicKeyBuf, TrustAnchor,
+};
+use hickory_server::proto::rr::rdata::A;
+use hickory_server::proto::rr::{DNSClass, LowerName, RData, Record, RecordType};
+use hickory_server::resolver::config::{NameServerConfig, NameServerConfigGroup, Protocol};
+use hickory_server::resolver::Name;
+use std::fs;
+use std::net::SocketAddr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use swandns::dnssec::{load_trust_anchor, Security, Validator};
+use swandns::forwarder::{Forwarder, Upstreams};
+use tokio::net::UdpSocket;
+
+static ROOT_KSK_2017: &str = ". 172800 IN DNSKEY 257 3 8 AwEAAaz/tAm8yTn4Mfeh5eyI96WSVexTBAvkMgJzkKTOiW1vkIbzxeF3+/4RgWOq7HrxRixHlFlExOLAJr5emLvN7SWXgnLh4+B5xQlNVz8Og8kvArMtNROxVQuCaSnIDdD5LKyWbRd2n9WGe2R8PzgCmr3EgVLrjyBxWezF0jLHwVN8efS3rCj/EWgvIWgb9tarpVUDK/b58Da+sqqls3eNbuv7pr+eoZG+SrDK6nWeL3c6H5Apxz7LjVc1uTIdsIXxuOLYA4/ilBmSVIzuDWfdRUfhHdY6+cn8HFRm+2hM8AnXGXws9555KrUB5qihylGa8subX2Nn6UwNR1AkUTV74bU=";
+
+#[test]
+fn test_load_trust_anchor() {
+    let dir = std::env::temp_dir().join("swandns-dnssec-test");
+    fs::create_dir_all(&dir).unwrap();
+
+    // DNSKEY records are loaded
+    let path = dir.join("root.key");
+    fs::write(&path, format!("{}\n", ROOT_KSK_2017)).unwrap();
+    let trust_anchor = load_trust_anchor(&path).unwrap();
+    assert_eq!(trust_anchor.len(), 1);
+
+    // Files without any DNSKEY records are rejected
+    let path = dir.join("empty.key");
+    fs::write(&path, ". 300 IN A 127.0.0.1\n").unwrap();
+    assert!(load_trust_anchor(&path).is_err());
+}
+
+/// A zone's Ed25519 signing key.
+struct ZoneKey {
+    zone: Name,
+    pair: KeyPair<Private>,
+    dnskey: DNSKEY,
+}
+
+impl ZoneKey {
+    fn generate(zone: &str) -> Self {
+        let pkcs8 = KeyPair::<Private>::generate_pkcs8(Algorithm::ED25519).unwrap();
+        let pair = KeyFormat::Pkcs8
+            .decode_key(&pkcs8, None, Algorithm::ED25519)
+            .unwrap();
+        let dnskey = pair.to_dnskey(Algorithm::ED25519).unwrap();
+        Self {
+            zone: name(zone),
+            pair,
+            dnskey,
+        }
+    }
+
+    fn ds(&self) -> Vec<Record> {
+        let ds = self
+            .pair
+            .to_ds(&self.zone, Algorithm::ED25519, DigestType::SHA256)
+            .unwrap();
+        vec![record(
+            self.zone.clone(),
+            RData::DNSSEC(DNSSECRData::DS(ds)),
+        )]
+    }
+
+    fn dnskey(&self) -> Vec<Record> {
+        self.sign(vec![record(
+            self.zone.clone(),
+            RData::DNSSEC(DNSSECRData::DNSKEY(self.dnskey.clone())),
+        )])
+    }
+
+    /// Adds an RRSIG record for an RRset.
+    fn sign(&self, mut records: Vec<Record>) -> Vec<Record> {
+        let name = records[0].name().clone();
+        let rtype = records[0].record_type();
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as u32;
+        let key_tag = self.dnskey.calculate_key_tag().unwrap();
+        let unsigned = SIG::new(
+            rtype,
+            Algorithm::ED25519,
+            name.num_labels(),
+            300,
+            now + 3600,
+            now - 3600,
+            key_tag,
+            self.zone.clone(),
+            vec![],
+        );
+        let tbs = rrset_tbs_with_sig(&name, DNSClass::IN, &unsigned, &records).unwrap();
+        let sig = self.pair.sign(Algorithm::ED25519, &tbs).unwrap();
+        let rrsig = RRSIG::new(
+            rtype,
+            Algorithm::ED25519,
+            name.num_labels(),
+            300,
+            now + 3600,
+            now - 3600,
+            key_tag,
+            self.zone.clone(),
+            sig,
+        );
+        records.push(record(name, RData::DNSSEC(DNSSECRData::RRSIG(rrsig))));
+        records
+    }
+
+    fn nsec(&self, owner: &str, next: &str, types: &[RecordType]) -> Vec<Record> {
+        let nsec = NSEC::new(name(next), types.to_vec());
+        self.sign(vec![record(
+            name(owner),
+            RData::DNSSEC(DNSSECRData::NSEC(nsec)),
+        )])
+    }
+}
+
+fn name(name: &str) -> Name {
+    Name::from_ascii(name).unwrap()
+}
+
+fn record(name: Name, rdata: RData) -> Record {
+    Record::from_rdata(name, 300, rdata)
+}
+
+fn a(owner: &str, address: &str) -> Vec<Record> {
+    vec![record(name(owner), RData::A(A(address.parse().unwrap())))]
+}
+
+type Answer = (ResponseCode, Vec<Record>, Vec<Record>);
+
+/// Serves answers over UDP like a recursive resolver that doesn't validate.
+async fn fake_upstream(answer: impl Fn(&str, RecordType) -> Answer + Send + 'static) -> SocketAddr {
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let addr = socket.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut buf = [0; 4096];
+        loop {
+            let (len, src) = socket.recv_from(&mut buf).await.unwrap();
+            let request = Message::from_vec(&buf[..len]).unwrap();
+            let query = request.queries()[0].clone();
+            let (response_code, answers, authority) =
+                answer(&query.name().to_ascii().to_lowercase(), query.query_type());
+            let mut response = Message::new();
+            response
+                .set_id(request.id())
+                .set_message_type(MessageType::Response)
+                .set_recursion_desired(true)
+                .set_recursion_available(true)
+                .set_response_code(response_code)
+                .add_query(query)
+                .add_answers(answers)
+                .add_name_servers(authority);
+            socket
+                .send_to(&response.to_vec().unwrap(), src)
+                .await
+                .unwrap();
+        }
+    });
+    addr
+}
+
+/// Signs a root zone with a signed delegation to `secure.` and an unsigned one to `insecure.`.
+async fn signed_upstream() -> (SocketAddr, TrustAnchor) {
+    let root = ZoneKey::generate(".");
+    let secure = ZoneKey::generate("secure.");
+    let mut trust_anchor = TrustAnchor::new();
+    trust_anchor.insert_trust_anchor(&PublicKeyBuf::new(root.dnskey.public_key().to_vec()));
+
+    let addr = fake_upstream(move |qname, qtype| {
+        use RecordType::*;
+        let no_error = ResponseCode::NoError;
+        match (qname, qtype) {
+            (".", DNSKEY) => (no_error, root.dnskey(), vec![]),
+            ("secure.", DS) => (no_error, root.sign(secure.ds()), vec![]),
+            ("secure.", DNSKEY) => (no_error, secure.dnskey(), vec![]),
+            ("insecure.", DS) => (
+                no_error,
+                vec![],
+                root.nsec("insecure.", "secure.", &[NS, RRSIG, NSEC]),
+            ),
+            ("www.secure.", DS) => (
+                no_error,
+                vec![],
+                secure.nsec("www.secure.", "secure.", &[A, RRSIG, NSEC]),
+            ),
+            ("www.secure.", A) => (no_error, secure.sign(a(qname, "192.0.2.1")), vec![]),
+            ("www.insecure.", DS) | ("www.insecure.", A) => {
+                (no_error, a("www.insecure.", "192.0.2.2"), vec![])
+            }
+            // Forged answers, unsigned or without the NSEC records proving them
+            ("unsigned.secure.", A) => (no_error, a(qname, "192.0.2.3"), vec![]),
+            ("forged.secure.", A) => (ResponseCode::NXDomain, vec![], vec![]),
+            (_, _) if qname.ends_with(".secure.") => (
+                ResponseCode::NXDomain,
+                vec![],
+                secure.nsec("secure.", "www.secure.", &[SOA, NS, DNSKEY, RRSIG, NSEC]),
+            ),
+            (_, _) => (ResponseCode::Refused, vec![], vec![]),
+        }
+    })
+    .await;
+    (addr, trust_anchor)
+}
+
+fn name_servers(addr: SocketAddr) -> NameServerConfigGroup {
+    NameServerConfigGroup::from(vec![NameServerConfig::new(addr, Protocol::Udp)])
+}
+
+#[tokio::test]
+async fn test_validate() {
+    let (addr, trust_anchor) = signed_upstream().await;
+    let upstreams = Upstreams::new(name_servers(addr));
+    let validator = Validator::new(trust_anchor);
+    let validate = |qname: &str| {
+        let query = Query::query(name(qname), RecordType::A);
+        let upstreams = &upstreams;
+        let validator = &validator;
+        async move {
+            let response = upstreams.query(query.name(), RecordType::A).await.unwrap();
+            validator.validate(upstreams, &query, &response).await
+        }
+    };
+
+    // Answers signed through the chain of trust are secure
+    assert_eq!(validate("www.secure.").await.unwrap(), Security::Secure);
+
+    // Unsigned answers from zones proven to be unsigned are insecure rather than bogus
+    assert_eq!(validate("www.insecure.").await.unwrap(), Security::Insecure);
+    assert!(validate("unsigned.secure.").await.is_err());
+
+    // Negative answers have to be proven
+    assert_eq!(validate("gone.secure.").await.unwrap(), Security::Secure);
+    assert!(validate("forged.secure.").await.is_err());
+}
+
+#[tokio::test]
+async fn test_forwarder_validation() {
+    let (addr, trust_anchor) = signed_upstream().await;
+    let forwarder = Forwarder::new(name_servers(addr), Some(trust_anchor), None);
+    let lookup = |qname: &str| {
+        let qname = LowerName::from(name(qname));
+        let forwarder = &forwarder;
+        async move { forwarder.lookup(&qname, RecordType::A, None).await }
+    };
+
+    let records = lookup("www.insecure.").await.unwrap();
+    assert_eq!(
+        records.records()[0].data().unwrap().to_string(),
+        "192.0.2.2"
+    );
+
+    // Signed negative answers are passed on, forged ones fail
+    assert!(matches!(
+        lookup("gone.secure.").await,
+        Err(LookupError::ResponseCode(ResponseCode::NXDomain))
+    ));
+    assert!(matches!(
+        lookup("forged.secure.").await,
+        Err(LookupError::ResponseCode(ResponseCode::ServFail))
+    ));
+}
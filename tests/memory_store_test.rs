@@ -0,0 +1,11 @@
+#[macro_use]
+mod store_contract;
+
+use swandns::memory_store::MemoryStore;
+use swandns::record_store::RecordStore;
+
+async fn store() -> Option<Box<dyn RecordStore>> {
+    Some(Box::new(MemoryStore::default()))
+}
+
+store_contract_tests!(store());
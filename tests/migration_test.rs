@@ -0,0 +1,105 @@
+use std::sync::Arc;
+use swandns::proto::FindUniqueRecordRequest;
+use swandns::record_repository::RecordRepository;
+use swandns::record_store::RecordStore;
+use swandns::util::{migrate_database, migrations, schema_status};
+use tokio_rusqlite::Connection;
+
+async fn migrate_to(conn: &Connection, version: usize) {
+    conn.call(move |conn| {
+        migrations()
+            .to_version(conn, version)
+            .map_err(|err| tokio_rusqlite::Error::Other(Box::new(err)))
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn test_migrations() {
+    let conn = Arc::new(Connection::open_in_memory().await.unwrap());
+    let status = schema_status(&conn).await.unwrap();
+    assert_eq!(status.current, 0);
+    assert!(!status.is_current());
+
+    // Every migration can be rolled back and applied again
+    migrate_database(conn.clone()).await.unwrap();
+    let latest = schema_status(&conn).await.unwrap().latest;
+    migrate_to(&conn, 0).await;
+    migrate_database(conn.clone()).await.unwrap();
+    let status = schema_status(&conn).await.unwrap();
+    assert_eq!(status.current, latest);
+    assert!(status.is_current());
+}
+
+#[tokio::test]
+async fn test_legacy_timestamps() {
+    let conn = Arc::new(Connection::open_in_memory().await.unwrap());
+    let latest = schema_status(&conn).await.unwrap().latest;
+    migrate_to(&conn, latest - 1).await;
+    conn.call(|conn| {
+        conn.execute_batch(
+            r#"
+INSERT INTO records (name, type, data, ttl, created_at, updated_at)
+VALUES ('foo.example.com', 'A', '192.168.1.5', 30, '2024-01-01 12:00:00', '2024-01-01 12:00:00:5 +00:00');
+INSERT INTO history (name, type, action, created_at)
+VALUES ('foo.example.com', 'A', 'upsert', '2024-01-01 12:00:00.25+00:00');"#,
+        )?;
+        Ok(())
+    })
+    .await
+    .unwrap();
+
+    // Timestamps are rewritten in the format written now
+    migrate_database(conn.clone()).await.unwrap();
+    let timestamps = conn
+        .call(|conn| {
+            let records =
+                conn.query_row("SELECT created_at, updated_at FROM records", [], |row| {
+                    Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+                })?;
+            let history = conn.query_row("SELECT created_at FROM history", [], |row| {
+                row.get::<_, String>(0)
+            })?;
+            Ok((records, history))
+        })
+        .await
+        .unwrap();
+    assert_eq!(
+        timestamps,
+        (
+            (
+                "2024-01-01 12:00:00.0+00:00".to_string(),
+                "2024-01-01 12:00:00.5+00:00".to_string()
+            ),
+            "2024-01-01 12:00:00.25+00:00".to_string()
+        )
+    );
+    let repo = RecordRepository { conn };
+    let record = repo
+        .find_unique(FindUniqueRecordRequest {
+            name: "foo.example.com".to_string(),
+            r#type: "A".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(record.created_at, 1704110400);
+}
+
+#[tokio::test]
+async fn test_newer_schema() {
+    let conn = Arc::new(Connection::open_in_memory().await.unwrap());
+    migrate_database(conn.clone()).await.unwrap();
+    conn.call(|conn| {
+        conn.pragma_update(None, "user_version", 1000)?;
+        Ok(())
+    })
+    .await
+    .unwrap();
+
+    // Databases from newer versions are left alone
+    assert!(schema_status(&conn).await.unwrap().is_newer());
+    let err = migrate_database(conn.clone()).await.unwrap_err();
+    assert!(err.to_string().contains("newer version"));
+}
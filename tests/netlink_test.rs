@@ -0,0 +1,44 @@
+#![cfg(target_os = "linux")]
+
+use swandns::netlink::{interface_name, parse_messages, AddressWatcher};
+
+fn message(r#type: u16, index: u32) -> Vec<u8> {
+    // nlmsghdr followed by an ifaddrmsg
+    let mut message = vec![];
+    message.extend_from_slice(&24u32.to_ne_bytes());
+    message.extend_from_slice(&r#type.to_ne_bytes());
+    message.extend_from_slice(&0u16.to_ne_bytes());
+    message.extend_from_slice(&0u32.to_ne_bytes());
+    message.extend_from_slice(&0u32.to_ne_bytes());
+    message.extend_from_slice(&[libc::AF_INET6 as u8, 64, 0, 0]);
+    message.extend_from_slice(&index.to_ne_bytes());
+    message
+}
+
+#[test]
+fn test_parse_messages() {
+    let mut buf = vec![];
+    buf.extend(message(libc::RTM_DELADDR, 2));
+    buf.extend(message(libc::RTM_NEWADDR, 2));
+    buf.extend(message(libc::RTM_NEWROUTE, 3));
+    buf.extend(message(libc::RTM_NEWLINK, 4));
+    assert_eq!(parse_messages(&buf), vec![2, 4]);
+
+    // Truncated messages are ignored
+    assert_eq!(parse_messages(&buf[..30]), vec![2]);
+    assert!(parse_messages(&[]).is_empty());
+}
+
+#[test]
+fn test_interface_name() {
+    let index = unsafe { libc::if_nametoindex(c"lo".as_ptr()) };
+    assert_ne!(index, 0);
+    assert_eq!(interface_name(index), Some("lo".to_string()));
+    assert_eq!(interface_name(0), None);
+}
+
+#[tokio::test]
+async fn test_address_watcher() {
+    // Subscribing doesn't need any privileges
+    AddressWatcher::new().unwrap();
+}
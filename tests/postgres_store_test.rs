@@ -0,0 +1,172 @@
+#[macro_use]
+mod store_contract;
+
+use std::time::Duration;
+use swandns::postgres_store::{connect, PostgresStore};
+use swandns::proto::{FindUniqueRecordRequest, RecordsQueryRequest, UpsertRecordRequest};
+use swandns::record_store::RecordStore;
+use tokio_postgres::Client;
+
+/// Database the tests run against, override it with `SWANDNS_TEST_POSTGRES_URL`.
+static DEFAULT_URL: &str = "postgres://postgres@localhost:5432/swandns_test";
+
+fn url(options: &str) -> String {
+    let url =
+        std::env::var("SWANDNS_TEST_POSTGRES_URL").unwrap_or_else(|_| DEFAULT_URL.to_string());
+    let separator = if url.contains('?') { '&' } else { '?' };
+    format!("{}{}{}", url, separator, options)
+}
+
+/// Connects to the test database, or returns `None` when it isn't available.
+///
+/// The tables are created in the connection's temporary schema, so every test starts with an
+/// empty store and leaves nothing behind.
+async fn store() -> Option<Box<dyn RecordStore>> {
+    match PostgresStore::connect(&url("options=-csearch_path%3Dpg_temp")).await {
+        Ok(store) => Some(Box::new(store)),
+        Err(err) => {
+            eprintln!("{:#}", err);
+            None
+        }
+    }
+}
+
+/// Connects to the test database in a new schema, with another connection to the schema for
+/// changing the store behind its back. The schema is the store's application name too.
+async fn shared_store() -> Option<(PostgresStore, Client, String)> {
+    let admin = match connect(&url("")).await {
+        Ok(admin) => admin,
+        Err(err) => {
+            eprintln!("{:#}", err);
+            return None;
+        }
+    };
+    let schema = format!("swandns_test_{}", rand::random::<u32>());
+    admin
+        .batch_execute(&format!(
+            "CREATE SCHEMA {0}; SET search_path TO {0}",
+            schema
+        ))
+        .await
+        .unwrap();
+    let store = PostgresStore::connect(&url(&format!(
+        "options=-csearch_path%3D{0}&application_name={0}",
+        schema
+    )))
+    .await
+    .unwrap();
+    Some((store, admin, schema))
+}
+
+async fn drop_schema(admin: &Client, schema: &str) {
+    admin
+        .batch_execute(&format!("DROP SCHEMA {} CASCADE", schema))
+        .await
+        .unwrap();
+}
+
+store_contract_tests!(store());
+
+#[tokio::test]
+async fn test_glob_special_characters() {
+    let Some(store) = store().await else {
+        eprintln!("Skipping test_glob_special_characters, the store isn't available");
+        return;
+    };
+    for name in ["a.example.com", "abexample.com", "a+b.example.com"] {
+        store
+            .upsert(UpsertRecordRequest {
+                name: name.to_string(),
+                r#type: "A".to_string(),
+                value: "192.168.1.5".to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    }
+    let names = |glob: &str| {
+        let store = &store;
+        let query = RecordsQueryRequest {
+            name: glob.to_string(),
+            ..Default::default()
+        };
+        async move {
+            let page = store.query(query).await.unwrap();
+            page.records
+                .into_iter()
+                .map(|record| record.name)
+                .collect::<Vec<String>>()
+        }
+    };
+
+    // Regular expression syntax in globs matches literally
+    assert_eq!(names("a.example.com").await, vec!["a.example.com"]);
+    assert_eq!(names("a+b.*").await, vec!["a+b.example.com"]);
+    assert_eq!(names("a?b.*").await, vec!["a+b.example.com"]);
+    assert!(names("a[").await.is_empty());
+}
+
+#[tokio::test]
+async fn test_reconnect() {
+    let Some((store, admin, schema)) = shared_store().await else {
+        eprintln!("Skipping test_reconnect, the store isn't available");
+        return;
+    };
+    let request = UpsertRecordRequest {
+        name: "a.example.com".to_string(),
+        r#type: "A".to_string(),
+        value: "192.168.1.5".to_string(),
+        ..Default::default()
+    };
+    store.upsert(request.clone()).await.unwrap();
+
+    // The store reconnects once the database closes its connection
+    admin
+        .execute(
+            "SELECT pg_terminate_backend(pid) FROM pg_stat_activity WHERE application_name = $1",
+            &[&schema],
+        )
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    let record = store
+        .find_unique(FindUniqueRecordRequest {
+            name: request.name,
+            r#type: request.r#type,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(record.values, vec!["192.168.1.5"]);
+
+    drop_schema(&admin, &schema).await;
+}
+
+#[tokio::test]
+async fn test_integrity_check() {
+    let Some((store, admin, schema)) = shared_store().await else {
+        eprintln!("Skipping test_integrity_check, the store isn't available");
+        return;
+    };
+    store
+        .upsert(UpsertRecordRequest {
+            name: "a.example.com".to_string(),
+            r#type: "A".to_string(),
+            value: "192.168.1.5".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert!(store.integrity_check().await.unwrap().is_empty());
+
+    admin
+        .execute("UPDATE records SET data = '{}'", &[])
+        .await
+        .unwrap();
+    assert_eq!(
+        store.integrity_check().await.unwrap(),
+        vec!["RRset a.example.com A in view \"\" is invalid"]
+    );
+
+    drop_schema(&admin, &schema).await;
+}
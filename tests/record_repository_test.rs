@@ -0,0 +1,16 @@
+#[macro_use]
+mod store_contract;
+
+use std::sync::Arc;
+use swandns::record_repository::RecordRepository;
+use swandns::record_store::RecordStore;
+use swandns::util::migrate_database;
+use tokio_rusqlite::Connection;
+
+async fn store() -> Option<Box<dyn RecordStore>> {
+    let conn = Arc::new(Connection::open_in_memory().await.unwrap());
+    migrate_database(conn.clone()).await.unwrap();
+    Some(Box::new(RecordRepository { conn }))
+}
+
+store_contract_tests!(store());
@@ -1,12 +1,18 @@
 use std::sync::Arc;
 use std::time::Duration;
-use swandns::client::update_record;
+use swandns::client::{list_records, owner, remove_record, rpc_error, update_record};
+use swandns::proto::admin_client::AdminClient;
+use swandns::proto::error_details::field_violations;
 use swandns::proto::records_client::RecordsClient;
-use swandns::proto::{FindUniqueRecordRequest, RecordReply, RecordsQueryRequest};
+use swandns::proto::{
+    FindUniqueRecordRequest, HistoryAction, HistoryRequest, RecordReply, RecordsQueryRequest,
+    UpsertRecordRequest,
+};
 use swandns::record_repository::RecordRepository;
 use swandns::rpc_server::RpcServer;
+use swandns::update_state::UpdateState;
 use swandns::util::{configure_tracing, migrate_database};
-use swandns::{ClientConfig, ClientRecordConfig};
+use swandns::{ClientConfig, ClientRecordConfig, ServerConfig, ZoneConfig};
 use tokio_rusqlite::Connection;
 use tokio_stream::StreamExt;
 use tonic::Code;
@@ -22,6 +28,14 @@ async fn test_rpc_server() {
     let rpc_server = Arc::new(RpcServer {
         addr: "127.0.0.1:8080".parse().unwrap(),
         repo,
+        cfg: Arc::new(ServerConfig {
+            zones: vec![ZoneConfig {
+                name: "example.com".to_string(),
+                allow_apex: true,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }),
     });
     let rpc_server_fut = tokio::spawn(async move { rpc_server.run().await });
 
@@ -30,19 +44,28 @@ async fn test_rpc_server() {
 
     let server_url = "http://127.0.0.1:8080";
     let mut client = RecordsClient::connect(server_url).await.unwrap();
+    let client_state_file =
+        std::env::temp_dir().join(format!("swandns-state-{}.json", rand::random::<u32>()));
+    let client_cfg = Arc::new(ClientConfig {
+        state_file: Some(client_state_file.clone()),
+        ..Default::default()
+    });
 
     // Create
-    let record = update_record(
-        Arc::new(Default::default()),
+    let records = update_record(
+        client_cfg.clone(),
         ClientRecordConfig {
             server_url: Some(server_url.to_string()),
             name: "example.com".to_string(),
             bind: Some("lo".to_string()),
             protocol: None,
+            ..Default::default()
         },
     )
     .await
     .unwrap();
+    assert_eq!(records.len(), 1);
+    let record = &records[0];
     assert_eq!(record.name, "example.com");
     assert_eq!(record.data, "127.0.0.1");
     assert_eq!(record.r#type, "A");
@@ -52,22 +75,25 @@ async fn test_rpc_server() {
     assert!(record.updated_at > 0);
 
     // Create w/ fallbacks
-    let record = update_record(
+    let records = update_record(
         Arc::new(ClientConfig {
             default_server_url: Some(server_url.to_string()),
             default_bind: Some("lo".to_string()),
             default_protocol: Some("ipv6".to_string()),
-            ..Default::default()
+            ..(*client_cfg).clone()
         }),
         ClientRecordConfig {
             server_url: None,
             name: "example.com".to_string(),
             bind: None,
             protocol: None,
+            ..Default::default()
         },
     )
     .await
     .unwrap();
+    assert_eq!(records.len(), 1);
+    let record = &records[0];
     assert_eq!(record.name, "example.com");
     assert_eq!(record.data, "::1");
     assert_eq!(record.r#type, "AAAA");
@@ -76,11 +102,44 @@ async fn test_rpc_server() {
     assert!(record.created_at > 0);
     assert!(record.updated_at > 0);
 
+    // Dual-stack records publish an RRset for each protocol
+    let records = update_record(
+        client_cfg.clone(),
+        ClientRecordConfig {
+            server_url: Some(server_url.to_string()),
+            name: "example.com".to_string(),
+            bind: Some("lo".to_string()),
+            protocol: Some("both".to_string()),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+    let types: Vec<&str> = records.iter().map(|r| r.r#type.as_str()).collect();
+    assert_eq!(types, vec!["A", "AAAA"]);
+    assert_eq!(records[0].values, vec!["127.0.0.1"]);
+    assert_eq!(records[1].values, vec!["::1"]);
+
+    // Excluded addresses aren't published
+    let err = update_record(
+        client_cfg.clone(),
+        ClientRecordConfig {
+            server_url: Some(server_url.to_string()),
+            name: "example.com".to_string(),
+            bind: Some("lo".to_string()),
+            exclude: vec!["127.0.0.0/8".to_string()],
+            ..Default::default()
+        },
+    )
+    .await;
+    assert!(err.is_err());
+
     // Find unique call
     let record = client
         .find_unique(FindUniqueRecordRequest {
             name: "example.com".to_string(),
             r#type: "AAAA".to_string(),
+            ..Default::default()
         })
         .await
         .unwrap()
@@ -94,14 +153,47 @@ async fn test_rpc_server() {
         .find_unique(FindUniqueRecordRequest {
             name: "google.com".to_string(),
             r#type: "A".to_string(),
+            ..Default::default()
         })
         .await
         .unwrap_err();
     assert_eq!(status.code(), Code::NotFound);
 
+    // Invalid records are rejected with the offending fields
+    let status = client
+        .upsert(UpsertRecordRequest {
+            name: "example.com".to_string(),
+            r#type: "banana".to_string(),
+            value: "192.168.1.5".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+    let violations = field_violations(&status);
+    assert_eq!(violations.len(), 1);
+    assert_eq!(violations[0].field, "type");
+    let err = rpc_error(status);
+    assert!(err
+        .to_string()
+        .contains("type: \"banana\" is not a record type"));
+    assert!(err.downcast_ref::<tonic::Status>().is_some());
+
+    // Records outside the served zones are rejected
+    let status = client
+        .upsert(UpsertRecordRequest {
+            name: "example.net".to_string(),
+            r#type: "A".to_string(),
+            value: "192.168.1.5".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(field_violations(&status)[0].field, "name");
+
     // List call
     let mut stream = client
-        .list(RecordsQueryRequest {})
+        .list(RecordsQueryRequest::default())
         .await
         .unwrap()
         .into_inner();
@@ -112,11 +204,49 @@ async fn test_rpc_server() {
     }
     assert_eq!(records.len(), 2);
 
+    // Paged list call
+    let page = client
+        .list_page(RecordsQueryRequest {
+            name: "EXAMPLE.*".to_string(),
+            page_size: 1,
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(page.records[0].r#type, "A");
+    let page = client
+        .list_page(RecordsQueryRequest {
+            name: "EXAMPLE.*".to_string(),
+            page_size: 1,
+            page_token: page.next_page_token,
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(page.records[0].r#type, "AAAA");
+    assert!(page.next_page_token.is_empty());
+
+    // Someone else can't delete the record while it's healthy
+    let status = client
+        .delete(FindUniqueRecordRequest {
+            name: "example.com".to_string(),
+            r#type: "A".to_string(),
+            owner: "vm2".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+
     // Delete call
     let _response = client
         .delete(FindUniqueRecordRequest {
             name: "example.com".to_string(),
             r#type: "A".to_string(),
+            owner: owner(&ClientConfig::default()),
+            ..Default::default()
         })
         .await
         .unwrap()
@@ -124,7 +254,7 @@ async fn test_rpc_server() {
 
     // Verify delete
     let mut stream = client
-        .list(RecordsQueryRequest {})
+        .list(RecordsQueryRequest::default())
         .await
         .unwrap()
         .into_inner();
@@ -135,5 +265,113 @@ async fn test_rpc_server() {
     }
     assert_eq!(records.len(), 1);
 
+    // Changes are recorded with the client's address
+    let history = AdminClient::connect(server_url)
+        .await
+        .unwrap()
+        .history(HistoryRequest {
+            name: "Example.com.".to_string(),
+            r#type: "a".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(history.entries[0].action(), HistoryAction::Delete);
+    assert_eq!(history.entries[0].actor, "127.0.0.1");
+    assert_eq!(history.entries[1].action(), HistoryAction::Upsert);
+
+    // Removing a record deletes every RRset it published
+    remove_record(
+        client_cfg.clone(),
+        ClientRecordConfig {
+            server_url: Some(server_url.to_string()),
+            name: "example.com".to_string(),
+            ..Default::default()
+        },
+        false,
+    )
+    .await
+    .unwrap();
+    let records = list_records(server_url.to_string()).await.unwrap();
+    assert!(records.is_empty());
+
+    // Published records are remembered, so unchanged ones are only kept alive
+    let state_file =
+        std::env::temp_dir().join(format!("swandns-state-{}.json", rand::random::<u32>()));
+    let cfg = Arc::new(ClientConfig {
+        state_file: Some(state_file.clone()),
+        machine_id: Some("vm1".to_string()),
+        ..Default::default()
+    });
+    let record_config = ClientRecordConfig {
+        server_url: Some(server_url.to_string()),
+        name: "keepalive.example.com".to_string(),
+        bind: Some("lo".to_string()),
+        ..Default::default()
+    };
+    let records = update_record(cfg.clone(), record_config.clone())
+        .await
+        .unwrap();
+    assert_eq!(records[0].revision, 1);
+    let state = UpdateState::load(&state_file).await;
+    assert_eq!(state.records.len(), 1);
+    assert_eq!(state.records[0].values, vec!["127.0.0.1"]);
+    assert_eq!(state.records[0].revision, 1);
+    let records = update_record(cfg.clone(), record_config.clone())
+        .await
+        .unwrap();
+    assert_eq!(records[0].revision, 1);
+
+    // Other hosts can't publish the same record
+    let err = update_record(
+        Arc::new(ClientConfig {
+            machine_id: Some("vm2".to_string()),
+            ..(*cfg).clone()
+        }),
+        record_config.clone(),
+    )
+    .await;
+    assert!(err.is_err());
+
+    // Records changed on the server are sent again in full
+    let record = client
+        .upsert(UpsertRecordRequest {
+            name: "keepalive.example.com".to_string(),
+            r#type: "A".to_string(),
+            ttl: 30,
+            values: vec!["10.0.0.1".to_string()],
+            force: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(record.revision, 2);
+    let records = update_record(cfg.clone(), record_config.clone())
+        .await
+        .unwrap();
+    assert_eq!(records[0].values, vec!["127.0.0.1"]);
+    assert_eq!(records[0].revision, 3);
+
+    // The TTL and lease come from the record config, changing them publishes the record again
+    let records = update_record(
+        cfg.clone(),
+        ClientRecordConfig {
+            ttl: Some(60),
+            lease: Some(120),
+            ..record_config.clone()
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(records[0].ttl, 60);
+    assert_eq!(records[0].lease, 120);
+    assert_eq!(records[0].revision, 4);
+    let state = UpdateState::load(&state_file).await;
+    assert_eq!(state.records[0].lease, 120);
+    std::fs::remove_file(&state_file).unwrap();
+    std::fs::remove_file(&client_state_file).unwrap();
+
     rpc_server_fut.abort();
 }
@@ -0,0 +1,820 @@
+//! Behaviour every [`RecordStore`] has to share, run against each backend with
+//! [`store_contract_tests`].
+
+use std::time::Duration;
+use swandns::proto::record_operation::Operation;
+use swandns::proto::{
+    FindUniqueRecordRequest, HealthFilter, HistoryAction, HistoryRequest, LeaseRenewal,
+    RecordOperation, RecordOrder, RecordsQueryRequest, UpsertRecordRequest,
+};
+use swandns::record_store::{acting_as, RecordError, RecordStore, DEFAULT_LEASE};
+use time::OffsetDateTime;
+
+/// Generates a test for each scenario, with a fresh store made by `$store`, an async expression
+/// returning `Option<Box<dyn RecordStore>>`. Scenarios are skipped when it returns `None`, e.g.
+/// when the backend's server isn't available.
+macro_rules! store_contract_tests {
+    ($store:expr) => {
+        store_contract_tests!(
+            $store;
+            test_rrsets,
+            test_views,
+            test_has_name,
+            test_revisions,
+            test_leases,
+            test_batch,
+            test_owners,
+            test_owned_deletes,
+            test_validation,
+            test_query,
+            test_history
+        );
+    };
+    ($store:expr; $($scenario:ident),*) => {
+        $(
+            #[tokio::test]
+            async fn $scenario() {
+                let Some(store) = $store.await else {
+                    eprintln!("Skipping {}, the store isn't available", stringify!($scenario));
+                    return;
+                };
+                store_contract::$scenario(&*store).await;
+            }
+        )*
+    };
+}
+
+/// Lets a lease of one second run out. SQL backends compare whole seconds, hence the margin.
+async fn expire_lease() {
+    tokio::time::sleep(Duration::from_millis(2100)).await;
+}
+
+fn find(name: &str, r#type: &str) -> FindUniqueRecordRequest {
+    FindUniqueRecordRequest {
+        name: name.to_string(),
+        r#type: r#type.to_string(),
+        ..Default::default()
+    }
+}
+
+pub async fn test_rrsets(store: &dyn RecordStore) {
+    // A single value
+    let record = store
+        .upsert(UpsertRecordRequest {
+            name: "foo.example.com".to_string(),
+            r#type: "A".to_string(),
+            value: "192.168.1.5".to_string(),
+            ttl: 30,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(record.data, "192.168.1.5");
+    assert_eq!(record.values, vec!["192.168.1.5"]);
+
+    // Upserting an RRset replaces the previous values
+    let values = vec!["192.168.1.6".to_string(), "192.168.1.7".to_string()];
+    let record = store
+        .upsert(UpsertRecordRequest {
+            name: "foo.example.com".to_string(),
+            r#type: "A".to_string(),
+            ttl: 60,
+            values: values.clone(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(record.data, "192.168.1.6");
+    assert_eq!(record.values, values);
+    assert_eq!(record.ttl, 60);
+
+    // RRsets are listed as a single record
+    let records = store.list().await.unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].values, values);
+
+    let record = store
+        .find_for_view(
+            "foo.example.com".to_string(),
+            "A".to_string(),
+            "lan".to_string(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(record.values, values);
+
+    // Delete removes the whole RRset
+    store.delete(find("foo.example.com", "A")).await.unwrap();
+    assert!(store.list().await.unwrap().is_empty());
+    let err = store
+        .find_unique(find("foo.example.com", "A"))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, RecordError::NotFound));
+}
+
+pub async fn test_views(store: &dyn RecordStore) {
+    let request = |view: &str, value: &str| UpsertRecordRequest {
+        name: "foo.example.com".to_string(),
+        r#type: "A".to_string(),
+        ttl: 30,
+        values: vec![value.to_string()],
+        view: view.to_string(),
+        ..Default::default()
+    };
+    let find_for_view = |view: &str| {
+        store.find_for_view(
+            "foo.example.com".to_string(),
+            "A".to_string(),
+            view.to_string(),
+        )
+    };
+    store.upsert(request("", "192.168.1.5")).await.unwrap();
+    store.upsert(request("internal", "10.0.0.5")).await.unwrap();
+
+    // Records tagged with a view take precedence in it, untagged ones are served elsewhere
+    let record = find_for_view("internal").await.unwrap();
+    assert_eq!(record.values, vec!["10.0.0.5"]);
+    let record = find_for_view("external").await.unwrap();
+    assert_eq!(record.values, vec!["192.168.1.5"]);
+
+    // A tagged RRset whose lease ran out isn't replaced by the untagged one
+    store
+        .upsert(UpsertRecordRequest {
+            lease: 1,
+            ..request("internal", "10.0.0.5")
+        })
+        .await
+        .unwrap();
+    expire_lease().await;
+    assert!(matches!(
+        find_for_view("internal").await.unwrap_err(),
+        RecordError::NotFound
+    ));
+}
+
+pub async fn test_has_name(store: &dyn RecordStore) {
+    store
+        .upsert(UpsertRecordRequest {
+            name: "foo.lan.example.com".to_string(),
+            r#type: "A".to_string(),
+            value: "192.168.1.5".to_string(),
+            ttl: 30,
+            view: "lan".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    store
+        .upsert(UpsertRecordRequest {
+            name: "gone.example.com".to_string(),
+            r#type: "A".to_string(),
+            value: "192.168.1.6".to_string(),
+            ttl: 30,
+            lease: 1,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    expire_lease().await;
+
+    // Names exist with their records and as parents of other names, in the record's view
+    for (name, view, exists) in [
+        ("foo.lan.example.com", "lan", true),
+        ("lan.example.com", "lan", true),
+        ("example.com", "lan", true),
+        ("oo.lan.example.com", "lan", false),
+        ("bar.foo.lan.example.com", "lan", false),
+        ("foo.lan.example.com", "wan", false),
+        ("gone.example.com", "lan", false),
+    ] {
+        let has_name = store
+            .has_name(name.to_string(), view.to_string())
+            .await
+            .unwrap();
+        assert_eq!(has_name, exists, "{} in {}", name, view);
+    }
+}
+
+pub async fn test_revisions(store: &dyn RecordStore) {
+    let request = UpsertRecordRequest {
+        name: "foo.example.com".to_string(),
+        r#type: "A".to_string(),
+        ttl: 30,
+        values: vec!["192.168.1.5".to_string()],
+        owner: "vm1".to_string(),
+        ..Default::default()
+    };
+    let renewal = |revision, owner: &str| LeaseRenewal {
+        name: "foo.example.com".to_string(),
+        r#type: "A".to_string(),
+        revision,
+        owner: owner.to_string(),
+        ..Default::default()
+    };
+
+    let record = store.upsert(request.clone()).await.unwrap();
+    assert_eq!(record.revision, 1);
+
+    // Refreshing the same values keeps the revision
+    let record = store.upsert(request.clone()).await.unwrap();
+    assert_eq!(record.revision, 1);
+
+    // Changing the values bumps it
+    let record = store
+        .upsert(UpsertRecordRequest {
+            values: vec!["192.168.1.5".to_string(), "192.168.1.6".to_string()],
+            ..request.clone()
+        })
+        .await
+        .unwrap();
+    assert_eq!(record.revision, 2);
+
+    // Reordering them doesn't
+    let record = store
+        .upsert(UpsertRecordRequest {
+            values: vec!["192.168.1.6".to_string(), "192.168.1.5".to_string()],
+            ..request.clone()
+        })
+        .await
+        .unwrap();
+    assert_eq!(record.revision, 2);
+
+    // Changing the TTL does
+    let record = store
+        .upsert(UpsertRecordRequest {
+            ttl: 60,
+            ..request.clone()
+        })
+        .await
+        .unwrap();
+    assert_eq!(record.revision, 3);
+
+    // Renewals only succeed for the current revision and owner
+    let results = store
+        .keep_alive(vec![
+            renewal(3, "vm1"),
+            renewal(2, "vm1"),
+            renewal(3, "vm2"),
+        ])
+        .await
+        .unwrap();
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].as_ref().unwrap().values, vec!["192.168.1.5"]);
+    assert_eq!(results[0].as_ref().unwrap().revision, 3);
+    assert!(results[1].is_none());
+    assert!(results[2].is_none());
+
+    // Or not at all once the record is gone
+    store
+        .delete(FindUniqueRecordRequest {
+            owner: "vm1".to_string(),
+            ..find("foo.example.com", "A")
+        })
+        .await
+        .unwrap();
+    assert!(store.keep_alive(vec![renewal(3, "vm1")]).await.unwrap()[0].is_none());
+}
+
+pub async fn test_leases(store: &dyn RecordStore) {
+    let request = UpsertRecordRequest {
+        name: "foo.example.com".to_string(),
+        r#type: "A".to_string(),
+        ttl: 30,
+        values: vec!["192.168.1.5".to_string()],
+        ..Default::default()
+    };
+    let find_for_view = || {
+        store.find_for_view(
+            "foo.example.com".to_string(),
+            "A".to_string(),
+            String::new(),
+        )
+    };
+
+    // Without a lease the server's default applies
+    let record = store.upsert(request.clone()).await.unwrap();
+    assert_eq!(record.lease, DEFAULT_LEASE);
+    assert_eq!(record.ttl, 30);
+
+    // The record is served until its lease runs out
+    let record = store
+        .upsert(UpsertRecordRequest {
+            lease: 1,
+            ..request.clone()
+        })
+        .await
+        .unwrap();
+    assert_eq!(record.lease, 1);
+    assert_eq!(record.revision, 1);
+    assert!(find_for_view().await.unwrap().healthy);
+    expire_lease().await;
+    assert!(find_for_view().await.is_err());
+    let record = store.list().await.unwrap().pop().unwrap();
+    assert!(!record.healthy);
+
+    // Renewing the lease brings it back
+    let results = store
+        .keep_alive(vec![LeaseRenewal {
+            name: "foo.example.com".to_string(),
+            r#type: "A".to_string(),
+            revision: 1,
+            ..Default::default()
+        }])
+        .await
+        .unwrap();
+    assert!(results[0].as_ref().unwrap().healthy);
+    assert!(find_for_view().await.is_ok());
+
+    // Static records never expire
+    store
+        .upsert(UpsertRecordRequest {
+            r#static: true,
+            lease: 1,
+            ..request.clone()
+        })
+        .await
+        .unwrap();
+    expire_lease().await;
+    assert!(find_for_view().await.unwrap().healthy);
+}
+
+pub async fn test_batch(store: &dyn RecordStore) {
+    let upsert = |r#type: &str, value: &str, if_revision| RecordOperation {
+        operation: Some(Operation::Upsert(UpsertRecordRequest {
+            name: "foo.example.com".to_string(),
+            r#type: r#type.to_string(),
+            ttl: 30,
+            values: vec![value.to_string()],
+            ..Default::default()
+        })),
+        if_revision,
+    };
+    let delete = |r#type: &str, if_revision| RecordOperation {
+        operation: Some(Operation::Delete(find("foo.example.com", r#type))),
+        if_revision,
+    };
+    let find = |r#type: &str| store.find_unique(find("foo.example.com", r#type));
+
+    // Every upsert is applied and returned in order
+    let reply = store
+        .batch(vec![
+            upsert("A", "192.168.1.5", 0),
+            upsert("AAAA", "fd00::5", 0),
+        ])
+        .await
+        .unwrap();
+    assert!(reply.applied);
+    assert_eq!(reply.records.len(), 2);
+    assert_eq!(reply.records[0].values, vec!["192.168.1.5"]);
+    assert_eq!(reply.records[1].values, vec!["fd00::5"]);
+
+    // A failed precondition applies nothing and reports the current RRset
+    let reply = store
+        .batch(vec![
+            upsert("A", "192.168.1.6", 1),
+            upsert("AAAA", "fd00::6", 2),
+        ])
+        .await
+        .unwrap();
+    assert!(!reply.applied);
+    assert!(reply.records.is_empty());
+    assert_eq!(reply.conflicts.len(), 1);
+    assert_eq!(reply.conflicts[0].r#type, "AAAA");
+    assert_eq!(reply.conflicts[0].revision, 1);
+    assert_eq!(find("A").await.unwrap().values, vec!["192.168.1.5"]);
+    assert_eq!(find("AAAA").await.unwrap().values, vec!["fd00::5"]);
+
+    // Matching revisions apply, deletes included
+    let reply = store
+        .batch(vec![upsert("A", "192.168.1.6", 1), delete("AAAA", 1)])
+        .await
+        .unwrap();
+    assert!(reply.applied);
+    assert_eq!(reply.records[0].revision, 2);
+    assert!(find("AAAA").await.is_err());
+
+    // RRsets that don't exist have no revision to match
+    let reply = store.batch(vec![delete("AAAA", 1)]).await.unwrap();
+    assert!(!reply.applied);
+    assert_eq!(reply.conflicts[0].revision, 0);
+
+    // Operations need an upsert or delete
+    let err = store
+        .batch(vec![RecordOperation::default()])
+        .await
+        .unwrap_err();
+    let RecordError::Invalid(violations) = err else {
+        panic!("unexpected error: {}", err);
+    };
+    assert_eq!(violations[0].field, "operations[0]");
+}
+
+pub async fn test_owners(store: &dyn RecordStore) {
+    let request = |owner: &str, value: &str| UpsertRecordRequest {
+        name: "foo.example.com".to_string(),
+        r#type: "A".to_string(),
+        ttl: 30,
+        values: vec![value.to_string()],
+        owner: owner.to_string(),
+        ..Default::default()
+    };
+
+    // Unowned records can be claimed by anyone
+    store.upsert(request("", "192.168.1.5")).await.unwrap();
+    let record = store.upsert(request("vm1", "192.168.1.5")).await.unwrap();
+    assert_eq!(record.owner, "vm1");
+
+    // Other publishers are rejected and the conflict is remembered
+    let err = store
+        .upsert(request("vm2", "192.168.1.6"))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, RecordError::Permission(_)));
+    let err = store
+        .batch(vec![RecordOperation {
+            operation: Some(Operation::Upsert(request("", "192.168.1.6"))),
+            ..Default::default()
+        }])
+        .await
+        .unwrap_err();
+    assert!(matches!(err, RecordError::Permission(_)));
+    let conflicts = store.conflicts().await.unwrap();
+    assert_eq!(conflicts.len(), 2);
+    assert_eq!(conflicts[0].challenger, "");
+    assert_eq!(conflicts[1].owner, "vm1");
+    assert_eq!(conflicts[1].challenger, "vm2");
+    let record = store.upsert(request("vm1", "192.168.1.5")).await.unwrap();
+    assert_eq!(record.values, vec!["192.168.1.5"]);
+
+    // Unless they force a takeover
+    let record = store
+        .upsert(UpsertRecordRequest {
+            force: true,
+            lease: 1,
+            ..request("vm2", "192.168.1.6")
+        })
+        .await
+        .unwrap();
+    assert_eq!(record.owner, "vm2");
+
+    // Or the owner stopped refreshing the record
+    expire_lease().await;
+    let record = store.upsert(request("vm1", "192.168.1.5")).await.unwrap();
+    assert_eq!(record.owner, "vm1");
+}
+
+pub async fn test_owned_deletes(store: &dyn RecordStore) {
+    let delete = |owner: &str, force| FindUniqueRecordRequest {
+        owner: owner.to_string(),
+        force,
+        ..find("foo.example.com", "A")
+    };
+    let publish = |lease| {
+        store.upsert(UpsertRecordRequest {
+            name: "foo.example.com".to_string(),
+            r#type: "A".to_string(),
+            ttl: 30,
+            values: vec!["192.168.1.5".to_string()],
+            owner: "vm1".to_string(),
+            lease,
+            ..Default::default()
+        })
+    };
+    publish(0).await.unwrap();
+
+    // Other publishers can't delete a healthy RRset and the attempt is remembered
+    let err = store.delete(delete("vm2", false)).await.unwrap_err();
+    assert!(matches!(err, RecordError::Permission(_)));
+    let err = store
+        .batch(vec![RecordOperation {
+            operation: Some(Operation::Delete(delete("", false))),
+            ..Default::default()
+        }])
+        .await
+        .unwrap_err();
+    assert!(matches!(err, RecordError::Permission(_)));
+    let conflicts = store.conflicts().await.unwrap();
+    assert_eq!(conflicts.len(), 2);
+    assert!(conflicts.iter().all(|conflict| conflict.owner == "vm1"));
+    assert!(store.find_unique(delete("", false)).await.is_ok());
+
+    // The owner can
+    store.delete(delete("vm1", false)).await.unwrap();
+    assert!(store.find_unique(delete("", false)).await.is_err());
+
+    // Others only with force
+    publish(0).await.unwrap();
+    store.delete(delete("vm2", true)).await.unwrap();
+    assert!(store.find_unique(delete("", false)).await.is_err());
+
+    // Or once the owner stopped refreshing it
+    publish(1).await.unwrap();
+    expire_lease().await;
+    store
+        .batch(vec![RecordOperation {
+            operation: Some(Operation::Delete(delete("vm2", false))),
+            ..Default::default()
+        }])
+        .await
+        .unwrap();
+    assert!(store.find_unique(delete("", false)).await.is_err());
+}
+
+pub async fn test_validation(store: &dyn RecordStore) {
+    // Every problem with the request is reported
+    let err = store
+        .upsert(UpsertRecordRequest {
+            r#type: "banana".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap_err();
+    let RecordError::Invalid(violations) = err else {
+        panic!("unexpected error: {}", err);
+    };
+    let fields: Vec<&str> = violations
+        .iter()
+        .map(|violation| violation.field.as_str())
+        .collect();
+    assert_eq!(fields, vec!["name", "type", "values"]);
+
+    // Batches are rejected as a whole, naming the operation
+    let err = store
+        .batch(vec![
+            RecordOperation {
+                operation: Some(Operation::Upsert(UpsertRecordRequest {
+                    name: "foo.example.com".to_string(),
+                    r#type: "A".to_string(),
+                    values: vec!["192.168.1.5".to_string()],
+                    ..Default::default()
+                })),
+                ..Default::default()
+            },
+            RecordOperation {
+                operation: Some(Operation::Upsert(UpsertRecordRequest {
+                    name: "bar.example.com".to_string(),
+                    r#type: "MX2".to_string(),
+                    values: vec!["192.168.1.6".to_string()],
+                    ..Default::default()
+                })),
+                ..Default::default()
+            },
+        ])
+        .await
+        .unwrap_err();
+    let RecordError::Invalid(violations) = err else {
+        panic!("unexpected error: {}", err);
+    };
+    assert_eq!(violations.len(), 1);
+    assert_eq!(violations[0].field, "operations[1].upsert.type");
+    assert!(store.list().await.unwrap().is_empty());
+
+    // Missing records aren't a storage error
+    let err = store
+        .find_unique(find("foo.example.com", "A"))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, RecordError::NotFound));
+}
+
+pub async fn test_query(store: &dyn RecordStore) {
+    let upsert = |name: &str, r#type: &str, value: &str, owner: &str| UpsertRecordRequest {
+        name: name.to_string(),
+        r#type: r#type.to_string(),
+        ttl: 30,
+        values: vec![value.to_string()],
+        owner: owner.to_string(),
+        ..Default::default()
+    };
+    // web-2 is updated first and expires
+    store
+        .upsert(UpsertRecordRequest {
+            lease: 1,
+            ..upsert("web-2.example.com", "A", "192.168.1.3", "vm3")
+        })
+        .await
+        .unwrap();
+    expire_lease().await;
+    let since = OffsetDateTime::now_utc().unix_timestamp();
+    for request in [
+        upsert("a.example.com", "A", "192.168.1.1", "vm1"),
+        upsert("a.example.com", "AAAA", "::1", "vm1"),
+        upsert("web-1.example.com", "A", "192.168.1.2", "vm2"),
+        upsert("web-1.example.org", "A", "192.168.1.4", "vm2"),
+        UpsertRecordRequest {
+            view: "lan".to_string(),
+            ..upsert("b.example.org", "A", "10.0.0.1", "")
+        },
+    ] {
+        store.upsert(request).await.unwrap();
+    }
+    let names = |query: RecordsQueryRequest| async move {
+        store
+            .query(query)
+            .await
+            .unwrap()
+            .records
+            .into_iter()
+            .map(|record| format!("{} {}", record.name, record.r#type))
+            .collect::<Vec<String>>()
+    };
+
+    // Filters
+    let by_zone = RecordsQueryRequest {
+        zone: "example.com".to_string(),
+        ..Default::default()
+    };
+    assert_eq!(names(by_zone.clone()).await.len(), 4);
+    let by_name = RecordsQueryRequest {
+        name: "web-*".to_string(),
+        ..Default::default()
+    };
+    assert_eq!(
+        names(by_name).await,
+        vec![
+            "web-1.example.com A",
+            "web-1.example.org A",
+            "web-2.example.com A"
+        ]
+    );
+    let by_class = RecordsQueryRequest {
+        zone: "example.com".to_string(),
+        name: "web-[0-9].*".to_string(),
+        ..Default::default()
+    };
+    assert_eq!(
+        names(by_class).await,
+        vec!["web-1.example.com A", "web-2.example.com A"]
+    );
+    let by_type = RecordsQueryRequest {
+        r#type: "AAAA".to_string(),
+        ..Default::default()
+    };
+    assert_eq!(names(by_type).await, vec!["a.example.com AAAA"]);
+    let by_owner = RecordsQueryRequest {
+        owner: "vm1".to_string(),
+        ..Default::default()
+    };
+    assert_eq!(names(by_owner).await.len(), 2);
+    let by_view = RecordsQueryRequest {
+        view: "lan".to_string(),
+        ..Default::default()
+    };
+    assert_eq!(names(by_view).await, vec!["b.example.org A"]);
+    let all_views = RecordsQueryRequest {
+        view: "*".to_string(),
+        ..Default::default()
+    };
+    assert_eq!(names(all_views).await.len(), 5);
+    let mut unhealthy = RecordsQueryRequest::default();
+    unhealthy.set_health(HealthFilter::Unhealthy);
+    assert_eq!(names(unhealthy).await, vec!["web-2.example.com A"]);
+    let mut healthy = RecordsQueryRequest::default();
+    healthy.set_health(HealthFilter::Healthy);
+    assert_eq!(names(healthy).await.len(), 5);
+    let recent = RecordsQueryRequest {
+        updated_since: since,
+        ..Default::default()
+    };
+    assert_eq!(names(recent).await.len(), 5);
+
+    // Pages continue after the last RRset of the previous one
+    let mut query = RecordsQueryRequest {
+        page_size: 3,
+        descending: true,
+        ..by_zone.clone()
+    };
+    query.set_order_by(RecordOrder::Name);
+    let page = store.query(query.clone()).await.unwrap();
+    assert_eq!(page.records.len(), 3);
+    assert_eq!(page.records[0].name, "web-2.example.com");
+    assert!(!page.next_page_token.is_empty());
+    let page = store
+        .query(RecordsQueryRequest {
+            page_token: page.next_page_token,
+            ..query.clone()
+        })
+        .await
+        .unwrap();
+    assert_eq!(page.records.len(), 1);
+    assert_eq!(page.records[0].r#type, "A");
+    assert!(page.next_page_token.is_empty());
+
+    // Ordered by update, web-2 was updated first
+    query.set_order_by(RecordOrder::UpdatedAt);
+    query.descending = false;
+    let page = store.query(query.clone()).await.unwrap();
+    assert_eq!(page.records[0].name, "web-2.example.com");
+
+    // Tokens from another order are rejected
+    let mut other_order = query.clone();
+    other_order.set_order_by(RecordOrder::Name);
+    let token = store.query(other_order).await.unwrap().next_page_token;
+    let err = store
+        .query(RecordsQueryRequest {
+            page_token: token,
+            ..query
+        })
+        .await
+        .unwrap_err();
+    assert!(matches!(err, RecordError::Invalid(_)));
+}
+
+pub async fn test_history(store: &dyn RecordStore) {
+    let request = |value: &str| UpsertRecordRequest {
+        name: "foo.example.com".to_string(),
+        r#type: "A".to_string(),
+        ttl: 30,
+        values: vec![value.to_string()],
+        owner: "vm1".to_string(),
+        lease: 1,
+        ..Default::default()
+    };
+    let history = |request: HistoryRequest| store.history(request);
+
+    // Changes are recorded with who made them
+    acting_as(
+        "192.0.2.1".to_string(),
+        store.upsert(request("192.168.1.5")),
+    )
+    .await
+    .unwrap();
+    acting_as(
+        "192.0.2.1".to_string(),
+        store.upsert(request("192.168.1.6")),
+    )
+    .await
+    .unwrap();
+    let entries = history(Default::default()).await.unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].action(), HistoryAction::Upsert);
+    assert_eq!(entries[0].old_values, vec!["192.168.1.5"]);
+    assert_eq!(entries[0].new_values, vec!["192.168.1.6"]);
+    assert_eq!(entries[0].revision, 2);
+    assert_eq!(entries[0].owner, "vm1");
+    assert_eq!(entries[0].actor, "192.0.2.1");
+    assert!(entries[1].old_values.is_empty());
+
+    // Refreshing an unchanged RRset isn't a change
+    store.upsert(request("192.168.1.6")).await.unwrap();
+    assert_eq!(history(Default::default()).await.unwrap().len(), 2);
+
+    // Taking it over is
+    store
+        .upsert(UpsertRecordRequest {
+            owner: "vm2".to_string(),
+            force: true,
+            ..request("192.168.1.6")
+        })
+        .await
+        .unwrap();
+    let entries = history(Default::default()).await.unwrap();
+    assert_eq!(entries.len(), 3);
+    assert_eq!(entries[0].owner, "vm2");
+    assert_eq!(entries[0].revision, 2);
+
+    // Expired leases are recorded once
+    expire_lease().await;
+    assert_eq!(store.record_expiries().await.unwrap(), 1);
+    assert_eq!(store.record_expiries().await.unwrap(), 0);
+    let entries = history(Default::default()).await.unwrap();
+    assert_eq!(entries[0].action(), HistoryAction::Expire);
+    assert_eq!(entries[0].old_values, vec!["192.168.1.6"]);
+    assert!(entries[0].new_values.is_empty());
+
+    // Deletes too
+    store.delete(find("foo.example.com", "A")).await.unwrap();
+    store
+        .upsert(UpsertRecordRequest {
+            name: "bar.example.org".to_string(),
+            ..request("192.168.1.7")
+        })
+        .await
+        .unwrap();
+    let entries = history(HistoryRequest {
+        zone: "example.com".to_string(),
+        limit: 2,
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].action(), HistoryAction::Delete);
+    assert_eq!(entries[0].old_values, vec!["192.168.1.6"]);
+    let entries = history(HistoryRequest {
+        actor: "192.0.2.1".to_string(),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    assert_eq!(entries.len(), 2);
+
+    // Changes older than the retention are pruned
+    let pruned = store
+        .prune_history(OffsetDateTime::now_utc() + time::Duration::seconds(1))
+        .await
+        .unwrap();
+    assert_eq!(pruned, 6);
+    assert!(history(Default::default()).await.unwrap().is_empty());
+}
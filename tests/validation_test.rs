@@ -0,0 +1,117 @@
+use swandns::proto::{FindUniqueRecordRequest, UpsertRecordRequest};
+use swandns::validation::{normalize_name, validate_find, validate_upsert};
+use swandns::{ServerConfig, ViewConfig, ZoneConfig};
+
+fn config() -> ServerConfig {
+    ServerConfig {
+        zones: vec![ZoneConfig {
+            name: "example.com".to_string(),
+            protected_names: vec!["@".to_string(), "*.infra".to_string()],
+            ..Default::default()
+        }],
+        views: vec![ViewConfig {
+            name: "lan".to_string(),
+            zones: vec![ZoneConfig {
+                name: "lan.example.org".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }],
+        ..Default::default()
+    }
+}
+
+fn upsert(name: &str, r#type: &str, value: &str) -> UpsertRecordRequest {
+    UpsertRecordRequest {
+        name: name.to_string(),
+        r#type: r#type.to_string(),
+        values: vec![value.to_string()],
+        ..Default::default()
+    }
+}
+
+fn fields(cfg: &ServerConfig, request: &mut UpsertRecordRequest) -> Vec<String> {
+    validate_upsert(cfg, request, "")
+        .into_iter()
+        .map(|violation| violation.field)
+        .collect()
+}
+
+#[test]
+fn test_normalize_name() {
+    assert_eq!(
+        normalize_name("Foo.Example.com.").unwrap(),
+        "foo.example.com"
+    );
+    assert_eq!(
+        normalize_name("bücher.example.com").unwrap(),
+        "xn--bcher-kva.example.com"
+    );
+    assert!(normalize_name("").is_err());
+    assert!(normalize_name(".").is_err());
+    assert!(normalize_name("foo..example.com").is_err());
+}
+
+#[test]
+fn test_validate_upsert() {
+    let cfg = config();
+
+    // Names and types are normalized
+    let mut request = upsert("Foo.Example.COM.", "aaaa", "::1");
+    assert!(fields(&cfg, &mut request).is_empty());
+    assert_eq!(request.name, "foo.example.com");
+    assert_eq!(request.r#type, "AAAA");
+
+    // Values have to parse as the type
+    let mut request = upsert("foo.example.com", "A", "::1");
+    assert_eq!(fields(&cfg, &mut request), vec!["values[0]"]);
+    let mut request = upsert("foo.example.com", "banana", "::1");
+    assert_eq!(fields(&cfg, &mut request), vec!["type"]);
+
+    // Names have to be in a zone served to the view
+    let mut request = upsert("foo.example.net", "A", "192.168.1.5");
+    assert_eq!(fields(&cfg, &mut request), vec!["name"]);
+    let mut request = upsert("foo.lan.example.org", "A", "192.168.1.5");
+    assert!(fields(&cfg, &mut request).is_empty());
+    request.view = "lan".to_string();
+    assert!(fields(&cfg, &mut request).is_empty());
+    let mut request = upsert("foo.example.com", "A", "192.168.1.5");
+    request.view = "lan".to_string();
+    assert_eq!(fields(&cfg, &mut request), vec!["name"]);
+    request.view = "wan".to_string();
+    assert_eq!(fields(&cfg, &mut request), vec!["view"]);
+
+    // Protected names and types can't be published
+    let mut request = upsert("example.com", "A", "192.168.1.5");
+    assert_eq!(fields(&cfg, &mut request), vec!["name"]);
+    let mut request = upsert("ns1.infra.example.com", "A", "192.168.1.5");
+    assert_eq!(fields(&cfg, &mut request), vec!["name"]);
+    let mut request = upsert("infra.example.com", "A", "192.168.1.5");
+    assert!(fields(&cfg, &mut request).is_empty());
+    let mut request = upsert("foo.example.com", "NS", "ns1.example.com.");
+    assert_eq!(fields(&cfg, &mut request), vec!["type"]);
+
+    // The apex is protected unless the zone allows it
+    let mut request = upsert("lan.example.org", "A", "192.168.1.5");
+    assert_eq!(fields(&cfg, &mut request), vec!["name"]);
+    let mut cfg = cfg;
+    cfg.views[0].zones[0].allow_apex = true;
+    let mut request = upsert("lan.example.org", "A", "192.168.1.5");
+    assert!(fields(&cfg, &mut request).is_empty());
+}
+
+#[test]
+fn test_validate_find() {
+    let mut request = FindUniqueRecordRequest {
+        name: "Foo.Example.net.".to_string(),
+        r#type: "a".to_string(),
+        ..Default::default()
+    };
+    assert!(validate_find(&mut request, "").is_empty());
+    assert_eq!(request.name, "foo.example.net");
+    assert_eq!(request.r#type, "A");
+
+    request.name = String::new();
+    let violations = validate_find(&mut request, "operations[0].delete.");
+    assert_eq!(violations[0].field, "operations[0].delete.name");
+}
@@ -0,0 +1,308 @@
+use std::sync::Arc;
+use swandns::proto::{ChangeAction, ExportRequest, ImportRequest};
+use swandns::record_repository::RecordRepository;
+use swandns::record_store::{RecordError, RecordStore};
+use swandns::util::migrate_database;
+use swandns::zone_file::{export_zone_file, import_zone_file};
+use swandns::{RecordConfig, ServerConfig, ZoneConfig};
+use tokio_rusqlite::Connection;
+
+static ZONE_FILE: &str = r#"
+$ORIGIN example.com.
+$TTL 300
+@       IN SOA ns1 hostmaster 1 7200 3600 1209600 300
+foo     IN A     192.168.1.5
+www     IN CNAME foo
+bar 60  IN AAAA  fd00::6
+"#;
+
+#[tokio::test]
+async fn test_import_export() {
+    let conn = Arc::new(Connection::open_in_memory().await.unwrap());
+    migrate_database(conn.clone()).await.unwrap();
+    let repo = RecordRepository { conn };
+    let cfg = ServerConfig {
+        zones: vec![ZoneConfig {
+            name: "example.com".to_string(),
+            records: vec![
+                RecordConfig {
+                    key: "baz".to_string(),
+                    value: "192.168.1.9".to_string(),
+                    ..Default::default()
+                },
+                RecordConfig {
+                    key: "@".to_string(),
+                    r#type: Some("MX".to_string()),
+                    values: vec![
+                        "10 mx1.example.com.".to_string(),
+                        "20 mx2.example.com.".to_string(),
+                    ],
+                    ttl: Some(3600),
+                    comment: Some("Mail servers".to_string()),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+
+    // Dry run reports the changes without making them
+    let reply = import_zone_file(
+        &cfg,
+        &repo,
+        ImportRequest {
+            zone_file: ZONE_FILE.to_string(),
+            dry_run: true,
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(reply.changes.len(), 3);
+    assert!(reply
+        .changes
+        .iter()
+        .all(|change| change.action() == ChangeAction::Add));
+    assert!(repo.list().await.unwrap().is_empty());
+
+    // Import
+    import_zone_file(
+        &cfg,
+        &repo,
+        ImportRequest {
+            zone_file: ZONE_FILE.to_string(),
+            r#static: true,
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+    let records = repo.list().await.unwrap();
+    assert_eq!(records.len(), 3);
+    assert!(records.iter().all(|record| record.r#static));
+    assert!(records.iter().any(|record| record.name == "www.example.com"
+        && record.r#type == "CNAME"
+        && record.data == "foo.example.com."));
+
+    // Importing again only reports the differences
+    let reply = import_zone_file(
+        &cfg,
+        &repo,
+        ImportRequest {
+            zone_file: "foo 300 IN A 192.168.1.7\nbar 60 IN AAAA fd00::6\n".to_string(),
+            origin: "example.com".to_string(),
+            r#static: true,
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+    let actions: Vec<ChangeAction> = reply.changes.iter().map(|c| c.action()).collect();
+    assert_eq!(actions, vec![ChangeAction::Unchanged, ChangeAction::Update]);
+    assert_eq!(reply.changes[1].previous_data, "192.168.1.5");
+
+    // Multiple records for a name and type are imported as an RRset
+    let reply = import_zone_file(
+        &cfg,
+        &repo,
+        ImportRequest {
+            zone_file: "multi 300 IN A 192.168.1.10\nmulti 300 IN A 192.168.1.11\n".to_string(),
+            origin: "example.com".to_string(),
+            r#static: true,
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(reply.changes.len(), 1);
+    assert_eq!(
+        reply.changes[0].record.as_ref().unwrap().values,
+        vec!["192.168.1.10", "192.168.1.11"]
+    );
+
+    // Export includes the static records from the config
+    let reply = export_zone_file(
+        &cfg,
+        &repo,
+        ExportRequest {
+            include_static: true,
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+    let lines: Vec<&str> = reply
+        .zone_file
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with(';'))
+        .collect();
+    assert_eq!(
+        lines,
+        vec![
+            "baz.example.com. 30 IN A 192.168.1.9",
+            "example.com. 3600 IN MX 10 mx1.example.com.",
+            "example.com. 3600 IN MX 20 mx2.example.com.",
+            "bar.example.com. 60 IN AAAA fd00::6",
+            "foo.example.com. 300 IN A 192.168.1.7",
+            "multi.example.com. 300 IN A 192.168.1.10",
+            "multi.example.com. 300 IN A 192.168.1.11",
+            "www.example.com. 300 IN CNAME foo.example.com.",
+        ]
+    );
+    assert!(reply.zone_file.contains("; Mail servers\n"));
+
+    // Zone filter
+    let reply = export_zone_file(
+        &cfg,
+        &repo,
+        ExportRequest {
+            zone: "example.org".to_string(),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+    assert!(!reply.zone_file.contains("example.com."));
+}
+
+#[tokio::test]
+async fn test_import_validation() {
+    let conn = Arc::new(Connection::open_in_memory().await.unwrap());
+    migrate_database(conn.clone()).await.unwrap();
+    let repo = RecordRepository { conn };
+    let cfg = ServerConfig {
+        zones: vec![ZoneConfig {
+            name: "example.com".to_string(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+
+    // Names are stored in lowercase and the apex NS records are left to the server
+    let reply = import_zone_file(
+        &cfg,
+        &repo,
+        ImportRequest {
+            zone_file: "@ 300 IN NS ns1.example.com.\nFoo.Example.COM. 300 IN A 192.168.1.5\n"
+                .to_string(),
+            origin: "example.com".to_string(),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(reply.changes.len(), 1);
+    assert_eq!(
+        reply.changes[0].record.as_ref().unwrap().name,
+        "foo.example.com"
+    );
+
+    // Names outside the zones and delegations are rejected, and nothing is imported
+    let err = import_zone_file(
+        &cfg,
+        &repo,
+        ImportRequest {
+            zone_file: "bar.example.com. 300 IN A 192.168.1.6\n\
+                        foo.example.org. 300 IN A 192.168.1.7\n\
+                        sub.example.com. 300 IN NS ns1.example.org.\n"
+                .to_string(),
+            origin: "example.com".to_string(),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap_err();
+    let Ok(RecordError::Invalid(violations)) = err.downcast::<RecordError>() else {
+        panic!("Expected violations");
+    };
+    let mut fields: Vec<&str> = violations.iter().map(|v| v.field.as_str()).collect();
+    fields.sort();
+    assert_eq!(
+        fields,
+        vec![
+            "records[foo.example.org. A].name",
+            "records[sub.example.com. NS].type"
+        ]
+    );
+    assert_eq!(repo.list().await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_txt_round_trip() {
+    let conn = Arc::new(Connection::open_in_memory().await.unwrap());
+    migrate_database(conn.clone()).await.unwrap();
+    let repo = RecordRepository { conn };
+    let cfg = ServerConfig {
+        zones: vec![ZoneConfig {
+            name: "example.com".to_string(),
+            records: vec![RecordConfig {
+                key: "dkim".to_string(),
+                r#type: Some("TXT".to_string()),
+                value: r#""v=DKIM1; k=rsa" "p=MIGf""#.to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+
+    // Character-strings with spaces stay intact in the database
+    import_zone_file(
+        &cfg,
+        &repo,
+        ImportRequest {
+            zone_file: "spf 300 IN TXT \"v=spf1 mx -all\" \"say \\\"hi\\\"\"\n".to_string(),
+            origin: "example.com".to_string(),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+    let records = repo.list().await.unwrap();
+    assert_eq!(records[0].values, vec![r#""v=spf1 mx -all" "say \"hi\"""#]);
+
+    // Exports, static records included, import back unchanged
+    let reply = export_zone_file(
+        &cfg,
+        &repo,
+        ExportRequest {
+            include_static: true,
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+    assert!(reply
+        .zone_file
+        .contains(r#"dkim.example.com. 30 IN TXT "v=DKIM1; k=rsa" "p=MIGf""#));
+    let reply = import_zone_file(
+        &cfg,
+        &repo,
+        ImportRequest {
+            zone_file: reply.zone_file,
+            origin: "example.com".to_string(),
+            dry_run: true,
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+    let changes: Vec<(&str, ChangeAction)> = reply
+        .changes
+        .iter()
+        .map(|change| {
+            (
+                change.record.as_ref().unwrap().data.as_str(),
+                change.action(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        changes,
+        vec![
+            (r#""v=DKIM1; k=rsa" "p=MIGf""#, ChangeAction::Add),
+            (r#""v=spf1 mx -all" "say \"hi\"""#, ChangeAction::Unchanged),
+        ]
+    );
+}
@@ -0,0 +1,106 @@
+use hickory_server::proto::rr::RData;
+use std::fs;
+use swandns::dns_server::static_rrset;
+use swandns::zone_includes::{included_paths, load_server_config};
+
+#[tokio::test]
+async fn test_includes() {
+    let dir = std::env::temp_dir().join(format!("swandns-includes-{}", rand::random::<u32>()));
+    fs::create_dir_all(dir.join("records")).unwrap();
+    fs::write(
+        dir.join("server.yaml"),
+        r#"
+zones:
+  - name: example.com
+    records:
+      - key: www
+        value: 192.168.1.5
+    zone_file: db.example.com
+    records_dir: records
+"#,
+    )
+    .unwrap();
+    fs::write(
+        dir.join("db.example.com"),
+        r#"
+$TTL 3600
+@     IN SOA ns1.example.com. admin.example.com. 1 7200 3600 1209600 3600
+@     IN MX  10 mx1.example.com.
+mail  IN A   192.168.1.6
+Ftp.Example.COM. IN A 192.168.1.7
+spf   IN TXT "v=spf1 mx -all" "say \"hi\""
+"#,
+    )
+    .unwrap();
+    fs::write(
+        dir.join("records/b.yaml"),
+        "records:\n  - key: db\n    value: 192.168.1.8\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("records/a.yaml"),
+        "records:\n  - key: app\n    type: TXT\n    value: hello\n    ttl: 60\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("records/c.toml"),
+        "[[records]]\nkey = \"api\"\nvalues = [\"192.168.1.10\", \"192.168.1.11\"]\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("records/d.json"),
+        r#"{"records": [{"key": "cache", "type": "AAAA", "value": "fd00::7"}]}"#,
+    )
+    .unwrap();
+    fs::write(dir.join("records/README"), "not a snippet").unwrap();
+
+    // Included records follow the zone's own, snippets in the order of their names
+    let cfg = load_server_config("server", &dir.join("server.yaml"))
+        .await
+        .unwrap();
+    let zone = &cfg.zones[0];
+    let keys: Vec<(&str, Option<&str>)> = zone
+        .records
+        .iter()
+        .map(|record| (record.key.as_str(), record.r#type.as_deref()))
+        .collect();
+    assert_eq!(
+        keys,
+        vec![
+            ("www", None),
+            ("@", Some("MX")),
+            ("ftp", Some("A")),
+            ("mail", Some("A")),
+            ("spf", Some("TXT")),
+            ("app", Some("TXT")),
+            ("db", None),
+            ("api", None),
+            ("cache", Some("AAAA")),
+        ]
+    );
+    let mx = static_rrset(zone, &zone.records[1]).unwrap();
+    assert_eq!(mx[0].ttl(), 3600);
+    assert_eq!(mx[0].name().to_string(), "example.com");
+    let txt = static_rrset(zone, &zone.records[4]).unwrap();
+    let Some(RData::TXT(txt)) = txt[0].data() else {
+        panic!("Expected TXT data");
+    };
+    let strings: Vec<&[u8]> = txt.txt_data().iter().map(|data| &data[..]).collect();
+    assert_eq!(strings, vec![&b"v=spf1 mx -all"[..], &b"say \"hi\""[..]]);
+    assert_eq!(zone.records[7].values, vec!["192.168.1.10", "192.168.1.11"]);
+    assert_eq!(
+        included_paths(&cfg),
+        vec![dir.join("db.example.com"), dir.join("records")]
+    );
+
+    // Errors name the zone and file
+    fs::write(dir.join("records/e.yaml"), "records: [not a record\n").unwrap();
+    let err = load_server_config("server", &dir.join("server.yaml"))
+        .await
+        .unwrap_err();
+    let err = format!("{:#}", err);
+    assert!(err.contains("\"example.com\""));
+    assert!(err.contains("e.yaml"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}